@@ -1,6 +1,23 @@
+use std::sync::Arc;
+
 use mongodb::Database;
 
+use super::settings::Settings;
+use crate::{
+    mail::mailer::Mailer, payments::provider::PaymentProvider, ratelimit::store::RateLimitStore,
+    search::backend::SearchBackend, services::role_service::RoleCache,
+    storage::object_storage::ObjectStorage, utils::jwt::JwtKeys,
+};
+
 #[derive(Clone)]
 pub struct AppState {
     pub db: Database,
+    pub settings: Settings,
+    pub jwt: Arc<JwtKeys>,
+    pub payments: Arc<dyn PaymentProvider>,
+    pub storage: Arc<dyn ObjectStorage>,
+    pub mailer: Arc<dyn Mailer>,
+    pub roles: Arc<RoleCache>,
+    pub limiter: Arc<dyn RateLimitStore>,
+    pub search: Arc<dyn SearchBackend>,
 }
@@ -0,0 +1,518 @@
+use std::{env, fmt, fs, net::SocketAddr, path::Path};
+
+use toml::Table;
+
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+#[derive(Debug, Clone)]
+pub struct ServerSettings {
+    pub bind_address: SocketAddr,
+    pub app_url: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct DatabaseSettings {
+    pub uri: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JwtAlgorithmKind {
+    Rs256,
+    EdDsa,
+}
+
+/// Access tokens are signed with the private key in `private_key_file` and
+/// verified against the public keys in `public_keys_file`, a JWKS document
+/// that is also served at `/.well-known/jwks.json`. To rotate, publish the
+/// new public key next to the old one, switch `signing_kid` and the private
+/// key, then drop the old public key once its tokens have expired.
+#[derive(Debug, Clone)]
+pub struct JwtSettings {
+    pub algorithm: JwtAlgorithmKind,
+    pub issuer: String,
+    pub audience: String,
+    pub signing_kid: String,
+    pub private_key_file: String,
+    pub public_keys_file: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SmtpSettings {
+    pub host: String,
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MailTransportKind {
+    Smtp,
+    File,
+    Memory,
+}
+
+#[derive(Debug, Clone)]
+pub struct MailSettings {
+    pub transport: MailTransportKind,
+    /// Shop name used in the subject and body of every email.
+    pub brand: String,
+    pub from: String,
+    pub reply_to: String,
+    pub smtp: SmtpSettings,
+    /// Directory the `file` transport writes `.eml` files to.
+    pub drop_dir: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageBackendKind {
+    S3,
+    Local,
+}
+
+#[derive(Debug, Clone)]
+pub struct S3Settings {
+    pub bucket_name: String,
+    pub region: String,
+    /// Custom endpoint for S3 compatible services such as MinIO.
+    pub endpoint: Option<String>,
+    pub force_path_style: bool,
+    /// Base url objects are served from when it is not the bucket itself,
+    /// e.g. a CDN in front of it.
+    pub public_url: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LocalStorageSettings {
+    pub root: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct StorageSettings {
+    pub backend: StorageBackendKind,
+    pub s3: S3Settings,
+    pub local: LocalStorageSettings,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaymentProviderKind {
+    Mock,
+    Stripe,
+}
+
+#[derive(Debug, Clone)]
+pub struct PaymentSettings {
+    pub provider: PaymentProviderKind,
+    pub currency: String,
+    pub webhook_secret: String,
+    pub stripe_secret_key: String,
+    pub stripe_api_base: String,
+}
+
+/// Where rate limit counters are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitStoreKind {
+    Memory,
+    Mongo,
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimitSettings {
+    /// `memory` counts per instance, `mongo` shares counters between instances.
+    pub store: RateLimitStoreKind,
+    /// Proxies in front of the app that append to `X-Forwarded-For`. The
+    /// client ip is the entry this many hops from the right, 0 ignores the
+    /// header and uses the peer address.
+    pub trusted_proxies: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchBackendKind {
+    Atlas,
+    Embedded,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchSettings {
+    /// `atlas` needs an Atlas Search index on `products`, `embedded` keeps a
+    /// full-text index inside the process and works with any MongoDB.
+    pub backend: SearchBackendKind,
+    /// Name of the Atlas Search index.
+    pub atlas_index: String,
+    /// Directory the embedded index is kept in, in memory when not set.
+    pub index_dir: Option<String>,
+}
+
+/// Application configuration, loaded once at startup.
+///
+/// Every value is looked up in the environment first (a `.env` file is loaded
+/// into it by `main`) and then in an optional TOML file, `config.toml` or the
+/// path given by `CONFIG_FILE`, under `[section] key`.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub server: ServerSettings,
+    pub database: DatabaseSettings,
+    pub jwt: JwtSettings,
+    pub mail: MailSettings,
+    pub storage: StorageSettings,
+    pub payments: PaymentSettings,
+    pub rate_limit: RateLimitSettings,
+    pub search: SearchSettings,
+}
+
+/// Every problem found while loading the configuration, so they can all be
+/// fixed in one go instead of one restart per missing variable.
+#[derive(Debug)]
+pub struct SettingsError(pub Vec<String>);
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "invalid configuration:")?;
+        for error in &self.0 {
+            writeln!(f, "  - {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+struct Source {
+    file: Table,
+    errors: Vec<String>,
+}
+
+impl Source {
+    fn lookup(&self, section: &str, key: &str, env_name: &str) -> Option<String> {
+        if let Ok(value) = env::var(env_name) {
+            return Some(value);
+        }
+
+        match self.file.get(section).and_then(|s| s.get(key)) {
+            Some(toml::Value::String(value)) => Some(value.clone()),
+            Some(value) => Some(value.to_string()),
+            None => None,
+        }
+    }
+
+    fn required(&mut self, section: &str, key: &str, env_name: &str) -> String {
+        match self.lookup(section, key, env_name) {
+            Some(value) if !value.trim().is_empty() => value,
+            _ => {
+                self.errors.push(format!(
+                    "{} is not set (env `{}` or `[{}] {}`)",
+                    env_name, env_name, section, key
+                ));
+                String::new()
+            }
+        }
+    }
+
+    fn optional(&self, section: &str, key: &str, env_name: &str, default: &str) -> String {
+        self.lookup(section, key, env_name)
+            .filter(|value| !value.trim().is_empty())
+            .unwrap_or_else(|| default.to_string())
+    }
+}
+
+fn read_config_file() -> Result<Table, String> {
+    let (path, explicit) = match env::var("CONFIG_FILE") {
+        Ok(path) => (path, true),
+        Err(_) => (DEFAULT_CONFIG_FILE.to_string(), false),
+    };
+
+    if !explicit && !Path::new(&path).exists() {
+        return Ok(Table::new());
+    }
+
+    let content =
+        fs::read_to_string(&path).map_err(|e| format!("failed to read {}: {}", path, e))?;
+
+    content
+        .parse::<Table>()
+        .map_err(|e| format!("failed to parse {}: {}", path, e))
+}
+
+impl Settings {
+    pub fn load() -> Result<Self, SettingsError> {
+        let (file, errors) = match read_config_file() {
+            Ok(file) => (file, vec![]),
+            Err(e) => (Table::new(), vec![e]),
+        };
+        let mut source = Source { file, errors };
+
+        let bind_address =
+            source.optional("server", "bind_address", "BIND_ADDRESS", "127.0.0.1:3000");
+        let app_url = source.optional("server", "app_url", "APP_URL", "http://localhost:3000");
+
+        let database = DatabaseSettings {
+            uri: source.required("database", "uri", "MONGODB_URI"),
+            name: source.required("database", "name", "DATABASE_NAME"),
+        };
+        let jwt_algorithm = source.optional("jwt", "algorithm", "JWT_ALGORITHM", "rs256");
+        let jwt = JwtSettings {
+            algorithm: match jwt_algorithm.to_lowercase().as_str() {
+                "rs256" => JwtAlgorithmKind::Rs256,
+                "eddsa" => JwtAlgorithmKind::EdDsa,
+                other => {
+                    source.errors.push(format!(
+                        "JWT_ALGORITHM `{}` must be one of: rs256, eddsa",
+                        other
+                    ));
+                    JwtAlgorithmKind::Rs256
+                }
+            },
+            issuer: source.optional("jwt", "issuer", "JWT_ISSUER", &app_url),
+            audience: source.optional("jwt", "audience", "JWT_AUDIENCE", &app_url),
+            signing_kid: source.required("jwt", "signing_kid", "JWT_SIGNING_KID"),
+            private_key_file: source.required("jwt", "private_key_file", "JWT_PRIVATE_KEY_FILE"),
+            public_keys_file: source.required("jwt", "public_keys_file", "JWT_PUBLIC_KEYS_FILE"),
+        };
+        let mail_transport = source.optional("mail", "transport", "MAIL_TRANSPORT", "smtp");
+        let transport = match mail_transport.to_lowercase().as_str() {
+            "smtp" => MailTransportKind::Smtp,
+            "file" => MailTransportKind::File,
+            "memory" => MailTransportKind::Memory,
+            other => {
+                source.errors.push(format!(
+                    "MAIL_TRANSPORT `{}` must be one of: smtp, file, memory",
+                    other
+                ));
+                MailTransportKind::Smtp
+            }
+        };
+        let smtp = match transport {
+            MailTransportKind::Smtp => SmtpSettings {
+                host: source.optional("smtp", "host", "SMTP_HOST", "smtp.gmail.com"),
+                username: source.required("smtp", "username", "SMTP_USERNAME"),
+                password: source.required("smtp", "password", "SMTP_PASSWORD"),
+            },
+            MailTransportKind::File | MailTransportKind::Memory => SmtpSettings {
+                host: String::new(),
+                username: String::new(),
+                password: String::new(),
+            },
+        };
+        let mail = MailSettings {
+            transport,
+            brand: source.optional("mail", "brand", "MAIL_BRAND", "Clicon.io"),
+            from: source.optional(
+                "mail",
+                "from",
+                "MAIL_FROM",
+                "Clicon.io <no-reply@clicon.io>",
+            ),
+            reply_to: source.optional(
+                "mail",
+                "reply_to",
+                "MAIL_REPLY_TO",
+                "Support <support@clicon.io>",
+            ),
+            smtp,
+            drop_dir: source.optional("mail", "drop_dir", "MAIL_DROP_DIR", "mail"),
+        };
+
+        let storage_backend = source.optional("storage", "backend", "STORAGE_BACKEND", "s3");
+        let backend = match storage_backend.to_lowercase().as_str() {
+            "s3" => StorageBackendKind::S3,
+            "local" => StorageBackendKind::Local,
+            other => {
+                source.errors.push(format!(
+                    "STORAGE_BACKEND `{}` must be one of: s3, local",
+                    other
+                ));
+                StorageBackendKind::S3
+            }
+        };
+        let s3 = match backend {
+            StorageBackendKind::S3 => S3Settings {
+                bucket_name: source.required("s3", "bucket_name", "AWS_BUCKET_NAME"),
+                region: source.required("s3", "region", "AWS_REGION"),
+                endpoint: source
+                    .lookup("s3", "endpoint", "S3_ENDPOINT")
+                    .filter(|url| !url.trim().is_empty())
+                    .map(|url| url.trim_end_matches('/').to_string()),
+                force_path_style: source
+                    .optional("s3", "force_path_style", "S3_FORCE_PATH_STYLE", "false")
+                    .eq_ignore_ascii_case("true"),
+                public_url: source
+                    .lookup("s3", "public_url", "S3_PUBLIC_URL")
+                    .filter(|url| !url.trim().is_empty())
+                    .map(|url| url.trim_end_matches('/').to_string()),
+            },
+            StorageBackendKind::Local => S3Settings {
+                bucket_name: String::new(),
+                region: String::new(),
+                endpoint: None,
+                force_path_style: false,
+                public_url: None,
+            },
+        };
+        let storage = StorageSettings {
+            backend,
+            s3,
+            local: LocalStorageSettings {
+                root: source.optional("storage", "local_root", "LOCAL_STORAGE_ROOT", "uploads"),
+            },
+        };
+
+        // no default, a deployment has to pick its provider on purpose
+        let payment_provider = source.required("payments", "provider", "PAYMENT_PROVIDER");
+        let provider = match payment_provider.to_lowercase().as_str() {
+            "stripe" => PaymentProviderKind::Stripe,
+            "mock" => {
+                // the mock provider marks orders paid without charging anyone
+                let allow_mock = source
+                    .optional("payments", "allow_mock", "PAYMENT_ALLOW_MOCK", "false")
+                    .eq_ignore_ascii_case("true");
+                if !allow_mock {
+                    source.errors.push(
+                        "PAYMENT_PROVIDER `mock` is for development and tests only, set PAYMENT_ALLOW_MOCK=true to use it"
+                            .to_string(),
+                    );
+                }
+                PaymentProviderKind::Mock
+            }
+            // already reported as missing
+            "" => PaymentProviderKind::Mock,
+            other => {
+                source.errors.push(format!(
+                    "PAYMENT_PROVIDER `{}` must be one of: mock, stripe",
+                    other
+                ));
+                PaymentProviderKind::Mock
+            }
+        };
+        // the webhook route is always mounted, whatever the provider
+        let webhook_secret =
+            source.required("payments", "webhook_secret", "PAYMENT_WEBHOOK_SECRET");
+        let currency = source.optional("payments", "currency", "PAYMENT_CURRENCY", "usd");
+        let payments = match provider {
+            PaymentProviderKind::Stripe => PaymentSettings {
+                provider,
+                currency,
+                webhook_secret,
+                stripe_secret_key: source.required(
+                    "payments",
+                    "stripe_secret_key",
+                    "STRIPE_SECRET_KEY",
+                ),
+                stripe_api_base: source.optional(
+                    "payments",
+                    "stripe_api_base",
+                    "STRIPE_API_BASE",
+                    "https://api.stripe.com",
+                ),
+            },
+            PaymentProviderKind::Mock => PaymentSettings {
+                provider,
+                currency,
+                webhook_secret,
+                stripe_secret_key: String::new(),
+                stripe_api_base: String::new(),
+            },
+        };
+
+        let rate_limit_store = source.optional("rate_limit", "store", "RATE_LIMIT_STORE", "memory");
+        let trusted_proxies = source.optional(
+            "rate_limit",
+            "trusted_proxies",
+            "RATE_LIMIT_TRUSTED_PROXIES",
+            "0",
+        );
+        let rate_limit = RateLimitSettings {
+            store: match rate_limit_store.to_lowercase().as_str() {
+                "memory" => RateLimitStoreKind::Memory,
+                "mongo" => RateLimitStoreKind::Mongo,
+                other => {
+                    source.errors.push(format!(
+                        "RATE_LIMIT_STORE `{}` must be one of: memory, mongo",
+                        other
+                    ));
+                    RateLimitStoreKind::Memory
+                }
+            },
+            trusted_proxies: match trusted_proxies.trim().parse::<usize>() {
+                Ok(hops) => hops,
+                Err(_) => {
+                    source.errors.push(format!(
+                        "RATE_LIMIT_TRUSTED_PROXIES `{}` must be a number of proxies",
+                        trusted_proxies
+                    ));
+                    0
+                }
+            },
+        };
+
+        let search_backend = source.optional("search", "backend", "SEARCH_BACKEND", "embedded");
+        let search = SearchSettings {
+            backend: match search_backend.to_lowercase().as_str() {
+                "atlas" => SearchBackendKind::Atlas,
+                "embedded" => SearchBackendKind::Embedded,
+                other => {
+                    source.errors.push(format!(
+                        "SEARCH_BACKEND `{}` must be one of: atlas, embedded",
+                        other
+                    ));
+                    SearchBackendKind::Embedded
+                }
+            },
+            atlas_index: source.optional("search", "atlas_index", "ATLAS_SEARCH_INDEX", "default"),
+            index_dir: source
+                .lookup("search", "index_dir", "SEARCH_INDEX_DIR")
+                .filter(|dir| !dir.trim().is_empty()),
+        };
+
+        let mut errors = source.errors;
+
+        let bind_address = match bind_address.parse::<SocketAddr>() {
+            Ok(addr) => Some(addr),
+            Err(_) => {
+                errors.push(format!(
+                    "BIND_ADDRESS `{}` is not a valid socket address",
+                    bind_address
+                ));
+                None
+            }
+        };
+
+        if !database.uri.is_empty()
+            && !database.uri.starts_with("mongodb://")
+            && !database.uri.starts_with("mongodb+srv://")
+        {
+            errors.push("MONGODB_URI must start with mongodb:// or mongodb+srv://".to_string());
+        }
+
+        if let Some(endpoint) = &storage.s3.endpoint {
+            if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+                errors.push(format!("S3_ENDPOINT `{}` must be an http(s) url", endpoint));
+            }
+        }
+
+        for (name, address) in [("MAIL_FROM", &mail.from), ("MAIL_REPLY_TO", &mail.reply_to)] {
+            if address.parse::<lettre::message::Mailbox>().is_err() {
+                errors.push(format!("{} `{}` is not a valid mailbox", name, address));
+            }
+        }
+
+        if !app_url.starts_with("http://") && !app_url.starts_with("https://") {
+            errors.push(format!("APP_URL `{}` must be an http(s) url", app_url));
+        }
+
+        match bind_address {
+            Some(bind_address) if errors.is_empty() => Ok(Settings {
+                server: ServerSettings {
+                    bind_address,
+                    app_url: app_url.trim_end_matches('/').to_string(),
+                },
+                database,
+                jwt,
+                mail,
+                storage,
+                payments,
+                rate_limit,
+                search,
+            }),
+            _ => Err(SettingsError(errors)),
+        }
+    }
+}
@@ -1,23 +1,30 @@
-use mongodb::bson::doc;
-use mongodb::{Client, Database};
-use std::env;
+use mongodb::bson::{doc, Bson, Document};
+use mongodb::error::{ErrorKind, WriteFailure};
+use mongodb::options::IndexOptions;
+use mongodb::{Client, Collection, Database, IndexModel};
+use std::time::Duration;
 
-pub async fn connect_to_mongodb() -> Database {
-    let uri = match env::var("MONGODB_URI") {
-        Ok(env) => env,
-        Err(e) => e.to_string(),
-    };
+use crate::config::settings::DatabaseSettings;
+use crate::models::{
+    api_key_model::ApiKey,
+    auth_model::{LoginChallenge, LoginFailures, PasswordReset},
+    brand_model::Brand,
+    category_model::Category,
+    inventory_model::{StockMovement, StockReservation},
+    order_model::Order,
+    outbox_model::OutboxEmail,
+    products_model::Products,
+    review_model::{Review, ReviewVote},
+    session_model::Session,
+    user_model::TempUser,
+};
 
-    let database_name = match env::var("DATABASE_NAME") {
-        Ok(name) => name,
-        Err(e) => e.to_string(),
-    };
-
-    let client = Client::with_uri_str(uri.to_string())
+pub async fn connect_to_mongodb(settings: &DatabaseSettings) -> Database {
+    let client = Client::with_uri_str(&settings.uri)
         .await
         .expect("failed to connect_to_mongodb");
 
-    let database = client.database(&database_name);
+    let database = client.database(&settings.name);
 
     database
         .run_command(doc! {"ping": 1})
@@ -28,3 +35,365 @@ pub async fn connect_to_mongodb() -> Database {
 
     database
 }
+
+pub fn is_duplicate_key_error(err: &mongodb::error::Error) -> bool {
+    matches!(
+        err.kind.as_ref(),
+        ErrorKind::Write(WriteFailure::WriteError(e)) if e.code == 11000
+    )
+}
+
+/// Registrations left by older versions hold a plaintext code and a string
+/// `expires_at` the TTL index ignores, and the same email may be pending more
+/// than once, which would stop the unique index from being built. Nothing in
+/// them can be verified any more, so they are dropped.
+async fn purge_legacy_temp_users(database: &Database) {
+    let temp_users: Collection<Document> = database.collection("temp-user");
+
+    let legacy = temp_users
+        .delete_many(doc! {"$or": [
+            {"otp_hash": {"$exists": false}},
+            {"expires_at": {"$not": {"$type": "date"}}},
+        ]})
+        .await
+        .expect("failed to purge legacy temp-user documents");
+
+    // keep the most recent registration per email
+    let mut duplicates = temp_users
+        .aggregate(vec![
+            doc! {"$sort": {"last_sent_at": -1}},
+            doc! {"$group": {"_id": "$email", "ids": {"$push": "$_id"}, "count": {"$sum": 1}}},
+            doc! {"$match": {"count": {"$gt": 1}}},
+        ])
+        .await
+        .expect("failed to look for duplicate temp-user documents");
+
+    let mut removed = legacy.deleted_count;
+    while duplicates
+        .advance()
+        .await
+        .expect("failed to look for duplicate temp-user documents")
+    {
+        let group = duplicates
+            .deserialize_current()
+            .expect("failed to read duplicate temp-user documents");
+        let stale = group
+            .get_array("ids")
+            .map(|ids| ids.iter().skip(1).cloned().collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        removed += temp_users
+            .delete_many(doc! {"_id": {"$in": stale}})
+            .await
+            .expect("failed to purge duplicate temp-user documents")
+            .deleted_count;
+    }
+
+    if removed > 0 {
+        tracing::info!("removed {} legacy pending registrations", removed);
+    }
+}
+
+pub async fn create_indexes(database: &Database) {
+    let sessions: Collection<Session> = database.collection("sessions");
+
+    sessions
+        .create_indexes([
+            IndexModel::builder()
+                .keys(doc! {"expires_at": 1})
+                .options(
+                    IndexOptions::builder()
+                        .expire_after(Duration::from_secs(0))
+                        .build(),
+                )
+                .build(),
+            IndexModel::builder()
+                .keys(doc! {"refresh_token_hash": 1})
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            IndexModel::builder()
+                .keys(doc! {"rotated_token_hashes": 1})
+                .build(),
+            IndexModel::builder().keys(doc! {"user_id": 1}).build(),
+        ])
+        .await
+        .expect("failed to create sessions indexes");
+
+    let password_resets: Collection<PasswordReset> = database.collection("password_resets");
+
+    password_resets
+        .create_indexes([
+            IndexModel::builder()
+                .keys(doc! {"expires_at": 1})
+                .options(
+                    IndexOptions::builder()
+                        .expire_after(Duration::from_secs(0))
+                        .build(),
+                )
+                .build(),
+            IndexModel::builder()
+                .keys(doc! {"token_hash": 1})
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            IndexModel::builder().keys(doc! {"user_id": 1}).build(),
+        ])
+        .await
+        .expect("failed to create password_resets indexes");
+
+    let orders: Collection<Order> = database.collection("orders");
+
+    orders
+        .create_indexes([
+            IndexModel::builder()
+                .keys(doc! {"user_id": 1, "created_at": -1})
+                .build(),
+            IndexModel::builder()
+                .keys(doc! {"status": 1, "created_at": -1})
+                .build(),
+            IndexModel::builder()
+                .keys(doc! {"created_at": -1, "_id": -1})
+                .build(),
+            IndexModel::builder()
+                .keys(doc! {"payment.intent_id": 1})
+                .options(IndexOptions::builder().sparse(true).build())
+                .build(),
+        ])
+        .await
+        .expect("failed to create orders indexes");
+
+    let reservations: Collection<StockReservation> = database.collection("stock_reservations");
+
+    reservations
+        .create_indexes([
+            IndexModel::builder()
+                .keys(doc! {"order_id": 1})
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            IndexModel::builder()
+                .keys(doc! {"status": 1, "expires_at": 1})
+                .build(),
+        ])
+        .await
+        .expect("failed to create stock_reservations indexes");
+
+    let movements: Collection<StockMovement> = database.collection("stock_movements");
+
+    movements
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! {"product_id": 1, "created_at": -1})
+                .build(),
+        )
+        .await
+        .expect("failed to create stock_movements indexes");
+
+    let products: Collection<Products> = database.collection("products");
+
+    // products from before reviews have no rating to sort and page by
+    for (field, zero) in [
+        ("rating_average", Bson::Double(0.0)),
+        ("rating_count", Bson::Int64(0)),
+    ] {
+        products
+            .update_many(
+                doc! {field: {"$exists": false}},
+                doc! {"$set": {field: zero}},
+            )
+            .await
+            .expect("failed to backfill product ratings");
+    }
+
+    // skus are unique across the catalogue, products without variants are left out
+    products
+        .create_indexes([
+            IndexModel::builder()
+                .keys(doc! {"variants.sku": 1})
+                .options(
+                    IndexOptions::builder()
+                        .unique(true)
+                        .partial_filter_expression(doc! {"variants.sku": {"$exists": true}})
+                        .build(),
+                )
+                .build(),
+            IndexModel::builder()
+                .keys(doc! {"category_path": 1})
+                .build(),
+            IndexModel::builder().keys(doc! {"category_id": 1}).build(),
+            IndexModel::builder().keys(doc! {"brand_id": 1}).build(),
+        ])
+        .await
+        .expect("failed to create products indexes");
+
+    let categories: Collection<Category> = database.collection("categories");
+
+    categories
+        .create_indexes([
+            IndexModel::builder()
+                .keys(doc! {"slug": 1})
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            IndexModel::builder().keys(doc! {"parent_id": 1}).build(),
+            IndexModel::builder().keys(doc! {"path": 1}).build(),
+        ])
+        .await
+        .expect("failed to create categories indexes");
+
+    let brands: Collection<Brand> = database.collection("brands");
+
+    brands
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! {"slug": 1})
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+        )
+        .await
+        .expect("failed to create brands indexes");
+
+    let reviews: Collection<Review> = database.collection("reviews");
+
+    // one review per customer and product
+    reviews
+        .create_indexes([
+            IndexModel::builder()
+                .keys(doc! {"product_id": 1, "user_id": 1})
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            IndexModel::builder()
+                .keys(doc! {"product_id": 1, "status": 1, "_id": -1})
+                .build(),
+            IndexModel::builder()
+                .keys(doc! {"product_id": 1, "status": 1, "helpful_count": -1, "_id": -1})
+                .build(),
+            IndexModel::builder()
+                .keys(doc! {"status": 1, "_id": -1})
+                .build(),
+        ])
+        .await
+        .expect("failed to create reviews indexes");
+
+    let review_votes: Collection<ReviewVote> = database.collection("review_votes");
+
+    review_votes
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! {"review_id": 1, "user_id": 1})
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+        )
+        .await
+        .expect("failed to create review_votes indexes");
+
+    let mail_outbox: Collection<OutboxEmail> = database.collection("mail_outbox");
+
+    mail_outbox
+        .create_indexes([
+            IndexModel::builder()
+                .keys(doc! {"status": 1, "next_attempt_at": 1})
+                .build(),
+            IndexModel::builder()
+                .keys(doc! {"status": 1, "locked_until": 1})
+                .build(),
+            // delivered emails are kept for a week, dead ones until handled
+            IndexModel::builder()
+                .keys(doc! {"sent_at": 1})
+                .options(
+                    IndexOptions::builder()
+                        .expire_after(Duration::from_secs(7 * 24 * 60 * 60))
+                        .build(),
+                )
+                .build(),
+        ])
+        .await
+        .expect("failed to create mail_outbox indexes");
+
+    let api_keys: Collection<ApiKey> = database.collection("api_keys");
+
+    api_keys
+        .create_indexes([
+            IndexModel::builder()
+                .keys(doc! {"key_hash": 1})
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            IndexModel::builder()
+                .keys(doc! {"user_id": 1, "created_at": -1})
+                .build(),
+        ])
+        .await
+        .expect("failed to create api_keys indexes");
+
+    let login_challenges: Collection<LoginChallenge> = database.collection("login_challenges");
+
+    login_challenges
+        .create_indexes([
+            IndexModel::builder()
+                .keys(doc! {"token_hash": 1})
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            IndexModel::builder()
+                .keys(doc! {"expires_at": 1})
+                .options(
+                    IndexOptions::builder()
+                        .expire_after(Duration::from_secs(0))
+                        .build(),
+                )
+                .build(),
+        ])
+        .await
+        .expect("failed to create login_challenges indexes");
+
+    let rate_limits: Collection<Document> = database.collection("rate_limits");
+
+    rate_limits
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! {"resets_at": 1})
+                .options(
+                    IndexOptions::builder()
+                        .expire_after(Duration::from_secs(0))
+                        .build(),
+                )
+                .build(),
+        )
+        .await
+        .expect("failed to create rate_limits indexes");
+
+    let login_failures: Collection<LoginFailures> = database.collection("login_failures");
+
+    login_failures
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! {"last_failure_at": 1})
+                .options(
+                    IndexOptions::builder()
+                        .expire_after(Duration::from_secs(24 * 60 * 60))
+                        .build(),
+                )
+                .build(),
+        )
+        .await
+        .expect("failed to create login_failures indexes");
+
+    purge_legacy_temp_users(database).await;
+
+    // pending registrations disappear on their own once the code has expired
+    let temp_users: Collection<TempUser> = database.collection("temp-user");
+
+    temp_users
+        .create_indexes([
+            IndexModel::builder()
+                .keys(doc! {"email": 1})
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            IndexModel::builder()
+                .keys(doc! {"expires_at": 1})
+                .options(
+                    IndexOptions::builder()
+                        .expire_after(Duration::from_secs(0))
+                        .build(),
+                )
+                .build(),
+        ])
+        .await
+        .expect("failed to create temp-user indexes");
+}
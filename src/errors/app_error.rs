@@ -0,0 +1,285 @@
+use std::error::Error;
+
+use aws_sdk_s3::error::{DisplayErrorContext, SdkError};
+use axum::{
+    http::{header::RETRY_AFTER, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use jsonwebtoken::errors::ErrorKind;
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Debug, Serialize)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &'static str, message: impl Into<String>) -> Self {
+        FieldError {
+            field,
+            message: message.into(),
+        }
+    }
+}
+
+/// Body of every error response. `code` is stable and meant for clients to
+/// branch on, `message` is for humans and may change.
+#[derive(Debug, Serialize)]
+pub struct ErrorBody {
+    pub status: u16,
+    pub code: &'static str,
+    pub message: String,
+    pub details: Option<Value>,
+}
+
+#[derive(Debug)]
+pub enum AppError {
+    BadRequest(String),
+    Validation(Vec<FieldError>),
+    InvalidId,
+    InvalidOtp,
+    RegistrationExpired,
+    InvalidCredentials,
+    InvalidTwoFactorCode,
+    Unauthorized(String),
+    InvalidToken,
+    TokenExpired,
+    SessionExpired,
+    RefreshTokenReused,
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    /// Seconds until the client may try again, sent as `Retry-After`.
+    TooManyRequests(u64),
+    Database(mongodb::error::Error),
+    Hashing(bcrypt::BcryptError),
+    InvalidSignature,
+    Storage(String),
+    Email(String),
+    PaymentProvider(String),
+    Search(String),
+    Internal(String),
+}
+
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_)
+            | AppError::InvalidId
+            | AppError::InvalidOtp
+            | AppError::RegistrationExpired
+            | AppError::InvalidSignature => StatusCode::BAD_REQUEST,
+            AppError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::InvalidCredentials
+            | AppError::InvalidTwoFactorCode
+            | AppError::Unauthorized(_)
+            | AppError::InvalidToken
+            | AppError::TokenExpired
+            | AppError::SessionExpired
+            | AppError::RefreshTokenReused => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            AppError::Storage(_) | AppError::Email(_) | AppError::PaymentProvider(_) => {
+                StatusCode::BAD_GATEWAY
+            }
+            AppError::Database(_)
+            | AppError::Hashing(_)
+            | AppError::Search(_)
+            | AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::BadRequest(_) => "BAD_REQUEST",
+            AppError::Validation(_) => "VALIDATION_FAILED",
+            AppError::InvalidId => "INVALID_ID",
+            AppError::InvalidOtp => "INVALID_OTP",
+            AppError::InvalidTwoFactorCode => "INVALID_TWO_FACTOR_CODE",
+            AppError::RegistrationExpired => "REGISTRATION_EXPIRED",
+            AppError::InvalidCredentials => "INVALID_CREDENTIALS",
+            AppError::Unauthorized(_) => "UNAUTHORIZED",
+            AppError::InvalidToken => "INVALID_TOKEN",
+            AppError::TokenExpired => "TOKEN_EXPIRED",
+            AppError::SessionExpired => "SESSION_EXPIRED",
+            AppError::RefreshTokenReused => "REFRESH_TOKEN_REUSED",
+            AppError::Forbidden(_) => "FORBIDDEN",
+            AppError::NotFound(_) => "NOT_FOUND",
+            AppError::Conflict(_) => "CONFLICT",
+            AppError::TooManyRequests(_) => "TOO_MANY_REQUESTS",
+            AppError::Database(_) => "DATABASE_ERROR",
+            AppError::Hashing(_) => "HASHING_ERROR",
+            AppError::InvalidSignature => "INVALID_SIGNATURE",
+            AppError::Storage(_) => "STORAGE_ERROR",
+            AppError::Email(_) => "EMAIL_ERROR",
+            AppError::PaymentProvider(_) => "PAYMENT_ERROR",
+            AppError::Search(_) => "SEARCH_ERROR",
+            AppError::Internal(_) => "INTERNAL_ERROR",
+        }
+    }
+
+    /// Message shown to the client. Internal failures get a generic text so
+    /// driver or provider errors never leak into responses.
+    pub fn message(&self) -> String {
+        match self {
+            AppError::BadRequest(msg)
+            | AppError::Unauthorized(msg)
+            | AppError::Forbidden(msg)
+            | AppError::NotFound(msg)
+            | AppError::Conflict(msg) => msg.clone(),
+            AppError::TooManyRequests(seconds) => {
+                format!("too many attempts please try again in {} seconds", seconds)
+            }
+            AppError::Validation(_) => "request validation failed".to_string(),
+            AppError::InvalidId => "Invalid Id".to_string(),
+            AppError::InvalidOtp => "Invalid OTP".to_string(),
+            AppError::RegistrationExpired => {
+                "your registration session has expired please register again".to_string()
+            }
+            AppError::InvalidCredentials => "invalid email or password".to_string(),
+            AppError::InvalidTwoFactorCode => "invalid two-factor code".to_string(),
+            AppError::InvalidToken => "your token is invalid please login again".to_string(),
+            AppError::TokenExpired => "your token has expired".to_string(),
+            AppError::SessionExpired => "your session is expired please login again".to_string(),
+            AppError::RefreshTokenReused => {
+                "refresh token reuse detected please login again".to_string()
+            }
+            AppError::InvalidSignature => "signature verification failed".to_string(),
+            AppError::Storage(_) => "failed to store file".to_string(),
+            AppError::Email(_) => "failed to send email".to_string(),
+            AppError::PaymentProvider(_) => "payment provider request failed".to_string(),
+            AppError::Search(_) => "search failed".to_string(),
+            AppError::Database(_) | AppError::Hashing(_) | AppError::Internal(_) => {
+                "Internal Server Error".to_string()
+            }
+        }
+    }
+
+    pub fn details(&self) -> Option<Value> {
+        match self {
+            AppError::Validation(errors) => serde_json::to_value(errors).ok(),
+            _ => None,
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+
+        match &self {
+            AppError::Database(e) => tracing::error!("database error: {}", e),
+            AppError::Hashing(e) => tracing::error!("hashing error: {}", e),
+            AppError::Storage(e) => tracing::error!("storage error: {}", e),
+            AppError::Email(e) => tracing::error!("email error: {}", e),
+            AppError::PaymentProvider(e) => tracing::error!("payment provider error: {}", e),
+            AppError::Search(e) => tracing::error!("search error: {}", e),
+            AppError::Internal(e) => tracing::error!("internal error: {}", e),
+            _ => {}
+        }
+
+        let body = ErrorBody {
+            status: status.as_u16(),
+            code: self.code(),
+            message: self.message(),
+            details: self.details(),
+        };
+
+        let mut response = (status, Json(body)).into_response();
+        if let AppError::TooManyRequests(seconds) = self {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(seconds));
+        }
+        response
+    }
+}
+
+impl From<mongodb::error::Error> for AppError {
+    fn from(err: mongodb::error::Error) -> Self {
+        AppError::Database(err)
+    }
+}
+
+impl From<mongodb::bson::ser::Error> for AppError {
+    fn from(err: mongodb::bson::ser::Error) -> Self {
+        AppError::Internal(err.to_string())
+    }
+}
+
+impl From<bcrypt::BcryptError> for AppError {
+    fn from(err: bcrypt::BcryptError) -> Self {
+        AppError::Hashing(err)
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for AppError {
+    fn from(err: jsonwebtoken::errors::Error) -> Self {
+        match err.kind() {
+            ErrorKind::ExpiredSignature => AppError::TokenExpired,
+            ErrorKind::InvalidToken
+            | ErrorKind::InvalidSignature
+            | ErrorKind::InvalidIssuer
+            | ErrorKind::InvalidAudience
+            | ErrorKind::InvalidSubject
+            | ErrorKind::ImmatureSignature
+            | ErrorKind::InvalidAlgorithm
+            | ErrorKind::MissingRequiredClaim(_)
+            | ErrorKind::Base64(_)
+            | ErrorKind::Json(_)
+            | ErrorKind::Utf8(_) => AppError::InvalidToken,
+            _ => AppError::Internal(err.to_string()),
+        }
+    }
+}
+
+impl<E, R> From<SdkError<E, R>> for AppError
+where
+    E: Error + 'static,
+    R: std::fmt::Debug,
+{
+    fn from(err: SdkError<E, R>) -> Self {
+        AppError::Storage(DisplayErrorContext(&err).to_string())
+    }
+}
+
+impl From<lettre::error::Error> for AppError {
+    fn from(err: lettre::error::Error) -> Self {
+        AppError::Email(err.to_string())
+    }
+}
+
+impl From<lettre::address::AddressError> for AppError {
+    fn from(err: lettre::address::AddressError) -> Self {
+        AppError::Email(err.to_string())
+    }
+}
+
+impl From<lettre::transport::smtp::Error> for AppError {
+    fn from(err: lettre::transport::smtp::Error) -> Self {
+        AppError::Email(err.to_string())
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(err: std::io::Error) -> Self {
+        AppError::Storage(err.to_string())
+    }
+}
+
+impl From<reqwest::Error> for AppError {
+    fn from(err: reqwest::Error) -> Self {
+        AppError::PaymentProvider(err.to_string())
+    }
+}
+
+impl From<tantivy::TantivyError> for AppError {
+    fn from(err: tantivy::TantivyError) -> Self {
+        AppError::Search(err.to_string())
+    }
+}
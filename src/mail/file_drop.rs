@@ -0,0 +1,46 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use mongodb::bson::Uuid;
+
+use crate::{config::settings::MailSettings, errors::app_error::AppError};
+
+use super::mailer::{build_message, EmailMessage, Mailer};
+
+/// Writes every email as an `.eml` file that any mail client can open.
+pub struct FileDropMailer {
+    settings: MailSettings,
+    dir: PathBuf,
+}
+
+impl FileDropMailer {
+    pub fn new(settings: &MailSettings) -> Self {
+        FileDropMailer {
+            settings: settings.clone(),
+            dir: PathBuf::from(&settings.drop_dir),
+        }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileDropMailer {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    async fn send(&self, message: &EmailMessage) -> Result<(), AppError> {
+        let email = build_message(&self.settings, message)?;
+
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|e| AppError::Email(e.to_string()))?;
+
+        let file_name = format!("{}-{}.eml", Utc::now().format("%Y%m%dT%H%M%S"), Uuid::new());
+        tokio::fs::write(self.dir.join(file_name), email.formatted())
+            .await
+            .map_err(|e| AppError::Email(e.to_string()))?;
+
+        Ok(())
+    }
+}
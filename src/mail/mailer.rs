@@ -0,0 +1,54 @@
+use async_trait::async_trait;
+use lettre::{
+    message::{header::ContentType, MultiPart},
+    Message,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{config::settings::MailSettings, errors::app_error::AppError};
+
+/// A rendered email, independent of how it ends up being delivered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailMessage {
+    pub to_name: String,
+    pub to_email: String,
+    pub subject: String,
+    pub html: String,
+    /// Plain text alternative of `html`, empty for emails queued before
+    /// templates had one.
+    #[serde(default)]
+    pub text: String,
+}
+
+/// Delivers emails. Implementations are held in `AppState`, handlers never
+/// call it directly but go through the outbox.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn send(&self, message: &EmailMessage) -> Result<(), AppError>;
+
+    /// Emails kept by transports that capture instead of delivering.
+    fn captured(&self) -> Option<Vec<EmailMessage>> {
+        None
+    }
+}
+
+pub fn build_message(settings: &MailSettings, message: &EmailMessage) -> Result<Message, AppError> {
+    let builder = Message::builder()
+        .from(settings.from.parse()?)
+        .reply_to(settings.reply_to.parse()?)
+        .to(format!("{} <{}>", message.to_name, message.to_email).parse()?)
+        .subject(&message.subject);
+
+    if message.text.is_empty() {
+        return Ok(builder
+            .header(ContentType::TEXT_HTML)
+            .body(message.html.clone())?);
+    }
+
+    Ok(builder.multipart(MultiPart::alternative_plain_html(
+        message.text.clone(),
+        message.html.clone(),
+    ))?)
+}
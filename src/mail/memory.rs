@@ -0,0 +1,38 @@
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+
+use crate::errors::app_error::AppError;
+
+use super::mailer::{EmailMessage, Mailer};
+
+/// Keeps the emails it is given, for local runs where nothing should leave
+/// the machine. The most recent `CAPACITY` emails are kept.
+#[derive(Default)]
+pub struct MemoryMailer {
+    messages: Mutex<Vec<EmailMessage>>,
+}
+
+const CAPACITY: usize = 100;
+
+#[async_trait]
+impl Mailer for MemoryMailer {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn send(&self, message: &EmailMessage) -> Result<(), AppError> {
+        let mut messages = self.messages.lock().unwrap();
+
+        if messages.len() == CAPACITY {
+            messages.remove(0);
+        }
+        messages.push(message.clone());
+
+        Ok(())
+    }
+
+    fn captured(&self) -> Option<Vec<EmailMessage>> {
+        Some(self.messages.lock().unwrap().clone())
+    }
+}
@@ -0,0 +1,29 @@
+pub mod file_drop;
+pub mod mailer;
+pub mod memory;
+pub mod outbox;
+pub mod smtp;
+pub mod templates;
+
+use std::sync::Arc;
+
+use crate::config::settings::{MailSettings, MailTransportKind};
+
+use self::{file_drop::FileDropMailer, mailer::Mailer, memory::MemoryMailer, smtp::SmtpMailer};
+
+pub fn build_mailer(settings: &MailSettings) -> Arc<dyn Mailer> {
+    match settings.transport {
+        MailTransportKind::Smtp => Arc::new(SmtpMailer::new(settings)),
+        MailTransportKind::File => {
+            tracing::warn!(
+                "emails are written to `{}` instead of sent",
+                settings.drop_dir
+            );
+            Arc::new(FileDropMailer::new(settings))
+        }
+        MailTransportKind::Memory => {
+            tracing::warn!("emails are kept in memory instead of sent");
+            Arc::new(MemoryMailer::default())
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use std::{sync::Arc, time::Duration};
+
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime},
+    options::ReturnDocument,
+    Collection, Database,
+};
+
+use crate::{
+    errors::app_error::AppError,
+    models::outbox_model::{OutboxEmail, OutboxStatus},
+};
+
+use super::mailer::{EmailMessage, Mailer};
+
+pub const MAX_ATTEMPTS: u32 = 8;
+const BASE_BACKOFF_SECONDS: i64 = 30;
+const MAX_BACKOFF_SECONDS: i64 = 60 * 60;
+/// How long a worker may take to deliver a claimed email before another
+/// worker is allowed to pick it up again.
+const CLAIM_SECONDS: i64 = 5 * 60;
+const IDLE_POLL_SECONDS: u64 = 5;
+
+pub fn outbox(db: &Database) -> Collection<OutboxEmail> {
+    db.collection("mail_outbox")
+}
+
+fn seconds_from_now(seconds: i64) -> DateTime {
+    DateTime::from_millis(DateTime::now().timestamp_millis() + seconds * 1000)
+}
+
+/// Delay before the next attempt after `attempts` failed ones: 30s, 1m, 2m,
+/// 4m, ... capped at an hour.
+fn backoff_seconds(attempts: u32) -> i64 {
+    BASE_BACKOFF_SECONDS
+        .saturating_mul(1 << attempts.saturating_sub(1).min(16))
+        .min(MAX_BACKOFF_SECONDS)
+}
+
+/// Queues an email for delivery. The request that wants the email sent only
+/// pays for a database write, the worker does the talking to the mail server.
+pub async fn enqueue(db: &Database, message: EmailMessage) -> Result<(), AppError> {
+    let now = DateTime::now();
+
+    outbox(db)
+        .insert_one(OutboxEmail {
+            id: ObjectId::new(),
+            message,
+            status: OutboxStatus::Pending,
+            attempts: 0,
+            next_attempt_at: now,
+            locked_until: None,
+            last_error: None,
+            created_at: now,
+            sent_at: None,
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Claims the next due email, including ones whose worker died mid-send.
+async fn claim_next(db: &Database) -> Result<Option<OutboxEmail>, AppError> {
+    let now = DateTime::now();
+
+    Ok(outbox(db)
+        .find_one_and_update(
+            doc! {"$or": [
+                {"status": OutboxStatus::Pending.as_str(), "next_attempt_at": {"$lte": now}},
+                {"status": OutboxStatus::Sending.as_str(), "locked_until": {"$lte": now}},
+            ]},
+            doc! {"$set": {
+                "status": OutboxStatus::Sending.as_str(),
+                "locked_until": seconds_from_now(CLAIM_SECONDS),
+            }},
+        )
+        .sort(doc! {"next_attempt_at": 1})
+        .return_document(ReturnDocument::After)
+        .await?)
+}
+
+/// Delivers one due email. Returns `false` when there was nothing to do.
+pub async fn deliver_next(db: &Database, mailer: &dyn Mailer) -> Result<bool, AppError> {
+    let email = match claim_next(db).await? {
+        Some(email) => email,
+        None => return Ok(false),
+    };
+
+    let attempts = email.attempts + 1;
+
+    let update = match mailer.send(&email.message).await {
+        Ok(()) => {
+            tracing::debug!("email {} delivered via {}", email.id, mailer.name());
+            // bodies carry reset links and codes, nothing needs them once sent
+            doc! {"$set": {
+                "status": OutboxStatus::Sent.as_str(),
+                "attempts": attempts,
+                "sent_at": DateTime::now(),
+                "locked_until": null,
+                "message.html": "",
+                "message.text": "",
+            }}
+        }
+        Err(e) => {
+            let error = format!("{:?}", e);
+            let status = if attempts >= MAX_ATTEMPTS {
+                tracing::error!(
+                    "giving up on email {} after {} attempts: {}",
+                    email.id,
+                    attempts,
+                    error
+                );
+                OutboxStatus::Dead
+            } else {
+                tracing::warn!("email {} attempt {} failed: {}", email.id, attempts, error);
+                OutboxStatus::Pending
+            };
+
+            doc! {"$set": {
+                "status": status.as_str(),
+                "attempts": attempts,
+                "next_attempt_at": seconds_from_now(backoff_seconds(attempts)),
+                "last_error": error,
+                "locked_until": null,
+            }}
+        }
+    };
+
+    outbox(db)
+        .update_one(doc! {"_id": email.id}, update)
+        .await?;
+
+    Ok(true)
+}
+
+pub fn spawn_outbox_worker(db: Database, mailer: Arc<dyn Mailer>) {
+    tokio::spawn(async move {
+        loop {
+            match deliver_next(&db, mailer.as_ref()).await {
+                // keep draining while there is work
+                Ok(true) => continue,
+                Ok(false) => {}
+                Err(e) => tracing::error!("mail outbox worker failed: {:?}", e),
+            }
+            tokio::time::sleep(Duration::from_secs(IDLE_POLL_SECONDS)).await;
+        }
+    });
+}
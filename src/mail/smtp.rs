@@ -0,0 +1,53 @@
+use async_trait::async_trait;
+use lettre::{
+    transport::smtp::authentication::Credentials, AsyncSmtpTransport, AsyncTransport,
+    Tokio1Executor,
+};
+
+use crate::{config::settings::MailSettings, errors::app_error::AppError};
+
+use super::mailer::{build_message, EmailMessage, Mailer};
+
+pub struct SmtpMailer {
+    settings: MailSettings,
+    /// Built once, lettre pools the connections to the relay.
+    transport: Result<AsyncSmtpTransport<Tokio1Executor>, String>,
+}
+
+impl SmtpMailer {
+    pub fn new(settings: &MailSettings) -> Self {
+        let credentials = Credentials::new(
+            settings.smtp.username.to_owned(),
+            settings.smtp.password.to_owned(),
+        );
+
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::relay(&settings.smtp.host)
+            .map(|builder| builder.credentials(credentials).build())
+            .map_err(|e| e.to_string());
+
+        SmtpMailer {
+            settings: settings.clone(),
+            transport,
+        }
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    fn name(&self) -> &'static str {
+        "smtp"
+    }
+
+    async fn send(&self, message: &EmailMessage) -> Result<(), AppError> {
+        let transport = self
+            .transport
+            .as_ref()
+            .map_err(|e| AppError::Email(e.clone()))?;
+
+        transport
+            .send(build_message(&self.settings, message)?)
+            .await?;
+
+        Ok(())
+    }
+}
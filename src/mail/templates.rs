@@ -0,0 +1,467 @@
+use axum::http::{header::ACCEPT_LANGUAGE, HeaderMap};
+use maud::{html, Markup, DOCTYPE};
+use mongodb::Database;
+
+use crate::errors::app_error::AppError;
+
+use super::{mailer::EmailMessage, outbox::enqueue};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmailTemplate {
+    Otp,
+    Welcome,
+    PasswordReset,
+    OrderConfirmation,
+    ShippingNotice,
+}
+
+impl EmailTemplate {
+    pub const ALL: [EmailTemplate; 5] = [
+        EmailTemplate::Otp,
+        EmailTemplate::Welcome,
+        EmailTemplate::PasswordReset,
+        EmailTemplate::OrderConfirmation,
+        EmailTemplate::ShippingNotice,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EmailTemplate::Otp => "otp",
+            EmailTemplate::Welcome => "welcome",
+            EmailTemplate::PasswordReset => "password_reset",
+            EmailTemplate::OrderConfirmation => "order_confirmation",
+            EmailTemplate::ShippingNotice => "shipping_notice",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|template| template.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    En,
+    Es,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 2] = [Locale::En, Locale::Es];
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Es => "es",
+        }
+    }
+
+    /// Picks a supported locale from a language tag such as `es-ES`, falling
+    /// back to English.
+    pub fn parse(tag: &str) -> Self {
+        let language = tag
+            .trim()
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+
+        Self::ALL
+            .into_iter()
+            .find(|locale| locale.code() == language)
+            .unwrap_or(Locale::En)
+    }
+
+    /// The first language of the `Accept-Language` header, quality values
+    /// are not taken into account.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        headers
+            .get(ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split([',', ';']).next())
+            .map(Locale::parse)
+            .unwrap_or(Locale::En)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OrderLine {
+    pub title: String,
+    pub quantity: u32,
+    pub line_total: f32,
+}
+
+/// What each template needs to be rendered.
+#[derive(Debug, Clone)]
+pub enum TemplateData {
+    Otp {
+        otp: String,
+        valid_minutes: i64,
+    },
+    Welcome,
+    PasswordReset {
+        reset_link: String,
+        valid_minutes: i64,
+    },
+    OrderConfirmation {
+        order_id: String,
+        lines: Vec<OrderLine>,
+        total: f32,
+    },
+    ShippingNotice {
+        order_id: String,
+    },
+}
+
+impl TemplateData {
+    /// Made up data for previewing a template.
+    pub fn sample(template: EmailTemplate) -> Self {
+        match template {
+            EmailTemplate::Otp => TemplateData::Otp {
+                otp: "123456".to_string(),
+                valid_minutes: 5,
+            },
+            EmailTemplate::Welcome => TemplateData::Welcome,
+            EmailTemplate::PasswordReset => TemplateData::PasswordReset {
+                reset_link: "https://example.com/reset-password?token=sample".to_string(),
+                valid_minutes: 30,
+            },
+            EmailTemplate::OrderConfirmation => TemplateData::OrderConfirmation {
+                order_id: "65f000000000000000000000".to_string(),
+                lines: vec![
+                    OrderLine {
+                        title: "Wireless Headphones".to_string(),
+                        quantity: 1,
+                        line_total: 59.99,
+                    },
+                    OrderLine {
+                        title: "USB-C Cable".to_string(),
+                        quantity: 2,
+                        line_total: 19.98,
+                    },
+                ],
+                total: 79.97,
+            },
+            EmailTemplate::ShippingNotice => TemplateData::ShippingNotice {
+                order_id: "65f000000000000000000000".to_string(),
+            },
+        }
+    }
+}
+
+/// The localized pieces of an email. Both the HTML and the plain text body
+/// are rendered from it, so the two never drift apart.
+struct Content {
+    subject: String,
+    heading: String,
+    greeting: String,
+    paragraphs: Vec<String>,
+    code: Option<String>,
+    action: Option<(String, String)>,
+    lines: Vec<(String, String)>,
+    footer: String,
+}
+
+pub struct RenderedEmail {
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+fn content(brand: &str, locale: Locale, name: &str, data: &TemplateData) -> Content {
+    let greeting = match locale {
+        Locale::En => format!("Dear {},", name),
+        Locale::Es => format!("Hola {},", name),
+    };
+    let ignore = match locale {
+        Locale::En => "If you didn’t request this, you can ignore this email.",
+        Locale::Es => "Si no lo solicitaste, puedes ignorar este correo.",
+    }
+    .to_string();
+    let questions = match locale {
+        Locale::En => format!("Questions? Just reply to this email, the {} team", brand),
+        Locale::Es => format!("¿Dudas? Responde a este correo, el equipo de {}", brand),
+    };
+
+    match data {
+        TemplateData::Otp { otp, valid_minutes } => match locale {
+            Locale::En => Content {
+                subject: format!("Your OTP Code - {}", brand),
+                heading: "OTP Verification".to_string(),
+                greeting,
+                paragraphs: vec!["Use the OTP below to verify your email:".to_string()],
+                code: Some(otp.clone()),
+                action: None,
+                lines: vec![],
+                footer: format!(
+                    "This OTP is valid for {} minutes. Do not share it with anyone. {}",
+                    valid_minutes, ignore
+                ),
+            },
+            Locale::Es => Content {
+                subject: format!("Tu código OTP - {}", brand),
+                heading: "Verificación OTP".to_string(),
+                greeting,
+                paragraphs: vec!["Usa este código para verificar tu correo:".to_string()],
+                code: Some(otp.clone()),
+                action: None,
+                lines: vec![],
+                footer: format!(
+                    "El código es válido durante {} minutos. No lo compartas con nadie. {}",
+                    valid_minutes, ignore
+                ),
+            },
+        },
+        TemplateData::Welcome => match locale {
+            Locale::En => Content {
+                subject: format!("Welcome to {}", brand),
+                heading: format!("Welcome to {}", brand),
+                greeting,
+                paragraphs: vec![
+                    "Your email is verified and your account is ready.".to_string(),
+                    "Happy shopping!".to_string(),
+                ],
+                code: None,
+                action: None,
+                lines: vec![],
+                footer: questions,
+            },
+            Locale::Es => Content {
+                subject: format!("Bienvenido a {}", brand),
+                heading: format!("Bienvenido a {}", brand),
+                greeting,
+                paragraphs: vec![
+                    "Tu correo está verificado y tu cuenta ya está lista.".to_string(),
+                    "¡Felices compras!".to_string(),
+                ],
+                code: None,
+                action: None,
+                lines: vec![],
+                footer: questions,
+            },
+        },
+        TemplateData::PasswordReset {
+            reset_link,
+            valid_minutes,
+        } => match locale {
+            Locale::En => Content {
+                subject: format!("Reset your password - {}", brand),
+                heading: "Reset your password".to_string(),
+                greeting,
+                paragraphs: vec![
+                    "We received a request to reset the password of your account. Use the link below to choose a new one.".to_string(),
+                    format!(
+                        "This link is valid for {} minutes and can only be used once.",
+                        valid_minutes
+                    ),
+                ],
+                code: None,
+                action: Some(("Reset password".to_string(), reset_link.clone())),
+                lines: vec![],
+                footer: format!("{} Your password will stay the same.", ignore),
+            },
+            Locale::Es => Content {
+                subject: format!("Restablece tu contraseña - {}", brand),
+                heading: "Restablece tu contraseña".to_string(),
+                greeting,
+                paragraphs: vec![
+                    "Recibimos una solicitud para restablecer la contraseña de tu cuenta. Usa el enlace de abajo para elegir una nueva.".to_string(),
+                    format!(
+                        "El enlace es válido durante {} minutos y solo puede usarse una vez.",
+                        valid_minutes
+                    ),
+                ],
+                code: None,
+                action: Some(("Restablecer contraseña".to_string(), reset_link.clone())),
+                lines: vec![],
+                footer: format!("{} Tu contraseña no cambiará.", ignore),
+            },
+        },
+        TemplateData::OrderConfirmation {
+            order_id,
+            lines,
+            total,
+        } => {
+            let mut rows = lines
+                .iter()
+                .map(|line| {
+                    (
+                        format!("{} × {}", line.quantity, line.title),
+                        format!("{:.2}", line.line_total),
+                    )
+                })
+                .collect::<Vec<(String, String)>>();
+
+            rows.push(("Total".to_string(), format!("{:.2}", total)));
+
+            match locale {
+                Locale::En => Content {
+                        subject: format!("Order confirmation - {}", brand),
+                        heading: "Thanks for your order".to_string(),
+                        greeting,
+                        paragraphs: vec![format!(
+                            "We received your order {}. Here is what you bought:",
+                            order_id
+                        )],
+                        code: None,
+                        action: None,
+                        lines: rows,
+                        footer: questions,
+                },
+                Locale::Es => Content {
+                        subject: format!("Confirmación de pedido - {}", brand),
+                        heading: "Gracias por tu pedido".to_string(),
+                        greeting,
+                        paragraphs: vec![format!(
+                            "Recibimos tu pedido {}. Esto es lo que compraste:",
+                            order_id
+                        )],
+                        code: None,
+                        action: None,
+                        lines: rows,
+                        footer: questions,
+                },
+            }
+        }
+        TemplateData::ShippingNotice { order_id } => match locale {
+            Locale::En => Content {
+                subject: format!("Your order has shipped - {}", brand),
+                heading: "Your order is on its way".to_string(),
+                greeting,
+                paragraphs: vec![format!(
+                    "Good news, your order {} has shipped and will be with you soon.",
+                    order_id
+                )],
+                code: None,
+                action: None,
+                lines: vec![],
+                footer: questions,
+            },
+            Locale::Es => Content {
+                subject: format!("Tu pedido ha sido enviado - {}", brand),
+                heading: "Tu pedido está en camino".to_string(),
+                greeting,
+                paragraphs: vec![format!(
+                    "Buenas noticias, tu pedido {} ha sido enviado y llegará pronto.",
+                    order_id
+                )],
+                code: None,
+                action: None,
+                lines: vec![],
+                footer: questions,
+            },
+        },
+    }
+}
+
+/// Shared layout of every email.
+fn layout(locale: Locale, content: &Content) -> Markup {
+    html! {
+        (DOCTYPE)
+        html lang=(locale.code()) {
+            head {
+                meta charset="utf-8";
+                title { (content.subject) }
+                style type="text/css" {
+                    "body { font-family: Arial, Helvetica, sans-serif; text-align: center; padding: 20px; background-color: #f4f4f4; }"
+                    ".container { max-width: 500px; background: #fff; padding: 20px; border-radius: 8px; box-shadow: 0px 4px 10px rgba(0,0,0,0.1); text-align: left; }"
+                    "h2 { color: #333; margin-bottom: 15px; }"
+                    "p { font-size: 16px; color: #555; line-height: 1.6; margin-bottom: 10px; }"
+                    ".otp-container { font-size: 24px; font-weight: bold; color: #d9534f; background: #f8d7da; padding: 15px; border-radius: 5px; display: inline-block; margin: 15px 0; }"
+                    ".button { display: inline-block; background: #d9534f; color: #fff; padding: 12px 20px; border-radius: 5px; text-decoration: none; margin: 15px 0; }"
+                    "table { width: 100%; border-collapse: collapse; margin: 15px 0; }"
+                    "td { padding: 6px 0; color: #555; border-bottom: 1px solid #eee; }"
+                    "td.amount { text-align: right; }"
+                    ".footer { font-size: 12px; color: #777; margin-top: 20px; }"
+                }
+            }
+            body {
+                div class="container" style="padding: 20px;" {
+                    h2 { (content.heading) }
+                    p { (content.greeting) }
+                    @for paragraph in &content.paragraphs {
+                        p { (paragraph) }
+                    }
+                    @if let Some(code) = &content.code {
+                        div class="otp-container" { (code) }
+                    }
+                    @if let Some((label, href)) = &content.action {
+                        a class="button" href=(href) { (label) }
+                    }
+                    @if !content.lines.is_empty() {
+                        table {
+                            @for (label, amount) in &content.lines {
+                                tr {
+                                    td { (label) }
+                                    td class="amount" { (amount) }
+                                }
+                            }
+                        }
+                    }
+                    p class="footer" { (content.footer) }
+                }
+            }
+        }
+    }
+}
+
+fn plain_text(content: &Content) -> String {
+    let mut text = format!("{}\n\n{}\n\n", content.heading, content.greeting);
+
+    for paragraph in &content.paragraphs {
+        text.push_str(paragraph);
+        text.push_str("\n\n");
+    }
+    if let Some(code) = &content.code {
+        text.push_str(&format!("    {}\n\n", code));
+    }
+    if let Some((label, href)) = &content.action {
+        text.push_str(&format!("{}: {}\n\n", label, href));
+    }
+    for (label, amount) in &content.lines {
+        text.push_str(&format!("{}  {}\n", label, amount));
+    }
+    if !content.lines.is_empty() {
+        text.push('\n');
+    }
+    text.push_str("--\n");
+    text.push_str(&content.footer);
+    text.push('\n');
+
+    text
+}
+
+pub fn render(brand: &str, locale: Locale, name: &str, data: &TemplateData) -> RenderedEmail {
+    let content = content(brand, locale, name, data);
+
+    RenderedEmail {
+        html: layout(locale, &content).into_string(),
+        text: plain_text(&content),
+        subject: content.subject,
+    }
+}
+
+/// Renders a template and queues it, it is delivered by the outbox worker.
+pub async fn send_template(
+    db: &Database,
+    brand: &str,
+    locale: Locale,
+    name: &str,
+    email: &str,
+    data: TemplateData,
+) -> Result<(), AppError> {
+    let rendered = render(brand, locale, name, &data);
+
+    enqueue(
+        db,
+        EmailMessage {
+            to_name: name.to_string(),
+            to_email: email.to_string(),
+            subject: rendered.subject,
+            html: rendered.html,
+            text: rendered.text,
+        },
+    )
+    .await
+}
@@ -1,32 +1,99 @@
 mod database;
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 
 use self::database::mongo;
 mod config;
+mod errors;
 mod logger;
+mod mail;
 mod middlewares;
 mod routes;
 mod services;
 mod utils;
 
-use config::app_state::AppState;
+use config::{app_state::AppState, settings::Settings};
 use logger::init_logger::init_logger;
 use routes::app::app;
+use services::role_service::RoleCache;
+use utils::jwt::JwtKeys;
 mod models;
+mod payments;
+mod ratelimit;
+mod search;
+mod storage;
 
 #[tokio::main]
 async fn main() {
-    dotenvy::dotenv().expect(".env file not found");
+    // a .env file is optional, the variables may come from the real environment
+    let _ = dotenvy::dotenv();
     init_logger();
 
-    let db = mongo::connect_to_mongodb().await;
+    let settings = match Settings::load() {
+        Ok(settings) => settings,
+        Err(e) => {
+            tracing::error!("{}", e);
+            std::process::exit(1);
+        }
+    };
 
-    let app_state = Arc::new(AppState { db });
+    let jwt = match JwtKeys::load(&settings.jwt) {
+        Ok(keys) => Arc::new(keys),
+        Err(e) => {
+            tracing::error!("invalid jwt keys: {}", e);
+            std::process::exit(1);
+        }
+    };
 
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000")
+    let db = mongo::connect_to_mongodb(&settings.database).await;
+    mongo::create_indexes(&db).await;
+    services::role_service::seed_roles(&db).await;
+    services::inventory_service::spawn_reservation_sweeper(db.clone());
+
+    let listener = tokio::net::TcpListener::bind(settings.server.bind_address)
         .await
         .unwrap();
 
+    let payments = payments::build_provider(&settings.payments);
+    let storage = storage::build_storage(&settings.storage, &settings.server.app_url).await;
+    let mailer = mail::build_mailer(&settings.mail);
+    mail::outbox::spawn_outbox_worker(db.clone(), mailer.clone());
+    let limiter = ratelimit::build_rate_limit_store(&settings.rate_limit, &db);
+    tracing::info!("rate limits are counted in {}", limiter.name());
+
+    let search = match search::build_search(&settings.search, &db) {
+        Ok(search) => search,
+        Err(e) => {
+            tracing::error!("invalid search index: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let indexed = search
+        .rebuild(&db)
+        .await
+        .expect("failed to build the search index");
+    tracing::info!(
+        "searching with {}, {} products indexed",
+        search.name(),
+        indexed
+    );
+
+    let app_state = Arc::new(AppState {
+        db,
+        settings,
+        jwt,
+        payments,
+        storage,
+        mailer,
+        roles: Arc::new(RoleCache::default()),
+        limiter,
+        search,
+    });
+
     tracing::debug!("Listening on {}", listener.local_addr().unwrap());
-    axum::serve(listener, app(app_state)).await.unwrap();
+    axum::serve(
+        listener,
+        app(app_state).into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
@@ -2,81 +2,153 @@ use std::sync::Arc;
 
 use axum::{
     extract::{Request, State},
-    http::StatusCode,
+    http::{header::AUTHORIZATION, HeaderMap},
     middleware::Next,
     response::IntoResponse,
-    Json,
 };
 use axum_cookie::CookieManager;
 use axum_macros::debug_middleware;
-use mongodb::{bson::doc, Collection};
-use serde::Serialize;
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime},
+    Collection,
+};
+
+use crate::{
+    config::app_state::AppState,
+    errors::app_error::AppError,
+    models::{api_key_model::ApiKey, role_model::Permission, user_model::User},
+    services::{api_key_service::API_KEY_PREFIX, session_service::is_session_active},
+    utils::{
+        jwt::{decode_token, MyClaims},
+        token::hash_token,
+    },
+};
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// How the caller proved who they are, added to the request extensions next
+/// to the `User`. Session logins also get their `MyClaims` inserted.
+#[derive(Debug, Clone)]
+pub enum Credential {
+    Session(MyClaims),
+    ApiKey {
+        key_id: ObjectId,
+        scopes: Vec<Permission>,
+    },
+}
+
+/// The token from `Authorization: Bearer <token>`, if present.
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+}
 
-use crate::{config::app_state::AppState, models::user_model::User, utils::jwt::decode_token};
+async fn find_user(app_state: &AppState, user_id: ObjectId) -> Result<User, AppError> {
+    let collection: Collection<User> = app_state.db.collection("users");
+
+    collection
+        .find_one(doc! {"_id": user_id})
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("Invalid Token user not found".to_string()))
+}
+
+async fn authenticate_api_key(
+    app_state: &AppState,
+    key: &str,
+) -> Result<(User, Credential), AppError> {
+    let keys: Collection<ApiKey> = app_state.db.collection("api_keys");
+    let now = DateTime::now();
 
-#[derive(Debug, Serialize)]
-pub struct ErrorResponse {
-    pub status: &'static str,
-    pub message: String,
+    let api_key = keys
+        .find_one_and_update(
+            doc! {
+                "key_hash": hash_token(key),
+                "revoked_at": null,
+                "$or": [{"expires_at": null}, {"expires_at": {"$gt": now}}],
+            },
+            doc! {"$set": {"last_used_at": now}},
+        )
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("API key is invalid or revoked".to_string()))?;
+
+    let user = find_user(app_state, api_key.user_id).await?;
+
+    Ok((
+        user,
+        Credential::ApiKey {
+            key_id: api_key.id,
+            scopes: api_key.scopes,
+        },
+    ))
 }
 
+/// Resolves the caller from, in order, an `Authorization: Bearer` header
+/// holding a JWT or an API key, an `X-Api-Key` header, or the access token
+/// cookie.
+pub async fn authenticate(
+    app_state: &AppState,
+    cookie: &CookieManager,
+    headers: &HeaderMap,
+) -> Result<(User, Credential), AppError> {
+    let bearer = bearer_token(headers);
+
+    if let Some(key) = bearer.filter(|token| token.starts_with(API_KEY_PREFIX)) {
+        return authenticate_api_key(app_state, key).await;
+    }
+    if let Some(key) = headers.get(API_KEY_HEADER).and_then(|v| v.to_str().ok()) {
+        return authenticate_api_key(app_state, key.trim()).await;
+    }
+
+    let cookie_token = cookie.get("access_token");
+    let token = bearer
+        .or(cookie_token.as_ref().map(|c| c.value()))
+        .ok_or_else(|| AppError::Unauthorized("You are not logged in".to_string()))?;
+
+    let decoded = decode_token(&app_state.jwt, token)?;
+
+    if !is_session_active(&app_state.db, decoded.claims.session_id).await? {
+        return Err(AppError::SessionExpired);
+    }
+
+    let user = find_user(app_state, decoded.claims.user_id).await?;
+
+    Ok((user, Credential::Session(decoded.claims)))
+}
+
+/// Makes the caller available to later middleware and handlers.
+pub fn insert_identity(request: &mut Request, user: User, credential: Credential) {
+    if let Credential::Session(claims) = &credential {
+        request.extensions_mut().insert(claims.clone());
+    }
+    request.extensions_mut().insert(user);
+    request.extensions_mut().insert(credential);
+}
+
+/// Guards the self-service routes (cart, checkout, profile, 2FA, reviews),
+/// which only a signed in user can call. API keys are refused here since
+/// no scope covers acting on the account itself, routes meant for them use
+/// `require_permission`.
 #[debug_middleware]
 pub async fn validate_user(
     cookie: CookieManager,
     State(app_state): State<Arc<AppState>>,
     mut request: Request,
     next: Next,
-) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
-    let token = match cookie.get("access_token") {
-        Some(c) => c,
-        None => {
-            let error_response = ErrorResponse {
-                status: "fail",
-                message: "You are not logged in".to_string(),
-            };
-            return Err((StatusCode::UNAUTHORIZED, Json(error_response)));
-        }
-    };
-
-    let decoded = match decode_token(token.value()) {
-        Ok(data) => data,
-        Err(_) => {
-            let error_response = ErrorResponse {
-                status: "fail",
-                message: "your token is invalid please login again".to_string(),
-            };
-            return Err((StatusCode::BAD_REQUEST, Json(error_response)));
-        }
-    };
-
-    let user_id = decoded.claims.user_id;
+) -> Result<impl IntoResponse, AppError> {
+    let (user, credential) = authenticate(&app_state, &cookie, request.headers()).await?;
 
-    let collection: Collection<User> = app_state.db.collection("users");
-
-    let filter = doc! {"_id": &user_id};
-
-    let mut error_response = ErrorResponse {
-        status: "fail",
-        message: "your token is invalid please login again".to_string(),
-    };
-
-    let user = collection
-        .find_one(filter)
-        .await
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR));
-
-    match user {
-        Ok(Some(usr)) => {
-            request.extensions_mut().insert(usr);
-            Ok(next.run(request).await)
-        }
-        Err(_) => {
-            error_response.message = "Internal Server Error".to_string();
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error_response)));
-        }
-        Ok(None) => {
-            error_response.message = "Invalid Token user not found".to_string();
-            return Err((StatusCode::NOT_FOUND, Json(error_response)));
-        }
+    if let Credential::ApiKey { key_id, .. } = &credential {
+        tracing::debug!("api key {} refused on a self-service route", key_id);
+        return Err(AppError::Forbidden(
+            "API keys can't be used for this request, sign in instead".to_string(),
+        ));
     }
+
+    insert_identity(&mut request, user, credential);
+    Ok(next.run(request).await)
 }
@@ -1,2 +1,3 @@
-pub mod admin_middleware;
 pub mod auth_middleware;
+pub mod permission_middleware;
+pub mod rate_limit_middleware;
@@ -0,0 +1,82 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::IntoResponse,
+};
+use axum_cookie::CookieManager;
+use axum_macros::debug_middleware;
+
+use crate::{
+    config::app_state::AppState,
+    errors::app_error::AppError,
+    models::{
+        role_model::{Permission, DEFAULT_ROLE},
+        user_model::User,
+    },
+};
+
+use super::auth_middleware::{authenticate, insert_identity, Credential};
+
+/// Lets the request through only if the caller's role grants `permission`.
+/// Use it per route with
+/// `from_fn_with_state((app_state.clone(), Permission::X), require_permission)`.
+/// The caller is authenticated here unless `validate_user` already ran, API
+/// keys additionally need `permission` among their scopes.
+#[debug_middleware]
+pub async fn require_permission(
+    cookie: CookieManager,
+    State((app_state, permission)): State<(Arc<AppState>, Permission)>,
+    mut request: Request,
+    next: Next,
+) -> Result<impl IntoResponse, AppError> {
+    let (user, credential) = match (
+        request.extensions().get::<User>(),
+        request.extensions().get::<Credential>(),
+    ) {
+        (Some(user), Some(credential)) => (user.clone(), credential.clone()),
+        _ => {
+            let (user, credential) = authenticate(&app_state, &cookie, request.headers()).await?;
+            insert_identity(&mut request, user.clone(), credential.clone());
+            (user, credential)
+        }
+    };
+
+    let role = user.role.as_deref().unwrap_or(DEFAULT_ROLE);
+    let access = app_state.roles.access(&app_state.db, role).await?;
+
+    if !access.permissions.contains(&permission) {
+        return Err(AppError::Forbidden(format!(
+            "You need the {} permission for this request",
+            permission.as_str()
+        )));
+    }
+
+    if access.require_two_factor
+        && matches!(credential, Credential::Session(_))
+        && !user.two_factor_enabled()
+    {
+        return Err(AppError::Forbidden(
+            "Your role requires two-factor authentication, enable it at /api/auth/2fa/setup"
+                .to_string(),
+        ));
+    }
+
+    // an API key never grants more than its owner has, only less
+    if let Credential::ApiKey { key_id, scopes } = &credential {
+        if !scopes.contains(&permission) {
+            tracing::debug!(
+                "api key {} is missing scope {}",
+                key_id,
+                permission.as_str()
+            );
+            return Err(AppError::Forbidden(format!(
+                "This API key is not scoped for {}",
+                permission.as_str()
+            )));
+        }
+    }
+
+    Ok(next.run(request).await)
+}
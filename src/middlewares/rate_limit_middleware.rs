@@ -0,0 +1,114 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    middleware::Next,
+    response::IntoResponse,
+};
+use axum_macros::debug_middleware;
+
+use crate::{config::app_state::AppState, errors::app_error::AppError, ratelimit::store::enforce};
+
+/// How often one client ip may call a route, see `rate_limit`.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    /// Names the counter, routes sharing a name share the budget.
+    pub name: &'static str,
+    pub max: u32,
+    pub window: Duration,
+}
+
+impl RateLimit {
+    pub const fn per_minutes(name: &'static str, max: u32, minutes: u64) -> Self {
+        RateLimit {
+            name,
+            max,
+            window: Duration::from_secs(minutes * 60),
+        }
+    }
+}
+
+/// Each proxy appends the address it got the request from, so everything
+/// left of what the trusted proxies added is up to the client. With
+/// `trusted_proxies` hops the client is the `trusted_proxies`th entry from
+/// the right, or the left-most one when the header has fewer entries.
+fn forwarded_ip(header: &str, trusted_proxies: usize) -> Option<IpAddr> {
+    let entries = header.split(',').collect::<Vec<&str>>();
+    let index = entries.len().saturating_sub(trusted_proxies);
+
+    entries[index].trim().parse().ok()
+}
+
+fn client_ip(request: &Request, trusted_proxies: usize) -> Option<IpAddr> {
+    if trusted_proxies > 0 {
+        let forwarded = request
+            .headers()
+            .get("x-forwarded-for")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| forwarded_ip(value, trusted_proxies));
+        if forwarded.is_some() {
+            return forwarded;
+        }
+    }
+
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+}
+
+/// Limits calls per client ip. Use it per route with
+/// `from_fn_with_state((app_state.clone(), RateLimit::per_minutes(..)), rate_limit)`.
+#[debug_middleware]
+pub async fn rate_limit(
+    State((app_state, limit)): State<(Arc<AppState>, RateLimit)>,
+    request: Request,
+    next: Next,
+) -> Result<impl IntoResponse, AppError> {
+    let ip = client_ip(&request, app_state.settings.rate_limit.trusted_proxies)
+        .map(|ip| ip.to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    enforce(
+        app_state.limiter.as_ref(),
+        &format!("{}:ip:{}", limit.name, ip),
+        limit.max,
+        limit.window,
+    )
+    .await?;
+
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forwarded_ip_skips_the_trusted_proxies() {
+        let header = "10.0.0.1, 203.0.113.7, 198.51.100.2";
+
+        assert_eq!(forwarded_ip(header, 1), "198.51.100.2".parse().ok());
+        assert_eq!(forwarded_ip(header, 2), "203.0.113.7".parse().ok());
+        assert_eq!(forwarded_ip(header, 3), "10.0.0.1".parse().ok());
+    }
+
+    #[test]
+    fn forwarded_ip_falls_back_to_the_left_most_entry_of_a_short_header() {
+        assert_eq!(forwarded_ip("203.0.113.7", 2), "203.0.113.7".parse().ok());
+        assert_eq!(
+            forwarded_ip("203.0.113.7, 198.51.100.2", 5),
+            "203.0.113.7".parse().ok()
+        );
+    }
+
+    #[test]
+    fn forwarded_ip_rejects_garbage() {
+        assert_eq!(forwarded_ip("not an ip", 1), None);
+        assert_eq!(forwarded_ip("", 1), None);
+    }
+}
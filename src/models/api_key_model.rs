@@ -0,0 +1,78 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+use super::role_model::Permission;
+
+/// A long lived credential for server-to-server integrations. Only the
+/// sha256 of the key is stored, the key itself is shown once on creation.
+/// A request made with it acts as `user_id` but is limited to `scopes`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub name: String,
+    pub user_id: ObjectId,
+    pub key_hash: String,
+    /// First characters of the key so it can be recognised in listings.
+    pub prefix: String,
+    pub scopes: Vec<Permission>,
+    pub created_by: ObjectId,
+    pub created_at: DateTime,
+    pub expires_at: Option<DateTime>,
+    pub last_used_at: Option<DateTime>,
+    pub revoked_at: Option<DateTime>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiKey {
+    pub name: String,
+    /// Account the key acts as, defaults to the admin creating it.
+    pub user_id: Option<String>,
+    pub scopes: Vec<Permission>,
+    pub expires_in_days: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApiKeyQuery {
+    pub user_id: Option<String>,
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Listing view of a key, without its hash.
+#[derive(Debug, Serialize)]
+pub struct ApiKeyView {
+    pub id: String,
+    pub name: String,
+    pub user_id: String,
+    pub prefix: String,
+    pub scopes: Vec<Permission>,
+    pub created_at: DateTime,
+    pub expires_at: Option<DateTime>,
+    pub last_used_at: Option<DateTime>,
+    pub revoked_at: Option<DateTime>,
+}
+
+impl From<ApiKey> for ApiKeyView {
+    fn from(key: ApiKey) -> Self {
+        ApiKeyView {
+            id: key.id.to_hex(),
+            name: key.name,
+            user_id: key.user_id.to_hex(),
+            prefix: key.prefix,
+            scopes: key.scopes,
+            created_at: key.created_at,
+            expires_at: key.expires_at,
+            last_used_at: key.last_used_at,
+            revoked_at: key.revoked_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreatedApiKey {
+    /// The only time the full key is returned.
+    pub key: String,
+    #[serde(flatten)]
+    pub api_key: ApiKeyView,
+}
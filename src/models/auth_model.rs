@@ -1,3 +1,4 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -5,3 +6,96 @@ pub struct Login {
     pub email: String,
     pub password: String,
 }
+
+#[derive(Debug, Deserialize)]
+pub struct ForgotPasswordInput {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordInput {
+    pub token: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasswordReset {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub user_id: ObjectId,
+    pub token_hash: String,
+    pub created_at: DateTime,
+    pub expires_at: DateTime,
+    pub used_at: Option<DateTime>,
+}
+
+/// Returned by login and refresh for clients that send `Authorization:
+/// Bearer` instead of keeping cookies.
+#[derive(Debug, Serialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: &'static str,
+    pub expires_in: i64,
+}
+
+/// Issued by `login` instead of a session when the account has two-factor
+/// authentication. Only the hash of the challenge token is stored.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginChallenge {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub user_id: ObjectId,
+    pub token_hash: String,
+    pub attempts: u32,
+    pub created_at: DateTime,
+    pub expires_at: DateTime,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChallengeResponse {
+    pub two_factor_required: bool,
+    pub challenge_token: String,
+    pub expires_in: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorLogin {
+    pub challenge_token: String,
+    /// A TOTP code or one of the recovery codes.
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorCode {
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DisableTwoFactor {
+    pub password: String,
+    pub code: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TwoFactorSetup {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
+
+/// Failed logins of one account, keyed by email so unknown accounts are
+/// locked the same way and can't be told apart. Forgotten a day after the
+/// last failure.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginFailures {
+    #[serde(rename = "_id")]
+    pub email: String,
+    pub failures: u32,
+    pub last_failure_at: DateTime,
+    pub locked_until: Option<DateTime>,
+}
@@ -0,0 +1,25 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Brand {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub name: String,
+    pub slug: String,
+    pub created_at: DateTime,
+    pub updated_at: DateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateBrand {
+    pub name: String,
+    /// Derived from the name when not given.
+    pub slug: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateBrand {
+    pub name: Option<String>,
+    pub slug: Option<String>,
+}
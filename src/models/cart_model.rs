@@ -1,9 +1,14 @@
 use mongodb::bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
 
+use super::products_model::Products;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CartItem {
     pub product_id: String,
+    /// Required for products that have variants, absent otherwise.
+    #[serde(default)]
+    pub sku: Option<String>,
     pub quantity: u32,
 }
 
@@ -15,7 +20,36 @@ pub struct Cart {
     pub total_price: Option<f32>,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateCart {
     pub product_id: String,
 }
+
+/// Picks the variant a cart line endpoint applies to.
+#[derive(Debug, Deserialize)]
+pub struct CartItemQuery {
+    pub sku: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateCartItem {
+    pub quantity: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CartLine {
+    pub product_id: String,
+    pub sku: Option<String>,
+    pub quantity: u32,
+    pub unit_price: f32,
+    pub line_total: f32,
+    pub product: Products,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CartResponse {
+    pub _id: Option<ObjectId>,
+    pub items: Vec<CartLine>,
+    pub total_price: f32,
+}
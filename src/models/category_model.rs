@@ -0,0 +1,62 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+use super::products_model::nullable;
+
+/// A node of the category tree. `path` holds the ids of its ancestors, root
+/// first, so a whole subtree is found by matching on `path`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Category {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub name: String,
+    pub slug: String,
+    pub parent_id: Option<ObjectId>,
+    #[serde(default)]
+    pub path: Vec<ObjectId>,
+    /// Order among its siblings, lowest first.
+    #[serde(default)]
+    pub position: i32,
+    pub created_at: DateTime,
+    pub updated_at: DateTime,
+}
+
+impl Category {
+    /// Path of a product in this category, or of a category right below it.
+    pub fn path_below(&self) -> Vec<ObjectId> {
+        let mut path = self.path.clone();
+        path.push(self.id);
+        path
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCategory {
+    pub name: String,
+    /// Derived from the name when not given.
+    pub slug: Option<String>,
+    pub parent_id: Option<String>,
+    #[serde(default)]
+    pub position: i32,
+}
+
+/// Partial update, `parent_id: null` moves the category to the top level.
+#[derive(Debug, Deserialize)]
+pub struct UpdateCategory {
+    pub name: Option<String>,
+    pub slug: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub parent_id: Option<Option<String>>,
+    pub position: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CategoryNode {
+    pub id: ObjectId,
+    pub name: String,
+    pub slug: String,
+    pub position: i32,
+    /// Products in this category and every category below it.
+    pub product_count: u64,
+    pub children: Vec<CategoryNode>,
+}
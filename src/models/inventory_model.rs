@@ -0,0 +1,123 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReservationStatus {
+    Active,
+    Committed,
+    Released,
+}
+
+impl ReservationStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReservationStatus::Active => "active",
+            ReservationStatus::Committed => "committed",
+            ReservationStatus::Released => "released",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReservedItem {
+    pub product_id: ObjectId,
+    #[serde(default)]
+    pub sku: Option<String>,
+    pub quantity: u32,
+}
+
+/// Stock held back for an order between checkout and payment. The stock is
+/// taken off the product when the reservation is made, committing it keeps
+/// it that way and releasing it (cancellation or expiry) puts it back.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockReservation {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub order_id: ObjectId,
+    pub user_id: ObjectId,
+    pub items: Vec<ReservedItem>,
+    pub status: ReservationStatus,
+    pub created_at: DateTime,
+    pub expires_at: DateTime,
+    pub closed_at: Option<DateTime>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MovementReason {
+    Initial,
+    Adjustment,
+    Reserved,
+    Released,
+}
+
+/// One entry of the stock ledger, every change to a product's stock writes one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockMovement {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub product_id: ObjectId,
+    pub sku: Option<String>,
+    pub delta: i64,
+    pub stock_after: i64,
+    pub reason: MovementReason,
+    pub order_id: Option<ObjectId>,
+    pub changed_by: Option<ObjectId>,
+    pub note: Option<String>,
+    pub created_at: DateTime,
+}
+
+impl StockMovement {
+    pub fn new(
+        product_id: ObjectId,
+        sku: Option<String>,
+        delta: i64,
+        stock_after: i64,
+        reason: MovementReason,
+    ) -> Self {
+        StockMovement {
+            id: ObjectId::new(),
+            product_id,
+            sku,
+            delta,
+            stock_after,
+            reason,
+            order_id: None,
+            changed_by: None,
+            note: None,
+            created_at: DateTime::now(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StockAdjustment {
+    pub sku: Option<String>,
+    pub delta: i64,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StockLevel {
+    pub sku: String,
+    pub stock: i64,
+    pub reserved: i64,
+}
+
+/// `stock` is what can still be sold, `reserved` is what is held by orders
+/// waiting for payment and already taken out of `stock`. Products with
+/// variants track both per variant.
+#[derive(Debug, Serialize)]
+pub struct ProductStock {
+    pub product_id: ObjectId,
+    pub stock: i64,
+    pub reserved: i64,
+    pub variants: Vec<StockLevel>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MovementQuery {
+    pub sku: Option<String>,
+    pub limit: Option<i64>,
+}
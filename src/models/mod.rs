@@ -1,4 +1,14 @@
+pub mod api_key_model;
 pub mod auth_model;
+pub mod brand_model;
 pub mod cart_model;
+pub mod category_model;
+pub mod inventory_model;
+pub mod order_model;
+pub mod outbox_model;
+pub mod payment_model;
 pub mod products_model;
+pub mod review_model;
+pub mod role_model;
+pub mod session_model;
 pub mod user_model;
@@ -0,0 +1,167 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStatus {
+    Pending,
+    Paid,
+    Fulfilled,
+    Shipped,
+    Delivered,
+    Cancelled,
+    Refunded,
+}
+
+impl OrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Pending => "pending",
+            OrderStatus::Paid => "paid",
+            OrderStatus::Fulfilled => "fulfilled",
+            OrderStatus::Shipped => "shipped",
+            OrderStatus::Delivered => "delivered",
+            OrderStatus::Cancelled => "cancelled",
+            OrderStatus::Refunded => "refunded",
+        }
+    }
+
+    /// States an order may move to `self` from.
+    ///
+    /// pending -> paid -> fulfilled -> shipped -> delivered, an unpaid order
+    /// can be cancelled and anything that has been paid for but is not yet in
+    /// transit, or has been delivered, can be refunded.
+    pub fn allowed_from(&self) -> &'static [OrderStatus] {
+        match self {
+            OrderStatus::Pending => &[],
+            OrderStatus::Paid => &[OrderStatus::Pending],
+            OrderStatus::Fulfilled => &[OrderStatus::Paid],
+            OrderStatus::Shipped => &[OrderStatus::Fulfilled],
+            OrderStatus::Delivered => &[OrderStatus::Shipped],
+            OrderStatus::Cancelled => &[OrderStatus::Pending],
+            OrderStatus::Refunded => &[
+                OrderStatus::Paid,
+                OrderStatus::Fulfilled,
+                OrderStatus::Delivered,
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderItem {
+    pub product_id: String,
+    #[serde(default)]
+    pub sku: Option<String>,
+    pub title: String,
+    pub image_url: Option<String>,
+    pub unit_price: f32,
+    pub quantity: u32,
+    pub line_total: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusChange {
+    pub status: OrderStatus,
+    pub changed_at: DateTime,
+    pub changed_by: Option<ObjectId>,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaymentInfo {
+    pub provider: String,
+    pub intent_id: String,
+    pub amount: i64,
+    pub currency: String,
+    pub status: String,
+    /// Sum of all refunds so far, the order is refunded once it reaches
+    /// `amount`.
+    #[serde(default)]
+    pub refunded_amount: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Order {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub user_id: ObjectId,
+    pub items: Vec<OrderItem>,
+    pub total_price: f32,
+    pub status: OrderStatus,
+    pub status_history: Vec<StatusChange>,
+    #[serde(default)]
+    pub payment: Option<PaymentInfo>,
+    pub created_at: DateTime,
+    pub updated_at: DateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateOrderStatus {
+    pub status: OrderStatus,
+    pub note: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OrderStatus::{self, *};
+
+    const ALL: [OrderStatus; 7] = [
+        Pending, Paid, Fulfilled, Shipped, Delivered, Cancelled, Refunded,
+    ];
+
+    fn allowed(from: OrderStatus, to: OrderStatus) -> bool {
+        to.allowed_from().contains(&from)
+    }
+
+    #[test]
+    fn transition_table() {
+        let cases = [
+            (Pending, Paid, true),
+            (Pending, Cancelled, true),
+            (Pending, Fulfilled, false),
+            (Pending, Refunded, false),
+            (Paid, Fulfilled, true),
+            (Paid, Refunded, true),
+            (Paid, Cancelled, false),
+            (Paid, Shipped, false),
+            (Fulfilled, Shipped, true),
+            (Fulfilled, Refunded, true),
+            (Shipped, Delivered, true),
+            // in transit, it has to arrive before it can be refunded
+            (Shipped, Refunded, false),
+            (Delivered, Refunded, true),
+            (Delivered, Shipped, false),
+            (Cancelled, Paid, false),
+            (Refunded, Paid, false),
+        ];
+
+        for (from, to, expected) in cases {
+            assert_eq!(
+                allowed(from, to),
+                expected,
+                "{} -> {}",
+                from.as_str(),
+                to.as_str()
+            );
+        }
+    }
+
+    #[test]
+    fn nothing_moves_back_to_pending_or_out_of_final_states() {
+        for status in ALL {
+            assert!(!allowed(status, Pending), "{} -> pending", status.as_str());
+            assert!(
+                !allowed(Cancelled, status),
+                "cancelled -> {}",
+                status.as_str()
+            );
+            assert!(
+                !allowed(Refunded, status),
+                "refunded -> {}",
+                status.as_str()
+            );
+            assert!(!allowed(status, status), "{} -> itself", status.as_str());
+        }
+    }
+}
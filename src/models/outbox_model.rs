@@ -0,0 +1,88 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::mail::mailer::EmailMessage;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutboxStatus {
+    Pending,
+    Sending,
+    Sent,
+    Dead,
+}
+
+impl OutboxStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutboxStatus::Pending => "pending",
+            OutboxStatus::Sending => "sending",
+            OutboxStatus::Sent => "sent",
+            OutboxStatus::Dead => "dead",
+        }
+    }
+}
+
+/// An email waiting to be delivered by the outbox worker. Failed attempts
+/// are retried with a growing delay until the email is given up on and left
+/// as `dead` for an admin to look at.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxEmail {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub message: EmailMessage,
+    pub status: OutboxStatus,
+    pub attempts: u32,
+    pub next_attempt_at: DateTime,
+    /// While `sending`, when the worker's claim on the email runs out.
+    pub locked_until: Option<DateTime>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime,
+    pub sent_at: Option<DateTime>,
+}
+
+/// Listing view of an outbox email, without its body.
+#[derive(Debug, Serialize)]
+pub struct OutboxEmailView {
+    pub id: String,
+    pub to_name: String,
+    pub to_email: String,
+    pub subject: String,
+    pub status: OutboxStatus,
+    pub attempts: u32,
+    pub next_attempt_at: DateTime,
+    pub last_error: Option<String>,
+    pub created_at: DateTime,
+    pub sent_at: Option<DateTime>,
+}
+
+impl From<OutboxEmail> for OutboxEmailView {
+    fn from(email: OutboxEmail) -> Self {
+        OutboxEmailView {
+            id: email.id.to_hex(),
+            to_name: email.message.to_name,
+            to_email: email.message.to_email,
+            subject: email.message.subject,
+            status: email.status,
+            attempts: email.attempts,
+            next_attempt_at: email.next_attempt_at,
+            last_error: email.last_error,
+            created_at: email.created_at,
+            sent_at: email.sent_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OutboxQuery {
+    pub status: Option<OutboxStatus>,
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PreviewQuery {
+    pub locale: Option<String>,
+    /// `html` (the default) or `text`.
+    pub format: Option<String>,
+}
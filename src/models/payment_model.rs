@@ -0,0 +1,18 @@
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+
+/// A webhook event that has been received, keyed by the provider's event id
+/// so redeliveries of the same event are only acted upon once.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PaymentEvent {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub provider: String,
+    pub kind: String,
+    pub received_at: DateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefundInput {
+    pub amount: Option<i64>,
+}
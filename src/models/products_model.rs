@@ -1,26 +1,220 @@
-use mongodb::bson::oid::ObjectId;
-use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-#[derive(Debug, Serialize, Deserialize)]
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::utils::pagination::Page;
+
+/// An axis a product varies along, e.g. `size` with `S`, `M` and `L`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProductOption {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+/// One sellable combination of option values. Price and images fall back to
+/// the product's own when not set, stock is always tracked per variant.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProductVariant {
+    pub sku: String,
+    pub options: BTreeMap<String, String>,
+    pub price: Option<f32>,
+    pub offer_price: Option<f32>,
+    #[serde(default)]
+    pub stock: i64,
+    pub image_url: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Products {
     pub _id: Option<ObjectId>,
     pub title: String,
     pub description: String,
     pub price: f32,
     pub offer_price: Option<f32>,
+    /// Name of the category, copied from `category_id` for search and facets.
+    #[serde(default)]
     pub category: String,
+    #[serde(default)]
+    pub category_id: Option<ObjectId>,
+    /// `category_id` and all its ancestors, so a category lists the products
+    /// of its subcategories too.
+    #[serde(default)]
+    pub category_path: Vec<ObjectId>,
     pub image_url: Option<Vec<String>>,
+    /// Name of the brand, copied from `brand_id`.
+    #[serde(default)]
     pub brand: String,
+    #[serde(default)]
+    pub brand_id: Option<ObjectId>,
+    #[serde(default)]
+    pub stock: i64,
+    #[serde(default)]
+    pub options: Vec<ProductOption>,
+    #[serde(default)]
+    pub variants: Vec<ProductVariant>,
+    /// Average of the approved reviews, kept up to date by the review
+    /// service.
+    #[serde(default)]
+    pub rating_average: f64,
+    #[serde(default)]
+    pub rating_count: i64,
+    /// Set when the product is soft deleted, it is then hidden from the
+    /// catalogue and can no longer be bought.
+    #[serde(default)]
+    pub deleted_at: Option<DateTime>,
+}
+
+impl Products {
+    pub fn variant(&self, sku: &str) -> Option<&ProductVariant> {
+        self.variants.iter().find(|variant| variant.sku == sku)
+    }
+
+    /// Price a customer pays for the product, or for one of its variants when
+    /// `sku` is given. `None` if the variant does not exist.
+    pub fn unit_price(&self, sku: Option<&str>) -> Option<f32> {
+        match sku {
+            Some(sku) => self.variant(sku).map(|variant| {
+                variant
+                    .offer_price
+                    .or(variant.price)
+                    .unwrap_or(self.offer_price.unwrap_or(self.price))
+            }),
+            None => Some(self.offer_price.unwrap_or(self.price)),
+        }
+    }
+
+    /// Stock of the product, or of one of its variants when `sku` is given.
+    pub fn stock_of(&self, sku: Option<&str>) -> Option<i64> {
+        match sku {
+            Some(sku) => self.variant(sku).map(|variant| variant.stock),
+            None => Some(self.stock),
+        }
+    }
+
+    pub fn first_image(&self, sku: Option<&str>) -> Option<String> {
+        sku.and_then(|sku| self.variant(sku))
+            .and_then(|variant| variant.image_url.as_ref())
+            .or(self.image_url.as_ref())
+            .and_then(|urls| urls.first().cloned())
+    }
+}
+
+/// Lets a field tell "not sent" (`None`) apart from an explicit `null`
+/// (`Some(None)`), so a partial update can clear optional values.
+pub fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Partial update of a product, fields that are not sent stay unchanged.
+/// Stock is not part of it, it only changes through inventory adjustments.
+#[derive(Debug, Deserialize)]
+pub struct UpdateProduct {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub price: Option<f32>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub offer_price: Option<Option<f32>>,
+    pub category_id: Option<String>,
+    pub brand_id: Option<String>,
+    pub options: Option<Vec<ProductOption>>,
+    pub variants: Option<Vec<ProductVariant>>,
 }
 
 #[derive(Debug, Deserialize)]
-pub struct ProductPaginate {
-    pub page: i32,
+pub struct DeleteProductQuery {
+    #[serde(default)]
+    pub hard: bool,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct DeleteImageQuery {
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImageLinkQuery {
+    pub url: String,
+    pub expires_in: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImageLink {
+    pub url: String,
+    pub expires_in: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProductSort {
+    /// Best search match first, falls back to `newest` without a search.
+    #[default]
+    Relevance,
+    Newest,
+    PriceAsc,
+    PriceDesc,
+    /// Best rated first.
+    Rating,
+}
+
+/// Query of the public product listing. Every filter is optional and they
+/// all have to match.
 #[derive(Debug, Deserialize)]
 pub struct ProductFilter {
+    /// Full-text search over title, brand and category.
+    #[serde(alias = "title")]
+    pub q: Option<String>,
     pub category: Option<String>,
-    pub title: Option<String>,
     pub brand: Option<String>,
+    pub category_id: Option<String>,
+    /// Also list the products of subcategories of `category_id`, on by
+    /// default.
+    pub include_descendants: Option<bool>,
+    pub brand_id: Option<String>,
+    /// Bounds on the price a customer pays, i.e. the offer price if any.
+    pub min_price: Option<f64>,
+    pub max_price: Option<f64>,
+    pub has_offer: Option<bool>,
+    /// Lowest average review rating, 1 to 5.
+    pub min_rating: Option<f64>,
+    pub in_stock: Option<bool>,
+    #[serde(default)]
+    pub sort: ProductSort,
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FacetCount {
+    pub value: String,
+    pub count: u64,
+}
+
+/// Products priced from `min` up to, not including, `max`. The last bucket
+/// has no upper bound.
+#[derive(Debug, Serialize)]
+pub struct PriceBucket {
+    pub min: f64,
+    pub max: Option<f64>,
+    pub count: u64,
+}
+
+/// Counts per value for narrowing a listing down further. Each facet
+/// ignores its own filter, so picking a brand still shows the other brands.
+#[derive(Debug, Serialize)]
+pub struct ProductFacets {
+    pub brands: Vec<FacetCount>,
+    pub categories: Vec<FacetCount>,
+    pub prices: Vec<PriceBucket>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProductListing {
+    #[serde(flatten)]
+    pub page: Page<Products>,
+    pub facets: ProductFacets,
 }
@@ -0,0 +1,94 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+/// New and edited reviews wait for a moderator, only approved ones are
+/// shown and count towards the product's rating.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl ReviewStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReviewStatus::Pending => "pending",
+            ReviewStatus::Approved => "approved",
+            ReviewStatus::Rejected => "rejected",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Review {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub product_id: ObjectId,
+    pub user_id: ObjectId,
+    /// Name of the author when the review was written, shown next to it.
+    pub author_name: String,
+    pub rating: u8,
+    pub text: String,
+    pub status: ReviewStatus,
+    pub moderation_note: Option<String>,
+    pub moderated_by: Option<ObjectId>,
+    #[serde(default)]
+    pub helpful_count: i64,
+    pub created_at: DateTime,
+    pub updated_at: DateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateReview {
+    pub rating: u8,
+    pub text: String,
+}
+
+/// Editing a review sends it back to moderation.
+#[derive(Debug, Deserialize)]
+pub struct UpdateReview {
+    pub rating: Option<u8>,
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ModerateReview {
+    pub status: ReviewStatus,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewSort {
+    #[default]
+    Newest,
+    Helpful,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReviewQuery {
+    #[serde(default)]
+    pub sort: ReviewSort,
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ModerationQuery {
+    /// `pending` when not given.
+    pub status: Option<ReviewStatus>,
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// A user found a review helpful, at most once per review.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReviewVote {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub review_id: ObjectId,
+    pub user_id: ObjectId,
+    pub created_at: DateTime,
+}
@@ -0,0 +1,111 @@
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Permission {
+    #[serde(rename = "product:write")]
+    ProductWrite,
+    #[serde(rename = "inventory:read")]
+    InventoryRead,
+    #[serde(rename = "inventory:write")]
+    InventoryWrite,
+    #[serde(rename = "order:read")]
+    OrderRead,
+    #[serde(rename = "order:write")]
+    OrderWrite,
+    #[serde(rename = "order:refund")]
+    OrderRefund,
+    #[serde(rename = "payment:capture")]
+    PaymentCapture,
+    #[serde(rename = "mail:manage")]
+    MailManage,
+    #[serde(rename = "user:read")]
+    UserRead,
+    #[serde(rename = "user:write")]
+    UserWrite,
+    #[serde(rename = "role:manage")]
+    RoleManage,
+    #[serde(rename = "api_key:manage")]
+    ApiKeyManage,
+    #[serde(rename = "review:moderate")]
+    ReviewModerate,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 13] = [
+        Permission::ProductWrite,
+        Permission::InventoryRead,
+        Permission::InventoryWrite,
+        Permission::OrderRead,
+        Permission::OrderWrite,
+        Permission::OrderRefund,
+        Permission::PaymentCapture,
+        Permission::MailManage,
+        Permission::UserRead,
+        Permission::UserWrite,
+        Permission::RoleManage,
+        Permission::ApiKeyManage,
+        Permission::ReviewModerate,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::ProductWrite => "product:write",
+            Permission::InventoryRead => "inventory:read",
+            Permission::InventoryWrite => "inventory:write",
+            Permission::OrderRead => "order:read",
+            Permission::OrderWrite => "order:write",
+            Permission::OrderRefund => "order:refund",
+            Permission::PaymentCapture => "payment:capture",
+            Permission::MailManage => "mail:manage",
+            Permission::UserRead => "user:read",
+            Permission::UserWrite => "user:write",
+            Permission::RoleManage => "role:manage",
+            Permission::ApiKeyManage => "api_key:manage",
+            Permission::ReviewModerate => "review:moderate",
+        }
+    }
+}
+
+/// Built-in role every new account gets.
+pub const DEFAULT_ROLE: &str = "user";
+/// Built-in role that always holds every permission.
+pub const ADMIN_ROLE: &str = "admin";
+
+/// A named set of permissions, users reference it by name in `User.role`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Role {
+    #[serde(rename = "_id")]
+    pub name: String,
+    pub description: Option<String>,
+    pub permissions: Vec<Permission>,
+    /// Members must have two-factor authentication enabled before any of
+    /// the role's permissions apply to their sessions.
+    #[serde(default)]
+    pub require_two_factor: bool,
+    /// Built-in roles can be edited but not deleted.
+    pub built_in: bool,
+    pub created_at: DateTime,
+    pub updated_at: DateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRole {
+    pub name: String,
+    pub description: Option<String>,
+    pub permissions: Vec<Permission>,
+    #[serde(default)]
+    pub require_two_factor: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateRole {
+    pub description: Option<String>,
+    pub permissions: Option<Vec<Permission>>,
+    pub require_two_factor: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AssignRole {
+    pub role: String,
+}
@@ -0,0 +1,17 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+/// A refresh-token family. The current token is stored hashed in
+/// `refresh_token_hash`; every token it replaced is kept in
+/// `rotated_token_hashes` so a replayed token can be detected.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub user_id: ObjectId,
+    pub refresh_token_hash: String,
+    pub rotated_token_hashes: Vec<String>,
+    pub created_at: DateTime,
+    pub expires_at: DateTime,
+    pub revoked_at: Option<DateTime>,
+}
@@ -1,5 +1,4 @@
-use chrono::{DateTime, Utc};
-use mongodb::bson::oid::ObjectId;
+use mongodb::bson::{oid::ObjectId, DateTime};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -10,24 +9,119 @@ pub struct User {
     pub email: String,
     pub password: String,
     pub role: Option<String>,
+    /// Language emails are sent in, see `mail::templates::Locale`.
+    #[serde(default)]
+    pub locale: Option<String>,
+    #[serde(default)]
+    pub two_factor: Option<TwoFactor>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl User {
+    pub fn two_factor_enabled(&self) -> bool {
+        self.two_factor
+            .as_ref()
+            .is_some_and(|tf| tf.confirmed_at.is_some())
+    }
+}
+
+/// TOTP enrolment of a user. Until `confirmed_at` is set the secret is only
+/// pending and login does not ask for a code.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TwoFactor {
+    pub secret: String,
+    pub confirmed_at: Option<DateTime>,
+    /// sha256 of each unused recovery code.
+    #[serde(default)]
+    pub recovery_code_hashes: Vec<String>,
+    /// Last accepted time step, a code is never accepted twice.
+    pub last_used_step: Option<i64>,
+}
+
+/// What the API returns for a user, never includes the password hash.
+#[derive(Debug, Serialize)]
+pub struct PublicUser {
+    pub id: Option<String>,
+    pub name: String,
+    pub email: String,
+    pub role: Option<String>,
+    pub locale: Option<String>,
+    pub two_factor_enabled: bool,
+}
+
+impl From<User> for PublicUser {
+    fn from(user: User) -> Self {
+        PublicUser {
+            two_factor_enabled: user.two_factor_enabled(),
+            id: user.id.map(|id| id.to_hex()),
+            name: user.name,
+            email: user.email,
+            role: user.role,
+            locale: user.locale,
+        }
+    }
+}
+
+/// Fields a user may change on their own account.
+#[derive(Debug, Deserialize)]
+pub struct UpdateProfile {
+    pub name: Option<String>,
+    pub locale: Option<String>,
+}
+
+/// Fields an admin may change on any account, roles are assigned through
+/// `/api/roles/users/{id}`.
+#[derive(Debug, Deserialize)]
 pub struct UpdateUser {
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub locale: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteAccount {
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UserQuery {
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+    /// Case-insensitive match on name or email.
+    pub search: Option<String>,
+    pub role: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RegisterInput {
+    pub email: String,
+    pub password: String,
     pub name: String,
-    pub age: u8,
+    #[serde(default)]
+    pub locale: Option<String>,
 }
 
+/// A registration waiting for its emailed code, keyed by the random id in
+/// the `session_token` cookie. There is at most one per email and a TTL
+/// index removes it once `expires_at` has passed.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TempUser {
-    #[serde(skip_deserializing)]
-    pub _id: String,
-    pub otp: Option<String>,
+    #[serde(rename = "_id")]
+    pub id: String,
+    /// bcrypt hash of the emailed code.
+    pub otp_hash: String,
     pub email: String,
     pub password: String,
     pub name: String,
-    #[serde(skip_deserializing)]
-    pub expires_at: DateTime<Utc>,
+    #[serde(default)]
+    pub locale: Option<String>,
+    /// Wrong codes entered so far, the registration is dropped at a limit.
+    #[serde(default)]
+    pub attempts: u32,
+    /// Codes sent again through `resend_otp`.
+    #[serde(default)]
+    pub resends: u32,
+    pub last_sent_at: DateTime,
+    pub expires_at: DateTime,
 }
 
 #[derive(Serialize, Deserialize)]
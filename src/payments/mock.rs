@@ -0,0 +1,95 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use crate::{
+    config::settings::PaymentSettings, errors::app_error::AppError, models::order_model::Order,
+};
+
+use super::{
+    provider::{order_amount, PaymentIntent, PaymentProvider, Refund, WebhookEvent},
+    signature,
+};
+
+/// In-process provider for development and tests. Intents live in memory and
+/// webhooks are verified with the same scheme as Stripe, signed with
+/// `PAYMENT_WEBHOOK_SECRET`.
+pub struct MockPaymentProvider {
+    webhook_secret: String,
+    intents: Mutex<HashMap<String, PaymentIntent>>,
+}
+
+impl MockPaymentProvider {
+    pub fn new(settings: &PaymentSettings) -> Self {
+        MockPaymentProvider {
+            webhook_secret: settings.webhook_secret.clone(),
+            intents: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl PaymentProvider for MockPaymentProvider {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    async fn create_intent(
+        &self,
+        order: &Order,
+        currency: &str,
+    ) -> Result<PaymentIntent, AppError> {
+        let id = format!("pi_mock_{}", Uuid::new_v4().simple());
+        let intent = PaymentIntent {
+            client_secret: Some(format!("{}_secret", id)),
+            id: id.clone(),
+            amount: order_amount(order),
+            currency: currency.to_string(),
+            status: "requires_payment_method".to_string(),
+        };
+
+        self.intents.lock().unwrap().insert(id, intent.clone());
+
+        Ok(intent)
+    }
+
+    async fn retrieve(&self, intent_id: &str) -> Result<PaymentIntent, AppError> {
+        self.intents
+            .lock()
+            .unwrap()
+            .get(intent_id)
+            .cloned()
+            .ok_or_else(|| AppError::NotFound("payment intent not found".to_string()))
+    }
+
+    async fn capture(&self, intent_id: &str) -> Result<PaymentIntent, AppError> {
+        let mut intents = self.intents.lock().unwrap();
+        let intent = intents
+            .get_mut(intent_id)
+            .ok_or_else(|| AppError::NotFound("payment intent not found".to_string()))?;
+
+        intent.status = "succeeded".to_string();
+
+        Ok(intent.clone())
+    }
+
+    async fn refund(&self, intent_id: &str, amount: Option<i64>) -> Result<Refund, AppError> {
+        let intents = self.intents.lock().unwrap();
+        let intent = intents
+            .get(intent_id)
+            .ok_or_else(|| AppError::NotFound("payment intent not found".to_string()))?;
+
+        Ok(Refund {
+            id: format!("re_mock_{}", Uuid::new_v4().simple()),
+            payment_intent: intent.id.clone(),
+            amount: amount.unwrap_or(intent.amount),
+            status: "succeeded".to_string(),
+        })
+    }
+
+    fn verify_webhook(&self, payload: &[u8], signature: &str) -> Result<WebhookEvent, AppError> {
+        signature::verify(&self.webhook_secret, payload, signature)?;
+        signature::parse_event(payload)
+    }
+}
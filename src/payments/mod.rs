@@ -0,0 +1,20 @@
+pub mod mock;
+pub mod provider;
+pub mod signature;
+pub mod stripe;
+
+use std::sync::Arc;
+
+use crate::config::settings::{PaymentProviderKind, PaymentSettings};
+
+use self::{mock::MockPaymentProvider, provider::PaymentProvider, stripe::StripePaymentProvider};
+
+pub fn build_provider(settings: &PaymentSettings) -> Arc<dyn PaymentProvider> {
+    match settings.provider {
+        PaymentProviderKind::Stripe => Arc::new(StripePaymentProvider::new(settings)),
+        PaymentProviderKind::Mock => {
+            tracing::warn!("using the mock payment provider, no real payments will be taken");
+            Arc::new(MockPaymentProvider::new(settings))
+        }
+    }
+}
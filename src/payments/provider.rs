@@ -0,0 +1,68 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{errors::app_error::AppError, models::order_model::Order};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaymentIntent {
+    pub id: String,
+    pub client_secret: Option<String>,
+    /// Amount in the currency's minor unit, e.g. cents.
+    pub amount: i64,
+    pub currency: String,
+    pub status: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Refund {
+    pub id: String,
+    pub payment_intent: String,
+    pub amount: i64,
+    pub status: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebhookEventKind {
+    /// The customer confirmed, the amount is held until it is captured.
+    PaymentAuthorized,
+    PaymentSucceeded,
+    PaymentFailed,
+    RefundSucceeded,
+    Other(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct WebhookEvent {
+    pub id: String,
+    pub kind: WebhookEventKind,
+    pub payment_intent_id: Option<String>,
+    pub order_id: Option<String>,
+    /// For refund events, everything refunded on the charge so far.
+    pub amount_refunded: Option<i64>,
+}
+
+/// A payment gateway. Implementations are held in `AppState` so handlers
+/// never depend on a concrete provider.
+#[async_trait]
+pub trait PaymentProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn create_intent(&self, order: &Order, currency: &str)
+        -> Result<PaymentIntent, AppError>;
+
+    /// Fetches an existing intent, client secret included.
+    async fn retrieve(&self, intent_id: &str) -> Result<PaymentIntent, AppError>;
+
+    async fn capture(&self, intent_id: &str) -> Result<PaymentIntent, AppError>;
+
+    /// Refunds `amount` minor units, or everything that was captured when
+    /// `amount` is `None`.
+    async fn refund(&self, intent_id: &str, amount: Option<i64>) -> Result<Refund, AppError>;
+
+    /// Checks the signature header of a webhook delivery and parses its body.
+    fn verify_webhook(&self, payload: &[u8], signature: &str) -> Result<WebhookEvent, AppError>;
+}
+
+pub fn order_amount(order: &Order) -> i64 {
+    (order.total_price as f64 * 100.0).round() as i64
+}
@@ -0,0 +1,238 @@
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+
+use crate::errors::app_error::AppError;
+
+use super::provider::{WebhookEvent, WebhookEventKind};
+
+/// How old a signed webhook may be before it is rejected as a replay.
+const TOLERANCE_SECONDS: i64 = 300;
+
+fn mac(secret: &str, timestamp: i64, payload: &[u8]) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts keys of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(payload);
+    mac
+}
+
+/// Checks a `t=<unix time>,v1=<hex hmac>` header, the format Stripe uses.
+pub fn verify(secret: &str, payload: &[u8], header: &str) -> Result<(), AppError> {
+    let mut timestamp = None;
+    let mut signatures = vec![];
+
+    for part in header.split(',') {
+        match part.trim().split_once('=') {
+            Some(("t", value)) => timestamp = value.parse::<i64>().ok(),
+            Some(("v1", value)) => signatures.push(value),
+            _ => {}
+        }
+    }
+
+    let timestamp = timestamp.ok_or(AppError::InvalidSignature)?;
+
+    if (Utc::now().timestamp() - timestamp).abs() > TOLERANCE_SECONDS {
+        return Err(AppError::InvalidSignature);
+    }
+
+    let valid = signatures
+        .iter()
+        .any(|signature| match hex::decode(signature) {
+            // verify_slice compares in constant time
+            Ok(bytes) => mac(secret, timestamp, payload).verify_slice(&bytes).is_ok(),
+            Err(_) => false,
+        });
+
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::InvalidSignature)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RawEvent {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    data: RawEventData,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawEventData {
+    object: RawEventObject,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawEventObject {
+    id: String,
+    object: Option<String>,
+    payment_intent: Option<String>,
+    amount_refunded: Option<i64>,
+    #[serde(default)]
+    metadata: std::collections::HashMap<String, String>,
+}
+
+/// Parses a Stripe-shaped event body.
+pub fn parse_event(payload: &[u8]) -> Result<WebhookEvent, AppError> {
+    let raw: RawEvent = serde_json::from_slice(payload)
+        .map_err(|_| AppError::BadRequest("webhook body is not a valid event".to_string()))?;
+
+    let kind = match raw.kind.as_str() {
+        "payment_intent.amount_capturable_updated" => WebhookEventKind::PaymentAuthorized,
+        "payment_intent.succeeded" => WebhookEventKind::PaymentSucceeded,
+        "payment_intent.payment_failed" => WebhookEventKind::PaymentFailed,
+        "charge.refunded" => WebhookEventKind::RefundSucceeded,
+        other => WebhookEventKind::Other(other.to_string()),
+    };
+
+    let object = raw.data.object;
+    let payment_intent_id = match object.object.as_deref() {
+        Some("payment_intent") => Some(object.id),
+        _ => object.payment_intent,
+    };
+
+    Ok(WebhookEvent {
+        id: raw.id,
+        kind,
+        payment_intent_id,
+        order_id: object.metadata.get("order_id").cloned(),
+        amount_refunded: object.amount_refunded,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "whsec_test";
+    const PAYLOAD: &[u8] = br#"{"id":"evt_1","type":"payment_intent.succeeded"}"#;
+
+    fn sign(secret: &str, timestamp: i64, payload: &[u8]) -> String {
+        hex::encode(mac(secret, timestamp, payload).finalize().into_bytes())
+    }
+
+    #[test]
+    fn verifies_signature_header() {
+        let now = Utc::now().timestamp();
+        let valid = sign(SECRET, now, PAYLOAD);
+        let stale = now - TOLERANCE_SECONDS - 1;
+        let future = now + TOLERANCE_SECONDS + 1;
+
+        let cases = [
+            ("valid", format!("t={},v1={}", now, valid), PAYLOAD, true),
+            (
+                "one of several signatures",
+                format!("t={},v1={},v1={}", now, "00".repeat(32), valid),
+                PAYLOAD,
+                true,
+            ),
+            (
+                "spaces around parts",
+                format!("t={}, v1={}", now, valid),
+                PAYLOAD,
+                true,
+            ),
+            (
+                "other secret",
+                format!("t={},v1={}", now, sign("whsec_other", now, PAYLOAD)),
+                PAYLOAD,
+                false,
+            ),
+            (
+                "tampered payload",
+                format!("t={},v1={}", now, valid),
+                &br#"{"id":"evt_2","type":"payment_intent.succeeded"}"#[..],
+                false,
+            ),
+            (
+                "timestamp not signed",
+                format!("t={},v1={}", now + 1, valid),
+                PAYLOAD,
+                false,
+            ),
+            (
+                "replayed",
+                format!("t={},v1={}", stale, sign(SECRET, stale, PAYLOAD)),
+                PAYLOAD,
+                false,
+            ),
+            (
+                "from the future",
+                format!("t={},v1={}", future, sign(SECRET, future, PAYLOAD)),
+                PAYLOAD,
+                false,
+            ),
+            ("no timestamp", format!("v1={}", valid), PAYLOAD, false),
+            ("no signature", format!("t={}", now), PAYLOAD, false),
+            (
+                "not hex",
+                format!("t={},v1=zz{}", now, valid),
+                PAYLOAD,
+                false,
+            ),
+            ("v0 only", format!("t={},v0={}", now, valid), PAYLOAD, false),
+            ("empty", String::new(), PAYLOAD, false),
+        ];
+
+        for (name, header, payload, expected) in cases {
+            assert_eq!(
+                verify(SECRET, payload, &header).is_ok(),
+                expected,
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn parses_events() {
+        let cases = [
+            (
+                r#"{"id":"evt_1","type":"payment_intent.succeeded","data":{"object":
+                    {"id":"pi_1","object":"payment_intent","metadata":{"order_id":"abc"}}}}"#,
+                WebhookEventKind::PaymentSucceeded,
+                Some("pi_1"),
+                Some("abc"),
+                None,
+            ),
+            (
+                r#"{"id":"evt_2","type":"payment_intent.amount_capturable_updated","data":
+                    {"object":{"id":"pi_2","object":"payment_intent"}}}"#,
+                WebhookEventKind::PaymentAuthorized,
+                Some("pi_2"),
+                None,
+                None,
+            ),
+            (
+                r#"{"id":"evt_3","type":"charge.refunded","data":{"object":{"id":"ch_1",
+                    "object":"charge","payment_intent":"pi_3","amount_refunded":500}}}"#,
+                WebhookEventKind::RefundSucceeded,
+                Some("pi_3"),
+                None,
+                Some(500),
+            ),
+            (
+                r#"{"id":"evt_4","type":"customer.created","data":{"object":
+                    {"id":"cus_1","object":"customer"}}}"#,
+                WebhookEventKind::Other("customer.created".to_string()),
+                None,
+                None,
+                None,
+            ),
+        ];
+
+        for (payload, kind, intent_id, order_id, amount_refunded) in cases {
+            let event = parse_event(payload.as_bytes()).unwrap();
+            assert_eq!(event.kind, kind);
+            assert_eq!(event.payment_intent_id.as_deref(), intent_id);
+            assert_eq!(event.order_id.as_deref(), order_id);
+            assert_eq!(event.amount_refunded, amount_refunded);
+        }
+
+        assert!(parse_event(b"not json").is_err());
+    }
+}
@@ -0,0 +1,127 @@
+use async_trait::async_trait;
+use reqwest::{Client, RequestBuilder};
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{
+    config::settings::PaymentSettings, errors::app_error::AppError, models::order_model::Order,
+};
+
+use super::{
+    provider::{order_amount, PaymentIntent, PaymentProvider, Refund, WebhookEvent},
+    signature,
+};
+
+/// Talks to the Stripe REST API, or anything that speaks the same protocol
+/// at `STRIPE_API_BASE`.
+pub struct StripePaymentProvider {
+    client: Client,
+    api_base: String,
+    secret_key: String,
+    webhook_secret: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct StripeErrorBody {
+    error: StripeError,
+}
+
+#[derive(Debug, Deserialize)]
+struct StripeError {
+    message: Option<String>,
+}
+
+impl StripePaymentProvider {
+    pub fn new(settings: &PaymentSettings) -> Self {
+        StripePaymentProvider {
+            client: Client::new(),
+            api_base: settings.stripe_api_base.trim_end_matches('/').to_string(),
+            secret_key: settings.stripe_secret_key.clone(),
+            webhook_secret: settings.webhook_secret.clone(),
+        }
+    }
+
+    fn get(&self, path: &str) -> RequestBuilder {
+        self.client
+            .get(format!("{}{}", self.api_base, path))
+            .bearer_auth(&self.secret_key)
+    }
+
+    fn post(&self, path: &str) -> RequestBuilder {
+        self.client
+            .post(format!("{}{}", self.api_base, path))
+            .bearer_auth(&self.secret_key)
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, AppError> {
+        let response = request.send().await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let message = response
+                .json::<StripeErrorBody>()
+                .await
+                .ok()
+                .and_then(|body| body.error.message)
+                .unwrap_or_else(|| status.to_string());
+            return Err(AppError::PaymentProvider(message));
+        }
+
+        Ok(response.json::<T>().await?)
+    }
+}
+
+#[async_trait]
+impl PaymentProvider for StripePaymentProvider {
+    fn name(&self) -> &'static str {
+        "stripe"
+    }
+
+    async fn create_intent(
+        &self,
+        order: &Order,
+        currency: &str,
+    ) -> Result<PaymentIntent, AppError> {
+        let order_id = order.id.to_hex();
+        let request = self
+            .post("/v1/payment_intents")
+            // retrying checkout for the same order must not create a second intent
+            .header("Idempotency-Key", format!("order-{}-intent", order_id))
+            // the card is only authorised here, an admin captures it from the order
+            .form(&[
+                ("amount", order_amount(order).to_string()),
+                ("currency", currency.to_string()),
+                ("capture_method", "manual".to_string()),
+                ("metadata[order_id]", order_id),
+            ]);
+
+        self.send(request).await
+    }
+
+    async fn retrieve(&self, intent_id: &str) -> Result<PaymentIntent, AppError> {
+        let request = self.get(&format!("/v1/payment_intents/{}", intent_id));
+
+        self.send(request).await
+    }
+
+    async fn capture(&self, intent_id: &str) -> Result<PaymentIntent, AppError> {
+        let request = self.post(&format!("/v1/payment_intents/{}/capture", intent_id));
+
+        self.send(request).await
+    }
+
+    async fn refund(&self, intent_id: &str, amount: Option<i64>) -> Result<Refund, AppError> {
+        let mut form = vec![("payment_intent", intent_id.to_string())];
+        if let Some(amount) = amount {
+            form.push(("amount", amount.to_string()));
+        }
+
+        let request = self.post("/v1/refunds").form(&form);
+
+        self.send(request).await
+    }
+
+    fn verify_webhook(&self, payload: &[u8], signature: &str) -> Result<WebhookEvent, AppError> {
+        signature::verify(&self.webhook_secret, payload, signature)?;
+        signature::parse_event(payload)
+    }
+}
@@ -0,0 +1,44 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+
+use crate::errors::app_error::AppError;
+
+use super::store::RateLimitStore;
+
+/// Counters for a single instance. Expired windows are dropped once the map
+/// grows past `PRUNE_THRESHOLD`, so memory stays bounded.
+#[derive(Default)]
+pub struct MemoryRateLimitStore {
+    windows: Mutex<HashMap<String, (Instant, u32)>>,
+}
+
+const PRUNE_THRESHOLD: usize = 10_000;
+
+#[async_trait]
+impl RateLimitStore for MemoryRateLimitStore {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn hit(&self, key: &str, window: Duration) -> Result<(u32, Duration), AppError> {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap();
+
+        if windows.len() > PRUNE_THRESHOLD {
+            windows.retain(|_, (resets_at, _)| *resets_at > now);
+        }
+
+        let entry = windows.entry(key.to_string()).or_insert((now + window, 0));
+        if entry.0 <= now {
+            *entry = (now + window, 0);
+        }
+        entry.1 += 1;
+
+        Ok((entry.1, entry.0 - now))
+    }
+}
@@ -0,0 +1,21 @@
+pub mod memory;
+pub mod mongo;
+pub mod store;
+
+use std::sync::Arc;
+
+use mongodb::Database;
+
+use crate::config::settings::{RateLimitSettings, RateLimitStoreKind};
+
+use self::{memory::MemoryRateLimitStore, mongo::MongoRateLimitStore, store::RateLimitStore};
+
+pub fn build_rate_limit_store(
+    settings: &RateLimitSettings,
+    db: &Database,
+) -> Arc<dyn RateLimitStore> {
+    match settings.store {
+        RateLimitStoreKind::Memory => Arc::new(MemoryRateLimitStore::default()),
+        RateLimitStoreKind::Mongo => Arc::new(MongoRateLimitStore::new(db)),
+    }
+}
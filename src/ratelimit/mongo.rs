@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use mongodb::{
+    bson::{doc, DateTime, Document},
+    options::ReturnDocument,
+    Collection, Database,
+};
+
+use crate::{database::mongo::is_duplicate_key_error, errors::app_error::AppError};
+
+use super::store::RateLimitStore;
+
+/// Counters shared by every instance through the `rate_limits` collection.
+/// A TTL index on `resets_at` removes windows that have run out.
+pub struct MongoRateLimitStore {
+    collection: Collection<Document>,
+}
+
+impl MongoRateLimitStore {
+    pub fn new(db: &Database) -> Self {
+        MongoRateLimitStore {
+            collection: db.collection("rate_limits"),
+        }
+    }
+
+    async fn try_hit(&self, key: &str, window: Duration) -> mongodb::error::Result<Document> {
+        let now = DateTime::now();
+        let next_reset = DateTime::from_millis(now.timestamp_millis() + window.as_millis() as i64);
+        let running = doc! {"$gt": ["$resets_at", now]};
+
+        // a single pipeline update so concurrent hits never lose a count
+        let update = vec![doc! {"$set": {
+            "count": {"$cond": [&running, {"$add": ["$count", 1]}, 1]},
+            "resets_at": {"$cond": [&running, "$resets_at", next_reset]},
+        }}];
+
+        let counter = self
+            .collection
+            .find_one_and_update(doc! {"_id": key}, update)
+            .upsert(true)
+            .return_document(ReturnDocument::After)
+            .await?;
+
+        Ok(counter.unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for MongoRateLimitStore {
+    fn name(&self) -> &'static str {
+        "mongo"
+    }
+
+    async fn hit(&self, key: &str, window: Duration) -> Result<(u32, Duration), AppError> {
+        // two first hits racing on the upsert, the loser simply retries
+        let counter = match self.try_hit(key, window).await {
+            Err(e) if is_duplicate_key_error(&e) => self.try_hit(key, window).await?,
+            result => result?,
+        };
+
+        let count = counter.get_i32("count").unwrap_or(1).max(1) as u32;
+        let reset_in = counter
+            .get_datetime("resets_at")
+            .map(|resets_at| resets_at.timestamp_millis() - DateTime::now().timestamp_millis())
+            .unwrap_or_default();
+
+        Ok((count, Duration::from_millis(reset_in.max(0) as u64)))
+    }
+}
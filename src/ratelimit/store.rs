@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::errors::app_error::AppError;
+
+/// Fixed window counters keyed by whatever is being limited, e.g.
+/// `login:ip:203.0.113.7` or `forgot-password:account:jane@example.com`.
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Counts one hit against `key`. A new window of `window` starts when
+    /// the previous one has run out. Returns the hits in the current window
+    /// and the time left until it resets.
+    async fn hit(&self, key: &str, window: Duration) -> Result<(u32, Duration), AppError>;
+}
+
+/// Counts a hit and fails with `TooManyRequests` once more than `max` hits
+/// were made within `window`.
+pub async fn enforce(
+    store: &dyn RateLimitStore,
+    key: &str,
+    max: u32,
+    window: Duration,
+) -> Result<(), AppError> {
+    let (count, reset_in) = store.hit(key, window).await?;
+
+    if count > max {
+        tracing::warn!("rate limit exceeded for {}", key);
+        return Err(AppError::TooManyRequests(reset_in.as_secs().max(1)));
+    }
+    Ok(())
+}
@@ -0,0 +1,19 @@
+use std::sync::Arc;
+
+use axum::routing::{delete, get};
+use axum::{middleware, Router};
+
+use crate::config::app_state::AppState;
+use crate::middlewares::permission_middleware::require_permission;
+use crate::models::role_model::Permission;
+use crate::services::api_key_service::*;
+
+pub fn api_key_route(app_state: &Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::<Arc<AppState>>::new()
+        .route("/", get(list_api_keys).post(create_api_key))
+        .route("/{id}", delete(revoke_api_key))
+        .layer(middleware::from_fn_with_state(
+            (app_state.clone(), Permission::ApiKeyManage),
+            require_permission,
+        ))
+}
@@ -1,21 +1,44 @@
 use std::sync::Arc;
 
-use axum::Router;
+use axum::{routing::get, Router};
 use axum_cookie::CookieLayer;
+use tower_http::services::ServeDir;
 
-use crate::config::app_state::AppState;
+use crate::config::{app_state::AppState, settings::StorageBackendKind};
+use crate::services::auth_service::jwks;
+use crate::storage::LOCAL_STORAGE_ROUTE;
 
 use super::{
-    auth_route::auth_route, cart_route::cart_route, product_route::product_route,
+    api_key_route::api_key_route, auth_route::auth_route, brand_route::brand_route,
+    cart_route::cart_route, category_route::category_route, inventory_route::inventory_route,
+    mail_route::mail_route, order_route::order_route, payment_route::payment_route,
+    product_route::product_route, review_route::review_route, role_route::role_route,
     user_route::user_routes,
 };
 
 pub fn app(app_state: Arc<AppState>) -> Router {
-    Router::new()
-        .nest("/api/user", user_routes())
+    let mut router = Router::new()
+        .route("/.well-known/jwks.json", get(jwks))
+        .nest("/api/user", user_routes(&app_state))
         .nest("/api/auth", auth_route(&app_state))
         .nest("/api/product", product_route(&app_state))
+        .nest("/api/categories", category_route(&app_state))
+        .nest("/api/brands", brand_route(&app_state))
+        .nest("/api/reviews", review_route(&app_state))
         .nest("/api/cart", cart_route(&app_state))
-        .with_state(app_state)
-        .layer(CookieLayer::strict())
+        .nest("/api/inventory", inventory_route(&app_state))
+        .nest("/api/mail", mail_route(&app_state))
+        .nest("/api/order", order_route(&app_state))
+        .nest("/api/payment", payment_route(&app_state))
+        .nest("/api/roles", role_route(&app_state))
+        .nest("/api/api-keys", api_key_route(&app_state));
+
+    if app_state.settings.storage.backend == StorageBackendKind::Local {
+        router = router.nest_service(
+            LOCAL_STORAGE_ROUTE,
+            ServeDir::new(&app_state.settings.storage.local.root),
+        );
+    }
+
+    router.with_state(app_state).layer(CookieLayer::strict())
 }
@@ -3,15 +3,53 @@ use axum::{middleware, Router};
 use std::sync::Arc;
 
 use crate::middlewares::auth_middleware::validate_user;
-use crate::services::auth_service::logout;
+use crate::middlewares::rate_limit_middleware::{rate_limit, RateLimit};
+use crate::services::auth_service::{
+    forgot_password, login_two_factor, logout, refresh, reset_password,
+};
+use crate::services::two_factor_service::{
+    confirm_two_factor, disable_two_factor, regenerate_recovery_codes, setup_two_factor,
+};
 use crate::{
     config::app_state::AppState,
     services::auth_service::{login, me},
 };
 
+// both login steps draw from the same budget
+const LOGIN_LIMIT: RateLimit = RateLimit::per_minutes("login", 20, 15);
+const REFRESH_LIMIT: RateLimit = RateLimit::per_minutes("refresh", 30, 1);
+const FORGOT_PASSWORD_LIMIT: RateLimit = RateLimit::per_minutes("forgot-password", 5, 15);
+const RESET_PASSWORD_LIMIT: RateLimit = RateLimit::per_minutes("reset-password", 10, 15);
+
 pub fn auth_route(app_state: &Arc<AppState>) -> Router<Arc<AppState>> {
+    let limit =
+        |limit: RateLimit| middleware::from_fn_with_state((app_state.clone(), limit), rate_limit);
+
+    let two_factor_routes = Router::<Arc<AppState>>::new()
+        .route("/2fa/setup", post(setup_two_factor))
+        .route("/2fa/confirm", post(confirm_two_factor))
+        .route("/2fa/disable", post(disable_two_factor))
+        .route("/2fa/recovery-codes", post(regenerate_recovery_codes))
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            validate_user,
+        ));
+
     Router::<Arc<AppState>>::new()
-        .route("/login", post(login))
+        .route("/login", post(login).layer(limit(LOGIN_LIMIT)))
+        .route(
+            "/login/2fa",
+            post(login_two_factor).layer(limit(LOGIN_LIMIT)),
+        )
+        .route("/refresh", post(refresh).layer(limit(REFRESH_LIMIT)))
+        .route(
+            "/forgot-password",
+            post(forgot_password).layer(limit(FORGOT_PASSWORD_LIMIT)),
+        )
+        .route(
+            "/reset-password",
+            post(reset_password).layer(limit(RESET_PASSWORD_LIMIT)),
+        )
         .route(
             "/me",
             get(me).layer(middleware::from_fn_with_state(
@@ -26,4 +64,5 @@ pub fn auth_route(app_state: &Arc<AppState>) -> Router<Arc<AppState>> {
                 validate_user,
             )),
         )
+        .merge(two_factor_routes)
 }
@@ -0,0 +1,31 @@
+use std::sync::Arc;
+
+use axum::routing::{get, post, put};
+use axum::{middleware, Router};
+
+use crate::config::app_state::AppState;
+use crate::middlewares::permission_middleware::require_permission;
+use crate::models::role_model::Permission;
+use crate::services::brand_service::*;
+
+pub fn brand_route(app_state: &Arc<AppState>) -> Router<Arc<AppState>> {
+    let write = || {
+        middleware::from_fn_with_state(
+            (app_state.clone(), Permission::ProductWrite),
+            require_permission,
+        )
+    };
+
+    Router::<Arc<AppState>>::new()
+        .route(
+            "/",
+            get(list_brands).merge(post(create_brand).layer(write())),
+        )
+        .route(
+            "/{id}",
+            put(update_brand)
+                .patch(update_brand)
+                .delete(delete_brand)
+                .layer(write()),
+        )
+}
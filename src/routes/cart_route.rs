@@ -2,14 +2,19 @@ use std::sync::Arc;
 
 use axum::{middleware, Router};
 
-use axum::routing::post;
+use axum::routing::{get, patch, post};
 
 use crate::middlewares::auth_middleware::validate_user;
 use crate::{config::app_state::AppState, services::cart_service::*};
 
 pub fn cart_route(app_state: &Arc<AppState>) -> Router<Arc<AppState>> {
     Router::<Arc<AppState>>::new()
+        .route("/", get(get_cart).delete(clear_cart))
         .route("/create", post(add_to_cart))
+        .route(
+            "/items/{product_id}",
+            patch(update_cart_item).delete(remove_cart_item),
+        )
         .layer(middleware::from_fn_with_state(
             app_state.clone(),
             validate_user,
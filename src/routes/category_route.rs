@@ -0,0 +1,32 @@
+use std::sync::Arc;
+
+use axum::routing::{get, post, put};
+use axum::{middleware, Router};
+
+use crate::config::app_state::AppState;
+use crate::middlewares::permission_middleware::require_permission;
+use crate::models::role_model::Permission;
+use crate::services::category_service::*;
+
+pub fn category_route(app_state: &Arc<AppState>) -> Router<Arc<AppState>> {
+    let write = || {
+        middleware::from_fn_with_state(
+            (app_state.clone(), Permission::ProductWrite),
+            require_permission,
+        )
+    };
+
+    Router::<Arc<AppState>>::new()
+        .route(
+            "/",
+            get(list_categories).merge(post(create_category).layer(write())),
+        )
+        .route("/tree", get(category_tree))
+        .route(
+            "/{id}",
+            put(update_category)
+                .patch(update_category)
+                .delete(delete_category)
+                .layer(write()),
+        )
+}
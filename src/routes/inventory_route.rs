@@ -0,0 +1,26 @@
+use std::sync::Arc;
+
+use axum::routing::{get, post};
+use axum::{middleware, Router};
+
+use crate::config::app_state::AppState;
+use crate::middlewares::permission_middleware::require_permission;
+use crate::models::role_model::Permission;
+use crate::services::inventory_service::*;
+
+pub fn inventory_route(app_state: &Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::<Arc<AppState>>::new()
+        .route("/{product_id}", get(get_stock))
+        .route("/{product_id}/movements", get(get_stock_movements))
+        .layer(middleware::from_fn_with_state(
+            (app_state.clone(), Permission::InventoryRead),
+            require_permission,
+        ))
+        .route(
+            "/{product_id}/adjust",
+            post(adjust_stock).layer(middleware::from_fn_with_state(
+                (app_state.clone(), Permission::InventoryWrite),
+                require_permission,
+            )),
+        )
+}
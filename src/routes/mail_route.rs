@@ -0,0 +1,22 @@
+use std::sync::Arc;
+
+use axum::routing::{get, post};
+use axum::{middleware, Router};
+
+use crate::config::app_state::AppState;
+use crate::middlewares::permission_middleware::require_permission;
+use crate::models::role_model::Permission;
+use crate::services::mail_service::*;
+
+pub fn mail_route(app_state: &Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::<Arc<AppState>>::new()
+        .route("/outbox", get(get_outbox))
+        .route("/outbox/{id}/retry", post(retry_outbox_email))
+        .route("/captured", get(get_captured_mail))
+        .route("/templates", get(list_templates))
+        .route("/templates/{name}/preview", get(preview_template))
+        .layer(middleware::from_fn_with_state(
+            (app_state.clone(), Permission::MailManage),
+            require_permission,
+        ))
+}
@@ -1,5 +1,14 @@
+pub mod api_key_route;
 pub mod app;
 pub mod auth_route;
+pub mod brand_route;
 pub mod cart_route;
+pub mod category_route;
+pub mod inventory_route;
+pub mod mail_route;
+pub mod order_route;
+pub mod payment_route;
 pub mod product_route;
+pub mod review_route;
+pub mod role_route;
 pub mod user_route;
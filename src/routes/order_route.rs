@@ -0,0 +1,40 @@
+use std::sync::Arc;
+
+use axum::routing::{get, patch, post};
+use axum::{middleware, Router};
+
+use crate::config::app_state::AppState;
+use crate::middlewares::{
+    auth_middleware::validate_user, permission_middleware::require_permission,
+};
+use crate::models::role_model::Permission;
+use crate::services::order_service::*;
+
+pub fn order_route(app_state: &Arc<AppState>) -> Router<Arc<AppState>> {
+    let admin_routes = Router::<Arc<AppState>>::new()
+        .route(
+            "/admin/all",
+            get(get_all_orders).layer(middleware::from_fn_with_state(
+                (app_state.clone(), Permission::OrderRead),
+                require_permission,
+            )),
+        )
+        .route(
+            "/{id}/status",
+            patch(update_order_status).layer(middleware::from_fn_with_state(
+                (app_state.clone(), Permission::OrderWrite),
+                require_permission,
+            )),
+        );
+
+    Router::<Arc<AppState>>::new()
+        .route("/", get(get_my_orders))
+        .route("/checkout", post(checkout))
+        .route("/{id}", get(get_my_order))
+        .route("/{id}/cancel", post(cancel_order))
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            validate_user,
+        ))
+        .merge(admin_routes)
+}
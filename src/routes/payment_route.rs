@@ -0,0 +1,41 @@
+use std::sync::Arc;
+
+use axum::routing::post;
+use axum::{middleware, Router};
+
+use crate::config::app_state::AppState;
+use crate::middlewares::{
+    auth_middleware::validate_user, permission_middleware::require_permission,
+};
+use crate::models::role_model::Permission;
+use crate::services::payment_service::*;
+
+pub fn payment_route(app_state: &Arc<AppState>) -> Router<Arc<AppState>> {
+    let user_routes = Router::<Arc<AppState>>::new()
+        .route("/orders/{id}/intent", post(create_payment_intent))
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            validate_user,
+        ));
+
+    let admin_routes = Router::<Arc<AppState>>::new()
+        .route(
+            "/orders/{id}/capture",
+            post(capture_payment).layer(middleware::from_fn_with_state(
+                (app_state.clone(), Permission::PaymentCapture),
+                require_permission,
+            )),
+        )
+        .route(
+            "/orders/{id}/refund",
+            post(refund_payment).layer(middleware::from_fn_with_state(
+                (app_state.clone(), Permission::OrderRefund),
+                require_permission,
+            )),
+        );
+
+    Router::<Arc<AppState>>::new()
+        .route("/webhook", post(payment_webhook))
+        .merge(user_routes)
+        .merge(admin_routes)
+}
@@ -1,23 +1,36 @@
 use std::sync::Arc;
 
 use axum::extract::DefaultBodyLimit;
-use axum::routing::{get, post, put};
+use axum::routing::{delete, get, post, put};
 use axum::{middleware, Router};
 use tower_http::limit::RequestBodyLimitLayer;
 
 use crate::config::app_state::AppState;
-use crate::middlewares::admin_middleware::is_admin;
+use crate::middlewares::permission_middleware::require_permission;
+use crate::models::role_model::Permission;
 use crate::services::product_service::*;
 
 pub fn product_route(app_state: &Arc<AppState>) -> Router<Arc<AppState>> {
-    Router::<Arc<AppState>>::new()
+    let admin_routes = Router::<Arc<AppState>>::new()
         .route("/create", post(create_products))
-        .layer(middleware::from_fn_with_state(app_state.clone(), is_admin))
         .route("/image/{id}", put(upload_product_image))
-        .layer(middleware::from_fn_with_state(app_state.clone(), is_admin))
         .layer(DefaultBodyLimit::disable())
         .layer(RequestBodyLimitLayer::new(15 * 1024 * 1024))
         .route("/all", get(get_all_products))
-        .layer(middleware::from_fn_with_state(app_state.clone(), is_admin))
+        .route(
+            "/{id}",
+            put(update_product)
+                .patch(update_product)
+                .delete(delete_product),
+        )
+        .route("/{id}/image", delete(delete_product_image))
+        .route("/{id}/image/link", get(get_product_image_link))
+        .layer(middleware::from_fn_with_state(
+            (app_state.clone(), Permission::ProductWrite),
+            require_permission,
+        ));
+
+    Router::<Arc<AppState>>::new()
         .route("/filter", get(filter_products))
+        .merge(admin_routes)
 }
@@ -0,0 +1,43 @@
+use std::sync::Arc;
+
+use axum::routing::{get, post, put};
+use axum::{middleware, Router};
+
+use crate::config::app_state::AppState;
+use crate::middlewares::{
+    auth_middleware::validate_user, permission_middleware::require_permission,
+};
+use crate::models::role_model::Permission;
+use crate::services::review_service::*;
+
+pub fn review_route(app_state: &Arc<AppState>) -> Router<Arc<AppState>> {
+    let signed_in = || middleware::from_fn_with_state(app_state.clone(), validate_user);
+
+    let moderation_routes = Router::<Arc<AppState>>::new()
+        .route("/moderation", get(list_reviews_for_moderation))
+        .route("/{id}/moderation", put(moderate_review))
+        .layer(middleware::from_fn_with_state(
+            (app_state.clone(), Permission::ReviewModerate),
+            require_permission,
+        ));
+
+    Router::<Arc<AppState>>::new()
+        .route(
+            "/product/{id}",
+            get(list_product_reviews).merge(post(create_review).layer(signed_in())),
+        )
+        .route(
+            "/{id}",
+            put(update_my_review)
+                .patch(update_my_review)
+                .delete(delete_my_review)
+                .layer(signed_in()),
+        )
+        .route(
+            "/{id}/helpful",
+            post(vote_helpful)
+                .delete(remove_helpful_vote)
+                .layer(signed_in()),
+        )
+        .merge(moderation_routes)
+}
@@ -0,0 +1,21 @@
+use std::sync::Arc;
+
+use axum::routing::{get, put};
+use axum::{middleware, Router};
+
+use crate::config::app_state::AppState;
+use crate::middlewares::permission_middleware::require_permission;
+use crate::models::role_model::Permission;
+use crate::services::role_service::*;
+
+pub fn role_route(app_state: &Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::<Arc<AppState>>::new()
+        .route("/", get(list_roles).post(create_role))
+        .route("/permissions", get(list_permissions))
+        .route("/{name}", put(update_role).delete(delete_role))
+        .route("/users/{id}", put(assign_role))
+        .layer(middleware::from_fn_with_state(
+            (app_state.clone(), Permission::RoleManage),
+            require_permission,
+        ))
+}
@@ -1,23 +1,79 @@
 use std::sync::Arc;
 
-use crate::{config::app_state::AppState, services::user_service::*};
+use crate::{
+    config::app_state::AppState,
+    middlewares::{
+        auth_middleware::validate_user,
+        permission_middleware::require_permission,
+        rate_limit_middleware::{rate_limit, RateLimit},
+    },
+    models::role_model::Permission,
+    services::user_service::*,
+};
 use axum::{
     extract::DefaultBodyLimit,
-    routing::{get, post},
+    middleware,
+    routing::{get, post, put},
     Router,
 };
 use tower_http::limit::RequestBodyLimitLayer;
 
-pub fn user_routes() -> Router<Arc<AppState>> {
-    Router::<Arc<AppState>>::new()
-        .route("/register", post(register))
-        .route("/verify", post(verify))
+const REGISTER_LIMIT: RateLimit = RateLimit::per_minutes("register", 5, 15);
+const VERIFY_LIMIT: RateLimit = RateLimit::per_minutes("verify", 10, 15);
+const RESEND_OTP_LIMIT: RateLimit = RateLimit::per_minutes("resend-otp", 5, 15);
+
+pub fn user_routes(app_state: &Arc<AppState>) -> Router<Arc<AppState>> {
+    let limit =
+        |limit: RateLimit| middleware::from_fn_with_state((app_state.clone(), limit), rate_limit);
+
+    let self_routes = Router::<Arc<AppState>>::new()
         .route(
-            "/{id}",
-            get(get_user_by_id).delete(delete_user).put(update_user),
+            "/me",
+            get(get_me)
+                .put(update_me)
+                .patch(update_me)
+                .delete(delete_me),
         )
-        .route("/all", get(get_all_users))
         .route("/avatar", post(test_multipart))
         .layer(DefaultBodyLimit::disable())
         .layer(RequestBodyLimitLayer::new(10 * 1024 * 1024))
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            validate_user,
+        ));
+
+    let read = || {
+        middleware::from_fn_with_state(
+            (app_state.clone(), Permission::UserRead),
+            require_permission,
+        )
+    };
+    let write = || {
+        middleware::from_fn_with_state(
+            (app_state.clone(), Permission::UserWrite),
+            require_permission,
+        )
+    };
+
+    let admin_routes = Router::<Arc<AppState>>::new()
+        .route("/all", get(get_all_users).layer(read()))
+        .route(
+            "/{id}",
+            get(get_user_by_id).layer(read()).merge(
+                put(update_user)
+                    .patch(update_user)
+                    .delete(delete_user)
+                    .layer(write()),
+            ),
+        );
+
+    Router::<Arc<AppState>>::new()
+        .route("/register", post(register).layer(limit(REGISTER_LIMIT)))
+        .route("/verify", post(verify).layer(limit(VERIFY_LIMIT)))
+        .route(
+            "/resend-otp",
+            post(resend_otp).layer(limit(RESEND_OTP_LIMIT)),
+        )
+        .merge(self_routes)
+        .merge(admin_routes)
 }
@@ -0,0 +1,118 @@
+use async_trait::async_trait;
+use mongodb::{
+    bson::{doc, oid::ObjectId, Document},
+    Collection, Database,
+};
+
+use crate::{
+    config::settings::SearchSettings, errors::app_error::AppError, models::products_model::Products,
+};
+
+use super::backend::SearchBackend;
+
+const SEARCH_PATHS: [&str; 3] = ["title", "brand", "category"];
+
+/// Escapes the characters the `wildcard` operator gives a meaning to, so
+/// user text only ever matches itself.
+fn escape_wildcard(word: &str) -> String {
+    let mut escaped = String::with_capacity(word.len());
+    for c in word.chars() {
+        if matches!(c, '*' | '?' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Runs queries through an Atlas Search index on `products`. Atlas keeps the
+/// index in sync with the collection by itself, so writes are no-ops here.
+pub struct AtlasSearch {
+    products: Collection<Products>,
+    index: String,
+}
+
+impl AtlasSearch {
+    pub fn new(settings: &SearchSettings, db: &Database) -> Self {
+        AtlasSearch {
+            products: db.collection("products"),
+            index: settings.atlas_index.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl SearchBackend for AtlasSearch {
+    fn name(&self) -> &'static str {
+        "atlas"
+    }
+
+    async fn index(&self, _product: &Products) -> Result<(), AppError> {
+        Ok(())
+    }
+
+    async fn remove(&self, _product_id: ObjectId) -> Result<(), AppError> {
+        Ok(())
+    }
+
+    async fn search(&self, text: &str, limit: usize) -> Result<Vec<ObjectId>, AppError> {
+        let prefixes = text
+            .split_whitespace()
+            .map(|word| format!("{}*", escape_wildcard(&word.to_lowercase())))
+            .collect::<Vec<String>>();
+
+        let pipeline = vec![
+            doc! {
+                "$search": {
+                    "index": &self.index,
+                    "compound": {
+                        "should": [
+                            {"text": {
+                                "query": text,
+                                "path": SEARCH_PATHS.to_vec(),
+                                "fuzzy": {"maxEdits": 1, "prefixLength": 1},
+                            }},
+                            {"wildcard": {
+                                "query": prefixes,
+                                "path": SEARCH_PATHS.to_vec(),
+                                "allowAnalyzedField": true,
+                            }},
+                        ],
+                        "minimumShouldMatch": 1,
+                    },
+                }
+            },
+            doc! {"$match": {"deleted_at": null}},
+            doc! {"$limit": limit as i64},
+            doc! {"$project": {"_id": 1}},
+        ];
+
+        let mut cursor = self.products.aggregate(pipeline).await?;
+
+        let mut ids = vec![];
+        while cursor.advance().await? {
+            let hit: Document = cursor.deserialize_current()?;
+            if let Ok(id) = hit.get_object_id("_id") {
+                ids.push(id);
+            }
+        }
+
+        Ok(ids)
+    }
+
+    async fn rebuild(&self, _db: &Database) -> Result<usize, AppError> {
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_wildcard_escapes_operator_characters() {
+        assert_eq!(escape_wildcard("shoe"), "shoe");
+        assert_eq!(escape_wildcard("a*b?c"), "a\\*b\\?c");
+        assert_eq!(escape_wildcard("back\\slash"), "back\\\\slash");
+    }
+}
@@ -0,0 +1,24 @@
+use async_trait::async_trait;
+use mongodb::{bson::oid::ObjectId, Database};
+
+use crate::{errors::app_error::AppError, models::products_model::Products};
+
+/// Full-text search over the catalogue. Backends only rank products, the
+/// documents themselves are always read from MongoDB.
+#[async_trait]
+pub trait SearchBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Adds `product` to the index or replaces its previous version.
+    async fn index(&self, product: &Products) -> Result<(), AppError>;
+
+    async fn remove(&self, product_id: ObjectId) -> Result<(), AppError>;
+
+    /// Ids of the products matching `text`, best match first. Prefixes and
+    /// small typos still match, e.g. `samsng gal` finds "Samsung Galaxy".
+    async fn search(&self, text: &str, limit: usize) -> Result<Vec<ObjectId>, AppError>;
+
+    /// Indexes every product that is not deleted, dropping whatever was
+    /// indexed before. Returns the number of products indexed.
+    async fn rebuild(&self, db: &Database) -> Result<usize, AppError>;
+}
@@ -0,0 +1,310 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use mongodb::{
+    bson::{doc, oid::ObjectId},
+    Collection, Database,
+};
+use tantivy::{
+    collector::TopDocs,
+    directory::MmapDirectory,
+    query::{BooleanQuery, BoostQuery, FuzzyTermQuery, Occur, Query, TermQuery},
+    schema::{
+        Field, IndexRecordOption, Schema, TextFieldIndexing, TextOptions, Value, STORED, STRING,
+    },
+    tokenizer::{AsciiFoldingFilter, LowerCaser, SimpleTokenizer, TextAnalyzer},
+    Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term,
+};
+
+use crate::{
+    config::settings::SearchSettings, errors::app_error::AppError, models::products_model::Products,
+};
+
+use super::backend::SearchBackend;
+
+const TOKENIZER: &str = "product";
+const WRITER_MEMORY_BYTES: usize = 50_000_000;
+
+/// Title matches count the most, then brand and category.
+const FIELD_BOOSTS: [(&str, f32); 3] = [("title", 3.0), ("brand", 2.0), ("category", 2.0)];
+
+/// Exact words rank above prefixes, prefixes above words with typos.
+const EXACT_BOOST: f32 = 3.0;
+const PREFIX_BOOST: f32 = 2.0;
+/// Every word adds a fuzzy clause per field, words past this are ignored.
+const MAX_QUERY_WORDS: usize = 8;
+
+struct Fields {
+    id: Field,
+    text: Vec<(Field, f32)>,
+}
+
+struct Inner {
+    index: Index,
+    reader: IndexReader,
+    writer: Mutex<IndexWriter>,
+    fields: Fields,
+}
+
+/// A Tantivy index kept inside the process, for deployments without Atlas.
+/// It is rebuilt from MongoDB at startup and updated by the product
+/// handlers afterwards.
+pub struct EmbeddedSearch {
+    inner: Arc<Inner>,
+}
+
+/// Edits allowed for a word of `len` characters, short words have to match
+/// exactly or "cat" would also find "car" and "hat".
+fn allowed_typos(len: usize) -> u8 {
+    match len {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+impl EmbeddedSearch {
+    pub fn open(settings: &SearchSettings) -> Result<Self, String> {
+        let indexing = TextFieldIndexing::default()
+            .set_tokenizer(TOKENIZER)
+            .set_index_option(IndexRecordOption::WithFreqsAndPositions);
+        let text_options = TextOptions::default().set_indexing_options(indexing);
+
+        let mut builder = Schema::builder();
+        let id = builder.add_text_field("id", STRING | STORED);
+        let text = FIELD_BOOSTS
+            .iter()
+            .map(|(name, boost)| (builder.add_text_field(name, text_options.clone()), *boost))
+            .collect();
+        let schema = builder.build();
+
+        let index = match &settings.index_dir {
+            Some(dir) => {
+                std::fs::create_dir_all(dir)
+                    .map_err(|e| format!("failed to create {}: {}", dir, e))?;
+                let directory = MmapDirectory::open(Path::new(dir))
+                    .map_err(|e| format!("failed to open {}: {}", dir, e))?;
+                Index::open_or_create(directory, schema)
+                    .map_err(|e| format!("failed to open the search index in {}: {}", dir, e))?
+            }
+            None => Index::create_in_ram(schema),
+        };
+
+        // lowercased and without accents, so "Café" and "cafe" are the same word
+        index.tokenizers().register(
+            TOKENIZER,
+            TextAnalyzer::builder(SimpleTokenizer::default())
+                .filter(LowerCaser)
+                .filter(AsciiFoldingFilter)
+                .build(),
+        );
+
+        let writer = index
+            .writer(WRITER_MEMORY_BYTES)
+            .map_err(|e| format!("failed to open the search index writer: {}", e))?;
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()
+            .map_err(|e| format!("failed to open the search index reader: {}", e))?;
+
+        Ok(EmbeddedSearch {
+            inner: Arc::new(Inner {
+                index,
+                reader,
+                writer: Mutex::new(writer),
+                fields: Fields { id, text },
+            }),
+        })
+    }
+}
+
+impl Inner {
+    fn document(&self, product: &Products, id: ObjectId) -> TantivyDocument {
+        let mut document = TantivyDocument::default();
+        document.add_text(self.fields.id, id.to_hex());
+
+        for ((field, _), value) in
+            self.fields
+                .text
+                .iter()
+                .zip([&product.title, &product.brand, &product.category])
+        {
+            document.add_text(*field, value);
+        }
+        document
+    }
+
+    fn id_term(&self, id: ObjectId) -> Term {
+        Term::from_field_text(self.fields.id, &id.to_hex())
+    }
+
+    /// Applies `change` and commits it, then makes it visible to searches.
+    fn write(
+        &self,
+        change: impl FnOnce(&mut IndexWriter) -> tantivy::Result<()>,
+    ) -> Result<(), AppError> {
+        let mut writer = self
+            .writer
+            .lock()
+            .map_err(|_| AppError::Search("search index writer is poisoned".to_string()))?;
+
+        if let Err(e) = change(&mut writer).and_then(|_| writer.commit().map(|_| ())) {
+            let _ = writer.rollback();
+            return Err(e.into());
+        }
+        self.reader.reload()?;
+        Ok(())
+    }
+
+    fn words(&self, text: &str) -> Vec<String> {
+        let mut words = vec![];
+        if let Some(mut analyzer) = self.index.tokenizers().get(TOKENIZER) {
+            let mut stream = analyzer.token_stream(text);
+            while stream.advance() {
+                words.push(stream.token().text.clone());
+            }
+        }
+        words
+    }
+
+    fn query(&self, text: &str) -> BooleanQuery {
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = vec![];
+
+        for word in self.words(text).into_iter().take(MAX_QUERY_WORDS) {
+            for (field, boost) in &self.fields.text {
+                let term = Term::from_field_text(*field, &word);
+
+                clauses.push((
+                    Occur::Should,
+                    Box::new(BoostQuery::new(
+                        Box::new(TermQuery::new(term.clone(), IndexRecordOption::WithFreqs)),
+                        boost * EXACT_BOOST,
+                    )),
+                ));
+                clauses.push((
+                    Occur::Should,
+                    Box::new(BoostQuery::new(
+                        Box::new(FuzzyTermQuery::new_prefix(term.clone(), 0, true)),
+                        boost * PREFIX_BOOST,
+                    )),
+                ));
+
+                let typos = allowed_typos(word.chars().count());
+                if typos > 0 {
+                    clauses.push((
+                        Occur::Should,
+                        Box::new(BoostQuery::new(
+                            Box::new(FuzzyTermQuery::new(term, typos, true)),
+                            *boost,
+                        )),
+                    ));
+                }
+            }
+        }
+
+        BooleanQuery::new(clauses)
+    }
+
+    fn search(&self, text: &str, limit: usize) -> Result<Vec<ObjectId>, AppError> {
+        let query = self.query(text);
+        let searcher = self.reader.searcher();
+
+        let mut ids = vec![];
+        for (_score, address) in searcher.search(&query, &TopDocs::with_limit(limit))? {
+            let document: TantivyDocument = searcher.doc(address)?;
+            if let Some(id) = document
+                .get_first(self.fields.id)
+                .and_then(|value| value.as_str())
+                .and_then(|hex| ObjectId::parse_str(hex).ok())
+            {
+                ids.push(id);
+            }
+        }
+        Ok(ids)
+    }
+}
+
+/// Tantivy blocks on disk io and merges, so every call leaves the async
+/// runtime.
+async fn blocking<T: Send + 'static>(
+    inner: &Arc<Inner>,
+    work: impl FnOnce(&Inner) -> Result<T, AppError> + Send + 'static,
+) -> Result<T, AppError> {
+    let inner = inner.clone();
+    tokio::task::spawn_blocking(move || work(&inner))
+        .await
+        .map_err(|e| AppError::Search(e.to_string()))?
+}
+
+#[async_trait]
+impl SearchBackend for EmbeddedSearch {
+    fn name(&self) -> &'static str {
+        "embedded"
+    }
+
+    async fn index(&self, product: &Products) -> Result<(), AppError> {
+        let Some(id) = product._id else {
+            return Ok(());
+        };
+        let product = product.clone();
+
+        blocking(&self.inner, move |inner| {
+            let document = inner.document(&product, id);
+            let term = inner.id_term(id);
+            inner.write(|writer| {
+                writer.delete_term(term);
+                writer.add_document(document)?;
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn remove(&self, product_id: ObjectId) -> Result<(), AppError> {
+        blocking(&self.inner, move |inner| {
+            let term = inner.id_term(product_id);
+            inner.write(|writer| {
+                writer.delete_term(term);
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn search(&self, text: &str, limit: usize) -> Result<Vec<ObjectId>, AppError> {
+        let text = text.to_string();
+        blocking(&self.inner, move |inner| inner.search(&text, limit)).await
+    }
+
+    async fn rebuild(&self, db: &Database) -> Result<usize, AppError> {
+        let collection: Collection<Products> = db.collection("products");
+        let mut cursor = collection.find(doc! {"deleted_at": null}).await?;
+
+        let mut products = vec![];
+        while cursor.advance().await? {
+            products.push(cursor.deserialize_current()?);
+        }
+
+        blocking(&self.inner, move |inner| {
+            let documents = products
+                .iter()
+                .filter_map(|product: &Products| product._id.map(|id| inner.document(product, id)))
+                .collect::<Vec<TantivyDocument>>();
+            let count = documents.len();
+
+            inner.write(|writer| {
+                writer.delete_all_documents()?;
+                for document in documents {
+                    writer.add_document(document)?;
+                }
+                Ok(())
+            })?;
+            Ok(count)
+        })
+        .await
+    }
+}
@@ -0,0 +1,21 @@
+pub mod atlas;
+pub mod backend;
+pub mod embedded;
+
+use std::sync::Arc;
+
+use mongodb::Database;
+
+use crate::config::settings::{SearchBackendKind, SearchSettings};
+
+use self::{atlas::AtlasSearch, backend::SearchBackend, embedded::EmbeddedSearch};
+
+pub fn build_search(
+    settings: &SearchSettings,
+    db: &Database,
+) -> Result<Arc<dyn SearchBackend>, String> {
+    match settings.backend {
+        SearchBackendKind::Atlas => Ok(Arc::new(AtlasSearch::new(settings, db))),
+        SearchBackendKind::Embedded => Ok(Arc::new(EmbeddedSearch::open(settings)?)),
+    }
+}
@@ -0,0 +1,199 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use axum_macros::debug_handler;
+use chrono::{Duration, Utc};
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime},
+    Collection, Database,
+};
+
+use crate::{
+    config::app_state::AppState,
+    errors::app_error::{AppError, FieldError},
+    middlewares::auth_middleware::Credential,
+    models::{
+        api_key_model::{ApiKey, ApiKeyQuery, ApiKeyView, CreateApiKey, CreatedApiKey},
+        role_model::{Permission, DEFAULT_ROLE},
+        user_model::User,
+    },
+    services::cart_service::current_user_id,
+    utils::{
+        pagination::{find_page, Keyset, Page, PageRequest},
+        parse_id::parse_object_id,
+        token::{generate_token, hash_token},
+    },
+};
+
+/// Every API key starts with this, which is how a bearer token is told apart
+/// from a JWT.
+pub const API_KEY_PREFIX: &str = "ck_";
+const MAX_API_KEY_DAYS: i64 = 3650;
+
+fn api_keys(db: &Database) -> Collection<ApiKey> {
+    db.collection("api_keys")
+}
+
+/// A key can only carry permissions its creator holds, and only acts as an
+/// account whose role grants nothing more than the creator's unless the
+/// creator may manage roles anyway.
+#[debug_handler]
+pub async fn create_api_key(
+    State(app_state): State<Arc<AppState>>,
+    Extension(admin): Extension<User>,
+    Extension(credential): Extension<Credential>,
+    Json(input): Json<CreateApiKey>,
+) -> Result<impl IntoResponse, AppError> {
+    let creator_role = admin.role.as_deref().unwrap_or(DEFAULT_ROLE);
+    let creator = app_state.roles.access(&app_state.db, creator_role).await?;
+
+    // a key created with another key is limited by that key's scopes too
+    let granted = |permission: &Permission| {
+        creator.permissions.contains(permission)
+            && match &credential {
+                Credential::ApiKey { scopes, .. } => scopes.contains(permission),
+                Credential::Session(_) => true,
+            }
+    };
+
+    let mut errors = vec![];
+
+    let name = input.name.trim().to_string();
+    if name.is_empty() {
+        errors.push(FieldError::new("name", "must not be empty"));
+    }
+    if input.scopes.is_empty() {
+        errors.push(FieldError::new(
+            "scopes",
+            "must contain at least one permission",
+        ));
+    }
+    let not_held = input
+        .scopes
+        .iter()
+        .filter(|scope| !granted(scope))
+        .map(|scope| scope.as_str())
+        .collect::<Vec<&str>>();
+    if !not_held.is_empty() {
+        errors.push(FieldError::new(
+            "scopes",
+            format!("you don't hold {}", not_held.join(", ")),
+        ));
+    }
+    if let Some(days) = input.expires_in_days {
+        if !(1..=MAX_API_KEY_DAYS).contains(&days) {
+            errors.push(FieldError::new(
+                "expires_in_days",
+                format!("must be between 1 and {}", MAX_API_KEY_DAYS),
+            ));
+        }
+    }
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+
+    let created_by = current_user_id(&admin)?;
+    let owner = match input.user_id {
+        Some(id) => {
+            let user_id = parse_object_id(id)?;
+            let users: Collection<User> = app_state.db.collection("users");
+            users
+                .find_one(doc! {"_id": user_id})
+                .await?
+                .ok_or_else(|| AppError::NotFound("User not found".to_string()))?
+        }
+        None => admin.clone(),
+    };
+    let user_id = current_user_id(&owner)?;
+
+    let owner_role = owner.role.as_deref().unwrap_or(DEFAULT_ROLE);
+    let owner_access = app_state.roles.access(&app_state.db, owner_role).await?;
+
+    if !owner_access.permissions.iter().all(&granted) && !granted(&Permission::RoleManage) {
+        return Err(AppError::Forbidden(format!(
+            "{} can do more than you, creating a key for them needs {}",
+            owner.email,
+            Permission::RoleManage.as_str()
+        )));
+    }
+
+    // a key skips the second factor, so its owner must have set one up
+    if owner_access.require_two_factor && !owner.two_factor_enabled() {
+        return Err(AppError::Forbidden(
+            "The key owner's role requires two-factor authentication, they have to enable it first"
+                .to_string(),
+        ));
+    }
+
+    let key = format!("{}{}", API_KEY_PREFIX, generate_token());
+
+    let api_key = ApiKey {
+        id: ObjectId::new(),
+        name,
+        user_id,
+        key_hash: hash_token(&key),
+        prefix: key[..API_KEY_PREFIX.len() + 8].to_string(),
+        scopes: input.scopes,
+        created_by,
+        created_at: DateTime::now(),
+        expires_at: input.expires_in_days.map(|days| {
+            DateTime::from_millis((Utc::now() + Duration::days(days)).timestamp_millis())
+        }),
+        last_used_at: None,
+        revoked_at: None,
+    };
+
+    api_keys(&app_state.db).insert_one(&api_key).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedApiKey {
+            key,
+            api_key: api_key.into(),
+        }),
+    ))
+}
+
+#[debug_handler]
+pub async fn list_api_keys(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<ApiKeyQuery>,
+) -> Result<Json<Page<ApiKeyView>>, AppError> {
+    let request = PageRequest::new(Keyset::NEWEST, query.cursor.as_deref(), query.limit)?;
+
+    let filter = match query.user_id {
+        Some(id) => doc! {"user_id": parse_object_id(id)?},
+        None => doc! {},
+    };
+    let page = find_page(&api_keys(&app_state.db), filter, &request).await?;
+
+    Ok(Json(page.map(ApiKeyView::from)))
+}
+
+#[debug_handler]
+pub async fn revoke_api_key(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let key_id = parse_object_id(id)?;
+
+    let result = api_keys(&app_state.db)
+        .update_one(
+            doc! {"_id": key_id, "revoked_at": null},
+            doc! {"$set": {"revoked_at": DateTime::now()}},
+        )
+        .await?;
+
+    if result.matched_count == 0 {
+        return Err(AppError::NotFound(
+            "API key not found or already revoked".to_string(),
+        ));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
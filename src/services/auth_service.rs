@@ -1,75 +1,375 @@
 use std::{sync::Arc, time::Duration};
 
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Extension, Json};
+use axum::{
+    extract::State,
+    http::{header::CACHE_CONTROL, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
 use axum_cookie::{cookie::Cookie, prelude::SameSite, CookieManager};
 use axum_macros::debug_handler;
-use mongodb::{bson::doc, Collection};
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime},
+    Collection,
+};
 
 use crate::{
     config::app_state::AppState,
-    models::{auth_model::Login, user_model::User},
-    utils::{bcrypt::verify_password, jwt::create_token},
+    errors::app_error::{AppError, FieldError},
+    mail::templates::{send_template, Locale, TemplateData},
+    middlewares::auth_middleware::{bearer_token, Credential},
+    models::{
+        auth_model::{
+            ChallengeResponse, ForgotPasswordInput, Login, PasswordReset, ResetPasswordInput,
+            TokenResponse, TwoFactorLogin,
+        },
+        user_model::{PublicUser, User},
+    },
+    ratelimit::store::enforce,
+    services::{
+        lockout_service::{check_lockout, clear_failures, record_failure},
+        session_service::{
+            create_session, revoke_session, revoke_user_sessions, rotate_session,
+            REFRESH_TOKEN_TTL_DAYS,
+        },
+        two_factor_service::{
+            create_challenge, finish_challenge, redeem_challenge, verify_second_factor,
+            CHALLENGE_TTL_MINUTES,
+        },
+    },
+    utils::{
+        bcrypt::{hash_password, verify_password},
+        jwt::{create_token, ACCESS_TOKEN_TTL_MINUTES},
+        token::{generate_token, hash_token},
+    },
 };
 
+pub const PASSWORD_RESET_TTL_MINUTES: i64 = 30;
+const MIN_PASSWORD_LENGTH: usize = 8;
+const FORGOT_PASSWORD_PER_HOUR: u32 = 3;
+
+fn set_auth_cookies(cookie: &CookieManager, access_token: String, refresh_token: String) {
+    let mut auth_cookie = Cookie::new("access_token", access_token);
+    auth_cookie.set_http_only(true);
+    auth_cookie.set_max_age(Duration::from_secs(ACCESS_TOKEN_TTL_MINUTES as u64 * 60));
+    auth_cookie.set_same_site(SameSite::Strict);
+    auth_cookie.set_path("/");
+    cookie.set(auth_cookie);
+
+    let mut refresh_cookie = Cookie::new("refresh_token", refresh_token);
+    refresh_cookie.set_http_only(true);
+    refresh_cookie.set_max_age(Duration::from_secs(
+        REFRESH_TOKEN_TTL_DAYS as u64 * 24 * 60 * 60,
+    ));
+    refresh_cookie.set_same_site(SameSite::Strict);
+    refresh_cookie.set_path("/api/auth");
+    cookie.set(refresh_cookie);
+}
+
+/// Sets the auth cookies for browsers and returns the same tokens in the body
+/// for clients that authenticate with `Authorization: Bearer`.
+fn issue_tokens(
+    cookie: &CookieManager,
+    access_token: String,
+    refresh_token: String,
+) -> Json<TokenResponse> {
+    set_auth_cookies(cookie, access_token.clone(), refresh_token.clone());
+
+    Json(TokenResponse {
+        access_token,
+        refresh_token,
+        token_type: "Bearer",
+        expires_in: ACCESS_TOKEN_TTL_MINUTES * 60,
+    })
+}
+
+pub fn clear_auth_cookies(cookie: &CookieManager) {
+    // expire with the same paths they were set with, otherwise the browser keeps them
+    for (name, path) in [("access_token", "/"), ("refresh_token", "/api/auth")] {
+        let mut expired = Cookie::new(name, "");
+        expired.set_http_only(true);
+        expired.set_max_age(Duration::ZERO);
+        expired.set_same_site(SameSite::Strict);
+        expired.set_path(path);
+        cookie.set(expired);
+    }
+}
+
 #[debug_handler]
 pub async fn login(
     State(app_state): State<Arc<AppState>>,
     cookie: CookieManager,
     Json(input): Json<Login>,
-) -> impl IntoResponse {
+) -> Result<Response, AppError> {
     let is_valid_email = input.email.contains("@");
 
     if !is_valid_email {
-        return StatusCode::BAD_REQUEST.into_response();
+        return Err(AppError::Validation(vec![FieldError::new(
+            "email",
+            "must be a valid email address",
+        )]));
     }
 
+    let email = input.email.trim().to_lowercase();
+    check_lockout(&app_state.db, &email).await?;
+
     let collection: Collection<User> = app_state.db.collection("users");
-    let filter = doc! {"email": &input.email};
+    let filter = doc! {"email": &email};
 
-    let user = collection
-        .find_one(filter)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR);
-
-    match user {
-        Ok(Some(user)) => {
-            match verify_password(input.password, &user.password) {
-                Ok(valid) => {
-                    if !valid {
-                        return (StatusCode::BAD_REQUEST, "Invalid password").into_response();
-                    }
-                    let id = match user.id {
-                        Some(id) => id,
-                        None => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
-                    };
-                    let access_token = create_token(id);
-                    let mut auth_cookie = Cookie::new("access_token", access_token);
-                    auth_cookie.set_http_only(true);
-                    auth_cookie.set_max_age(Duration::from_secs(3600));
-                    auth_cookie.set_same_site(SameSite::Strict);
-                    auth_cookie.set_path("/");
-                    cookie.set(auth_cookie);
-                    return StatusCode::OK.into_response();
-                }
-                Err(_) => return StatusCode::BAD_REQUEST.into_response(),
-            };
+    let user = collection.find_one(filter).await?;
+
+    let user = match user {
+        Some(user) if verify_password(input.password, &user.password)? => user,
+        _ => {
+            record_failure(&app_state.db, &email).await?;
+            return Err(AppError::InvalidCredentials);
         }
-        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
-        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     };
+    clear_failures(&app_state.db, &email).await?;
+
+    let id = user
+        .id
+        .ok_or_else(|| AppError::Internal("user document has no _id".to_string()))?;
+
+    // the password alone is not enough, the session waits for login_two_factor
+    if user.two_factor_enabled() {
+        let challenge_token = create_challenge(&app_state.db, id).await?;
+        return Ok(Json(ChallengeResponse {
+            two_factor_required: true,
+            challenge_token,
+            expires_in: CHALLENGE_TTL_MINUTES * 60,
+        })
+        .into_response());
+    }
+
+    let (session, refresh_token) = create_session(&app_state.db, id).await?;
+    let access_token = create_token(&app_state.jwt, id, session.id)?;
+
+    Ok(issue_tokens(&cookie, access_token, refresh_token).into_response())
+}
+
+#[debug_handler]
+pub async fn login_two_factor(
+    State(app_state): State<Arc<AppState>>,
+    cookie: CookieManager,
+    Json(input): Json<TwoFactorLogin>,
+) -> Result<impl IntoResponse, AppError> {
+    let challenge = redeem_challenge(&app_state.db, &input.challenge_token).await?;
+
+    let collection: Collection<User> = app_state.db.collection("users");
+    let user = collection
+        .find_one(doc! {"_id": challenge.user_id})
+        .await?
+        .ok_or(AppError::InvalidCredentials)?;
+
+    // a wrong code counts like a wrong password, otherwise logging in again
+    // would hand out fresh challenges to guess codes with
+    check_lockout(&app_state.db, &user.email).await?;
+    match verify_second_factor(&app_state.db, &user, &input.code).await {
+        Err(AppError::InvalidTwoFactorCode) => {
+            record_failure(&app_state.db, &user.email).await?;
+            return Err(AppError::InvalidTwoFactorCode);
+        }
+        result => result?,
+    }
+    clear_failures(&app_state.db, &user.email).await?;
+    finish_challenge(&app_state.db, challenge.id).await?;
+
+    let (session, refresh_token) = create_session(&app_state.db, challenge.user_id).await?;
+    let access_token = create_token(&app_state.jwt, challenge.user_id, session.id)?;
+
+    Ok(issue_tokens(&cookie, access_token, refresh_token))
+}
+
+/// Public half of the token signing keys, so other services can verify
+/// access tokens without sharing a secret.
+#[debug_handler]
+pub async fn jwks(State(app_state): State<Arc<AppState>>) -> impl IntoResponse {
+    (
+        [(CACHE_CONTROL, "public, max-age=300")],
+        Json(app_state.jwt.jwks().clone()),
+    )
 }
 
 #[debug_handler]
 pub async fn me(Extension(user): Extension<User>) -> impl IntoResponse {
-    (StatusCode::OK, Json(user))
+    (StatusCode::OK, Json(PublicUser::from(user)))
 }
 
-pub async fn logout(cookie: CookieManager) -> impl IntoResponse {
-    match cookie.get("access_token") {
-        Some(c) => {
-            cookie.remove(c.name());
-            return (StatusCode::OK, "user logged out success").into_response();
+#[debug_handler]
+pub async fn refresh(
+    State(app_state): State<Arc<AppState>>,
+    cookie: CookieManager,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    // bearer clients send the refresh token the same way they send access tokens
+    let refresh_cookie = cookie.get("refresh_token");
+    let token = refresh_cookie
+        .as_ref()
+        .map(|c| c.value())
+        .or(bearer_token(&headers))
+        .ok_or_else(|| AppError::Unauthorized("you are not logged in".to_string()))?;
+
+    match rotate_session(&app_state.db, token).await {
+        Ok((session, refresh_token)) => {
+            let access_token = create_token(&app_state.jwt, session.user_id, session.id)?;
+            Ok(issue_tokens(&cookie, access_token, refresh_token))
+        }
+        Err(e @ (AppError::RefreshTokenReused | AppError::SessionExpired)) => {
+            clear_auth_cookies(&cookie);
+            Err(e)
         }
-        None => return (StatusCode::UNAUTHORIZED, "you are not logged in").into_response(),
+        Err(e) => Err(e),
+    }
+}
+
+#[debug_handler]
+pub async fn logout(
+    State(app_state): State<Arc<AppState>>,
+    Extension(credential): Extension<Credential>,
+    cookie: CookieManager,
+) -> Result<impl IntoResponse, AppError> {
+    let Credential::Session(claims) = credential else {
+        return Err(AppError::BadRequest(
+            "API keys are revoked by an admin, not logged out".to_string(),
+        ));
+    };
+
+    revoke_session(&app_state.db, claims.session_id).await?;
+
+    clear_auth_cookies(&cookie);
+
+    Ok((StatusCode::OK, "user logged out success"))
+}
+
+#[debug_handler]
+pub async fn forgot_password(
+    State(app_state): State<Arc<AppState>>,
+    Json(input): Json<ForgotPasswordInput>,
+) -> Result<impl IntoResponse, AppError> {
+    // the response is the same whether or not the email is registered so this
+    // endpoint can't be used to enumerate accounts
+    let response = (
+        StatusCode::OK,
+        "If an account exists for this email, a password reset link has been sent",
+    );
+
+    let email = input.email.trim().to_lowercase();
+
+    // counted for unknown addresses too, so the limit reveals nothing
+    enforce(
+        app_state.limiter.as_ref(),
+        &format!("forgot-password:account:{}", email),
+        FORGOT_PASSWORD_PER_HOUR,
+        Duration::from_secs(60 * 60),
+    )
+    .await?;
+
+    let user_collection: Collection<User> = app_state.db.collection("users");
+    let user = user_collection.find_one(doc! {"email": &email}).await?;
+
+    let user = match user {
+        Some(user) => user,
+        None => return Ok(response),
+    };
+    let user_id = match user.id {
+        Some(id) => id,
+        None => return Ok(response),
+    };
+
+    let reset_collection: Collection<PasswordReset> = app_state.db.collection("password_resets");
+
+    // only the most recently requested link stays valid
+    reset_collection
+        .update_many(
+            doc! {"user_id": user_id, "used_at": null},
+            doc! {"$set": {"used_at": DateTime::now()}},
+        )
+        .await?;
+
+    let token = generate_token();
+    let expires_at = chrono::Utc::now() + chrono::Duration::minutes(PASSWORD_RESET_TTL_MINUTES);
+
+    let reset = PasswordReset {
+        id: ObjectId::new(),
+        user_id,
+        token_hash: hash_token(&token),
+        created_at: DateTime::now(),
+        expires_at: DateTime::from_millis(expires_at.timestamp_millis()),
+        used_at: None,
     };
+
+    reset_collection.insert_one(reset).await?;
+
+    let reset_link = format!(
+        "{}/reset-password?token={}",
+        app_state.settings.server.app_url, token
+    );
+
+    if let Err(e) = send_template(
+        &app_state.db,
+        &app_state.settings.mail.brand,
+        Locale::parse(user.locale.as_deref().unwrap_or_default()),
+        &user.name,
+        &user.email,
+        TemplateData::PasswordReset {
+            reset_link,
+            valid_minutes: PASSWORD_RESET_TTL_MINUTES,
+        },
+    )
+    .await
+    {
+        tracing::error!(
+            "failed to queue password reset email for user {}: {:?}",
+            user_id,
+            e
+        );
+    }
+
+    Ok(response)
+}
+
+#[debug_handler]
+pub async fn reset_password(
+    State(app_state): State<Arc<AppState>>,
+    Json(input): Json<ResetPasswordInput>,
+) -> Result<impl IntoResponse, AppError> {
+    if input.password.len() < MIN_PASSWORD_LENGTH {
+        return Err(AppError::Validation(vec![FieldError::new(
+            "password",
+            format!("must be at least {} characters long", MIN_PASSWORD_LENGTH),
+        )]));
+    }
+
+    let reset_collection: Collection<PasswordReset> = app_state.db.collection("password_resets");
+
+    // consuming the token in the same operation that finds it keeps it single-use
+    let reset = reset_collection
+        .find_one_and_update(
+            doc! {
+                "token_hash": hash_token(&input.token),
+                "used_at": null,
+                "expires_at": {"$gt": DateTime::now()},
+            },
+            doc! {"$set": {"used_at": DateTime::now()}},
+        )
+        .await?
+        .ok_or_else(|| AppError::BadRequest("reset link is invalid or has expired".to_string()))?;
+
+    let hashed = hash_password(input.password)?;
+
+    let user_collection: Collection<User> = app_state.db.collection("users");
+    let user = user_collection
+        .find_one_and_update(
+            doc! {"_id": reset.user_id},
+            doc! {"$set": {"password": hashed}},
+        )
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    revoke_user_sessions(&app_state.db, reset.user_id).await?;
+    // proving control of the mailbox lifts a lock from guessed passwords
+    clear_failures(&app_state.db, &user.email).await?;
+
+    Ok((StatusCode::OK, "password has been reset please login again"))
 }
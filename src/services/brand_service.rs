@@ -0,0 +1,179 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use axum_macros::debug_handler;
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime, Document},
+    options::ReturnDocument,
+    Collection, Database,
+};
+
+use crate::{
+    config::app_state::AppState,
+    database::mongo::is_duplicate_key_error,
+    errors::app_error::{AppError, FieldError},
+    models::{
+        brand_model::{Brand, CreateBrand, UpdateBrand},
+        products_model::Products,
+    },
+    services::product_service::reindex_products,
+    utils::{
+        parse_id::parse_object_id,
+        slug::{slugify, validate_slug},
+    },
+};
+
+fn brands(db: &Database) -> Collection<Brand> {
+    db.collection("brands")
+}
+
+pub async fn find_brand(db: &Database, id: ObjectId) -> Result<Option<Brand>, AppError> {
+    Ok(brands(db).find_one(doc! {"_id": id}).await?)
+}
+
+fn slug_taken(slug: &str) -> AppError {
+    AppError::Conflict(format!("slug `{}` is already used by another brand", slug))
+}
+
+#[debug_handler]
+pub async fn list_brands(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut cursor = brands(&app_state.db)
+        .find(doc! {})
+        .sort(doc! {"name": 1})
+        .await?;
+
+    let mut result = vec![];
+    while cursor.advance().await? {
+        result.push(cursor.deserialize_current()?);
+    }
+
+    Ok(Json(result))
+}
+
+#[debug_handler]
+pub async fn create_brand(
+    State(app_state): State<Arc<AppState>>,
+    Json(input): Json<CreateBrand>,
+) -> Result<impl IntoResponse, AppError> {
+    let name = input.name.trim().to_string();
+    let slug = input.slug.unwrap_or_else(|| slugify(&name));
+
+    let mut errors = vec![];
+    if name.is_empty() {
+        errors.push(FieldError::new("name", "name cannot be empty"));
+    }
+    errors.extend(validate_slug(&slug));
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+
+    let now = DateTime::now();
+    let brand = Brand {
+        id: ObjectId::new(),
+        name,
+        slug,
+        created_at: now,
+        updated_at: now,
+    };
+
+    match brands(&app_state.db).insert_one(&brand).await {
+        Ok(_) => Ok((StatusCode::CREATED, Json(brand))),
+        Err(e) if is_duplicate_key_error(&e) => Err(slug_taken(&brand.slug)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Renaming a brand also renames it on its products.
+#[debug_handler]
+pub async fn update_brand(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(input): Json<UpdateBrand>,
+) -> Result<impl IntoResponse, AppError> {
+    let brand_id = parse_object_id(id)?;
+
+    let mut set = Document::new();
+    let mut errors = vec![];
+
+    let name = input.name.map(|name| name.trim().to_string());
+    if let Some(name) = &name {
+        if name.is_empty() {
+            errors.push(FieldError::new("name", "name cannot be empty"));
+        }
+        set.insert("name", name);
+    }
+    if let Some(slug) = &input.slug {
+        errors.extend(validate_slug(slug));
+        set.insert("slug", slug);
+    }
+
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+    if set.is_empty() {
+        return Err(AppError::BadRequest("nothing to update".to_string()));
+    }
+    set.insert("updated_at", DateTime::now());
+
+    let brand = match brands(&app_state.db)
+        .find_one_and_update(doc! {"_id": brand_id}, doc! {"$set": set})
+        .return_document(ReturnDocument::After)
+        .await
+    {
+        Ok(brand) => brand.ok_or_else(|| AppError::NotFound("Brand not found".to_string()))?,
+        Err(e) if is_duplicate_key_error(&e) => {
+            return Err(slug_taken(input.slug.as_deref().unwrap_or_default()))
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    if name.is_some() {
+        let products: Collection<Products> = app_state.db.collection("products");
+        products
+            .update_many(
+                doc! {"brand_id": brand_id},
+                doc! {"$set": {"brand": &brand.name}},
+            )
+            .await?;
+        reindex_products(&app_state, doc! {"brand_id": brand_id}).await?;
+    }
+
+    Ok(Json(brand))
+}
+
+#[debug_handler]
+pub async fn delete_brand(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let brand_id = parse_object_id(id)?;
+
+    let products: Collection<Products> = app_state.db.collection("products");
+    let in_use = products
+        .count_documents(doc! {"brand_id": brand_id, "deleted_at": null})
+        .await?;
+
+    if in_use > 0 {
+        return Err(AppError::Conflict(format!(
+            "brand is used by {} products",
+            in_use
+        )));
+    }
+
+    let result = brands(&app_state.db)
+        .delete_one(doc! {"_id": brand_id})
+        .await?;
+
+    if result.deleted_count == 0 {
+        return Err(AppError::NotFound("Brand not found".to_string()));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,55 +1,206 @@
-use std::{str::FromStr, sync::Arc};
+use std::{collections::HashMap, str::FromStr, sync::Arc};
 
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Extension, Json};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
 use axum_macros::debug_handler;
 use mongodb::bson;
 use mongodb::{
     bson::{doc, oid::ObjectId},
-    Collection,
+    Collection, Database,
 };
 
 use crate::{
     config::app_state::AppState,
+    errors::app_error::AppError,
     models::{
-        cart_model::{Cart, CartItem},
+        cart_model::{Cart, CartItem, CartItemQuery, CartLine, CartResponse, UpdateCartItem},
+        products_model::Products,
         user_model::User,
     },
 };
 
+pub fn current_user_id(user: &User) -> Result<ObjectId, AppError> {
+    user.id
+        .ok_or_else(|| AppError::Unauthorized("UNAUTHORIZED".to_string()))
+}
+
+pub fn round_price(value: f32) -> f32 {
+    (value * 100.0).round() / 100.0
+}
+
+/// Prices every line of `cart` from the current product documents and keeps
+/// the stored `total_price` in sync. Lines whose product or variant no longer
+/// exists, or whose product was deleted, are left out of the response.
+pub async fn priced_cart(db: &Database, cart: Cart) -> Result<CartResponse, AppError> {
+    let product_ids = cart
+        .products
+        .iter()
+        .filter_map(|item| ObjectId::from_str(&item.product_id).ok())
+        .collect::<Vec<ObjectId>>();
+
+    let product_collection: Collection<Products> = db.collection("products");
+    let mut cursor = product_collection
+        .find(doc! {"_id": {"$in": &product_ids}, "deleted_at": null})
+        .await?;
+
+    let mut products = HashMap::new();
+    while cursor.advance().await? {
+        let product = cursor.deserialize_current()?;
+        if let Some(id) = product._id {
+            products.insert(id.to_hex(), product);
+        }
+    }
+
+    let mut items = Vec::with_capacity(cart.products.len());
+    for item in cart.products {
+        let product = match products.get(&item.product_id) {
+            Some(product) => product,
+            None => continue,
+        };
+        let unit_price = match product.unit_price(item.sku.as_deref()) {
+            Some(unit_price) => unit_price,
+            None => continue,
+        };
+
+        items.push(CartLine {
+            product_id: item.product_id,
+            sku: item.sku,
+            quantity: item.quantity,
+            unit_price,
+            line_total: round_price(unit_price * item.quantity as f32),
+            product: product.clone(),
+        });
+    }
+
+    let total_price = round_price(items.iter().map(|line| line.line_total).sum());
+
+    if cart.total_price != Some(total_price) {
+        let cart_collection: Collection<Cart> = db.collection("cart");
+        cart_collection
+            .update_one(
+                doc! {"_id": cart._id},
+                doc! {"$set": {"total_price": total_price as f64}},
+            )
+            .await?;
+    }
+
+    Ok(CartResponse {
+        _id: cart._id,
+        items,
+        total_price,
+    })
+}
+
+/// Checks that `sku` names one of the product's variants, or is absent for a
+/// product without any.
+fn ensure_variant(product: &Products, sku: Option<&str>) -> Result<(), AppError> {
+    match sku {
+        None if !product.variants.is_empty() => Err(AppError::BadRequest(
+            "sku is required for this product".to_string(),
+        )),
+        Some(_) if product.variants.is_empty() => Err(AppError::BadRequest(
+            "this product has no variants".to_string(),
+        )),
+        Some(sku) if product.variant(sku).is_none() => {
+            Err(AppError::NotFound("Variant not found".to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Rejects quantities the product cannot cover right now. This is only an
+/// early hint for the customer, the stock is actually taken at checkout.
+fn ensure_in_stock(product: &Products, sku: Option<&str>, quantity: u32) -> Result<(), AppError> {
+    let stock = product.stock_of(sku).unwrap_or_default();
+    if quantity as i64 > stock {
+        return Err(AppError::Conflict(format!(
+            "only {} of {} left in stock",
+            stock.max(0),
+            sku.unwrap_or(&product.title)
+        )));
+    }
+    Ok(())
+}
+
+/// Matches the cart line for a product and variant. A `null` sku also
+/// matches lines stored before variants existed.
+fn line_filter(user_id: ObjectId, product_id: &str, sku: Option<&str>) -> bson::Document {
+    doc! {
+        "user_id": user_id,
+        "products": {"$elemMatch": {"product_id": product_id, "sku": sku}},
+    }
+}
+
+async fn cart_response(db: &Database, user_id: ObjectId) -> Result<CartResponse, AppError> {
+    let collection: Collection<Cart> = db.collection("cart");
+
+    match collection.find_one(doc! {"user_id": user_id}).await? {
+        Some(cart) => priced_cart(db, cart).await,
+        None => Ok(CartResponse {
+            _id: None,
+            items: vec![],
+            total_price: 0.0,
+        }),
+    }
+}
+
 #[debug_handler]
 pub async fn add_to_cart(
     State(app_state): State<Arc<AppState>>,
     Extension(user): Extension<User>,
     Json(input): Json<CartItem>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+) -> Result<impl IntoResponse, AppError> {
     let collection: Collection<Cart> = app_state.db.collection("cart");
 
     tracing::info!("USER FROM CART: {:?}", &user.id);
     tracing::info!("PRODUCT_ID: {:?}", &input.product_id);
 
-    let user_id = match user.id {
-        Some(id) => id,
-        None => return Err((StatusCode::UNAUTHORIZED, "UNAUTHORIZED".to_string())),
-    };
+    let user_id = current_user_id(&user)?;
 
-    match ObjectId::from_str(input.product_id.as_str()) {
-        Ok(id) => id,
-        Err(e) => return Err((StatusCode::BAD_REQUEST, e.to_string())),
-    };
+    let product_id =
+        ObjectId::from_str(input.product_id.as_str()).map_err(|_| AppError::InvalidId)?;
+
+    if input.quantity == 0 {
+        return Err(AppError::BadRequest(
+            "quantity must be at least 1".to_string(),
+        ));
+    }
+
+    let product_collection: Collection<Products> = app_state.db.collection("products");
+    let product = product_collection
+        .find_one(doc! {"_id": product_id, "deleted_at": null})
+        .await?
+        .ok_or_else(|| AppError::NotFound("Product not found".to_string()))?;
 
     let filter = doc! { "user_id": &user_id };
 
     let result = collection
         .find_one(filter.clone()) // Clone filter for later use
-        .await
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Failed".to_string()))?;
+        .await?;
+
+    ensure_variant(&product, input.sku.as_deref())?;
+
+    let in_cart = result
+        .as_ref()
+        .and_then(|cart| {
+            cart.products
+                .iter()
+                .find(|p| p.product_id == input.product_id && p.sku == input.sku)
+        })
+        .map(|item| item.quantity)
+        .unwrap_or(0);
+    ensure_in_stock(&product, input.sku.as_deref(), in_cart + input.quantity)?;
 
     match result {
         Some(mut cart) => {
             if let Some(item) = cart
                 .products
                 .iter_mut()
-                .find(|p| p.product_id == input.product_id)
+                .find(|p| p.product_id == input.product_id && p.sku == input.sku)
             {
                 item.quantity += input.quantity;
             } else {
@@ -57,13 +208,10 @@ pub async fn add_to_cart(
             }
 
             let update = doc! {
-                "$set": { "products": bson::to_bson(&cart.products).unwrap() }
+                "$set": { "products": bson::to_bson(&cart.products)? }
             };
 
-            collection
-                .update_one(filter, update)
-                .await
-                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            collection.update_one(filter, update).await?;
 
             Ok((StatusCode::OK, "Updated cart".to_string()))
         }
@@ -75,12 +223,107 @@ pub async fn add_to_cart(
                 total_price: None,
             };
 
-            collection
-                .insert_one(new_cart)
-                .await
-                .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Failed".to_string()))?;
+            collection.insert_one(new_cart).await?;
 
             Ok((StatusCode::OK, "CREATED".to_string()))
         }
     }
 }
+
+#[debug_handler]
+pub async fn get_cart(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, AppError> {
+    let user_id = current_user_id(&user)?;
+
+    Ok(Json(cart_response(&app_state.db, user_id).await?))
+}
+
+#[debug_handler]
+pub async fn update_cart_item(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(product_id): Path<String>,
+    Query(query): Query<CartItemQuery>,
+    Json(input): Json<UpdateCartItem>,
+) -> Result<impl IntoResponse, AppError> {
+    let user_id = current_user_id(&user)?;
+    let collection: Collection<Cart> = app_state.db.collection("cart");
+    let sku = query.sku.as_deref();
+
+    if input.quantity > 0 {
+        let id = ObjectId::from_str(&product_id).map_err(|_| AppError::InvalidId)?;
+        let product_collection: Collection<Products> = app_state.db.collection("products");
+        let product = product_collection
+            .find_one(doc! {"_id": id, "deleted_at": null})
+            .await?
+            .ok_or_else(|| AppError::NotFound("Product not found".to_string()))?;
+        ensure_variant(&product, sku)?;
+        ensure_in_stock(&product, sku, input.quantity)?;
+    }
+
+    let filter = line_filter(user_id, &product_id, sku);
+
+    // a quantity of zero means the line should go away
+    let update = if input.quantity == 0 {
+        doc! {"$pull": {"products": {"product_id": &product_id, "sku": sku}}}
+    } else {
+        doc! {"$set": {"products.$.quantity": input.quantity as i64}}
+    };
+
+    let result = collection.update_one(filter, update).await?;
+
+    if result.matched_count == 0 {
+        return Err(AppError::NotFound(
+            "Product is not in your cart".to_string(),
+        ));
+    }
+
+    Ok(Json(cart_response(&app_state.db, user_id).await?))
+}
+
+#[debug_handler]
+pub async fn remove_cart_item(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(product_id): Path<String>,
+    Query(query): Query<CartItemQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let user_id = current_user_id(&user)?;
+    let collection: Collection<Cart> = app_state.db.collection("cart");
+    let sku = query.sku.as_deref();
+
+    let result = collection
+        .update_one(
+            line_filter(user_id, &product_id, sku),
+            doc! {"$pull": {"products": {"product_id": &product_id, "sku": sku}}},
+        )
+        .await?;
+
+    if result.matched_count == 0 {
+        return Err(AppError::NotFound(
+            "Product is not in your cart".to_string(),
+        ));
+    }
+
+    Ok(Json(cart_response(&app_state.db, user_id).await?))
+}
+
+#[debug_handler]
+pub async fn clear_cart(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, AppError> {
+    let user_id = current_user_id(&user)?;
+    let collection: Collection<Cart> = app_state.db.collection("cart");
+
+    collection
+        .update_one(
+            doc! {"user_id": user_id},
+            doc! {"$set": {"products": [], "total_price": 0.0}},
+        )
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,364 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use axum_macros::debug_handler;
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime, Document},
+    options::ReturnDocument,
+    Collection, Database,
+};
+use serde::Deserialize;
+
+use crate::{
+    config::app_state::AppState,
+    database::mongo::is_duplicate_key_error,
+    errors::app_error::{AppError, FieldError},
+    models::{
+        category_model::{Category, CategoryNode, CreateCategory, UpdateCategory},
+        products_model::Products,
+    },
+    services::product_service::reindex_products,
+    utils::{
+        parse_id::parse_object_id,
+        slug::{slugify, validate_slug},
+    },
+};
+
+fn categories(db: &Database) -> Collection<Category> {
+    db.collection("categories")
+}
+
+pub async fn find_category(db: &Database, id: ObjectId) -> Result<Option<Category>, AppError> {
+    Ok(categories(db).find_one(doc! {"_id": id}).await?)
+}
+
+fn slug_taken(slug: &str) -> AppError {
+    AppError::Conflict(format!(
+        "slug `{}` is already used by another category",
+        slug
+    ))
+}
+
+async fn find_parent(db: &Database, parent_id: String) -> Result<Category, AppError> {
+    let parent_id = parse_object_id(parent_id)?;
+
+    find_category(db, parent_id).await?.ok_or_else(|| {
+        AppError::Validation(vec![FieldError::new(
+            "parent_id",
+            "parent category does not exist",
+        )])
+    })
+}
+
+async fn all_categories(db: &Database) -> Result<Vec<Category>, AppError> {
+    let mut cursor = categories(db)
+        .find(doc! {})
+        .sort(doc! {"position": 1, "name": 1})
+        .await?;
+
+    let mut result = vec![];
+    while cursor.advance().await? {
+        result.push(cursor.deserialize_current()?);
+    }
+    Ok(result)
+}
+
+#[debug_handler]
+pub async fn list_categories(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    Ok(Json(all_categories(&app_state.db).await?))
+}
+
+#[derive(Deserialize)]
+struct CategoryCount {
+    #[serde(rename = "_id")]
+    id: ObjectId,
+    count: u64,
+}
+
+fn build_tree(
+    parent_id: Option<ObjectId>,
+    children: &HashMap<Option<ObjectId>, Vec<&Category>>,
+    counts: &HashMap<ObjectId, u64>,
+) -> Vec<CategoryNode> {
+    children
+        .get(&parent_id)
+        .map(|categories| {
+            categories
+                .iter()
+                .map(|category| CategoryNode {
+                    id: category.id,
+                    name: category.name.clone(),
+                    slug: category.slug.clone(),
+                    position: category.position,
+                    product_count: counts.get(&category.id).copied().unwrap_or(0),
+                    children: build_tree(Some(category.id), children, counts),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The whole category tree for navigation menus, with product counts that
+/// include subcategories.
+#[debug_handler]
+pub async fn category_tree(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let all = all_categories(&app_state.db).await?;
+
+    let products: Collection<Products> = app_state.db.collection("products");
+    let pipeline = vec![
+        doc! {"$match": {"deleted_at": null}},
+        doc! {"$unwind": "$category_path"},
+        doc! {"$group": {"_id": "$category_path", "count": {"$sum": 1}}},
+    ];
+    let mut cursor = products
+        .aggregate(pipeline)
+        .with_type::<CategoryCount>()
+        .await?;
+
+    let mut counts = HashMap::new();
+    while cursor.advance().await? {
+        let count = cursor.deserialize_current()?;
+        counts.insert(count.id, count.count);
+    }
+
+    let mut children: HashMap<Option<ObjectId>, Vec<&Category>> = HashMap::new();
+    for category in &all {
+        children
+            .entry(category.parent_id)
+            .or_default()
+            .push(category);
+    }
+
+    Ok(Json(build_tree(None, &children, &counts)))
+}
+
+#[debug_handler]
+pub async fn create_category(
+    State(app_state): State<Arc<AppState>>,
+    Json(input): Json<CreateCategory>,
+) -> Result<impl IntoResponse, AppError> {
+    let name = input.name.trim().to_string();
+    let slug = input.slug.unwrap_or_else(|| slugify(&name));
+
+    let mut errors = vec![];
+    if name.is_empty() {
+        errors.push(FieldError::new("name", "name cannot be empty"));
+    }
+    errors.extend(validate_slug(&slug));
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+
+    let parent = match input.parent_id {
+        Some(parent_id) => Some(find_parent(&app_state.db, parent_id).await?),
+        None => None,
+    };
+
+    let now = DateTime::now();
+    let category = Category {
+        id: ObjectId::new(),
+        name,
+        slug,
+        parent_id: parent.as_ref().map(|parent| parent.id),
+        path: parent.map(|parent| parent.path_below()).unwrap_or_default(),
+        position: input.position,
+        created_at: now,
+        updated_at: now,
+    };
+
+    match categories(&app_state.db).insert_one(&category).await {
+        Ok(_) => Ok((StatusCode::CREATED, Json(category))),
+        Err(e) if is_duplicate_key_error(&e) => Err(slug_taken(&category.slug)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Gives the subtree below `moved` its new path and updates the paths of
+/// the products in it.
+async fn move_subtree(db: &Database, moved: &Category) -> Result<(), AppError> {
+    let mut subtree = vec![moved.clone()];
+
+    let mut cursor = categories(db).find(doc! {"path": moved.id}).await?;
+    while cursor.advance().await? {
+        let mut descendant: Category = cursor.deserialize_current()?;
+
+        let below = descendant
+            .path
+            .iter()
+            .position(|id| *id == moved.id)
+            .map(|index| descendant.path.split_off(index + 1))
+            .unwrap_or_default();
+        descendant.path = moved.path_below();
+        descendant.path.extend(below);
+
+        categories(db)
+            .update_one(
+                doc! {"_id": descendant.id},
+                doc! {"$set": {"path": &descendant.path}},
+            )
+            .await?;
+        subtree.push(descendant);
+    }
+
+    let products: Collection<Products> = db.collection("products");
+    for category in subtree {
+        products
+            .update_many(
+                doc! {"category_id": category.id},
+                doc! {"$set": {"category_path": category.path_below()}},
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Renames, reorders or moves a category. A move takes the whole subtree
+/// along, a category can't be moved below itself.
+#[debug_handler]
+pub async fn update_category(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(input): Json<UpdateCategory>,
+) -> Result<impl IntoResponse, AppError> {
+    let category_id = parse_object_id(id)?;
+
+    let current = find_category(&app_state.db, category_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Category not found".to_string()))?;
+
+    let mut set = Document::new();
+    let mut errors = vec![];
+
+    let name = input.name.map(|name| name.trim().to_string());
+    if let Some(name) = &name {
+        if name.is_empty() {
+            errors.push(FieldError::new("name", "name cannot be empty"));
+        }
+        set.insert("name", name);
+    }
+    if let Some(slug) = &input.slug {
+        errors.extend(validate_slug(slug));
+        set.insert("slug", slug);
+    }
+    if let Some(position) = input.position {
+        set.insert("position", position);
+    }
+
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+
+    let mut moved = false;
+    if let Some(parent_id) = input.parent_id {
+        let parent = match parent_id {
+            Some(parent_id) => Some(find_parent(&app_state.db, parent_id).await?),
+            None => None,
+        };
+
+        if parent
+            .as_ref()
+            .is_some_and(|parent| parent.id == category_id || parent.path.contains(&category_id))
+        {
+            return Err(AppError::BadRequest(
+                "a category can't be moved below itself".to_string(),
+            ));
+        }
+
+        let parent_id = parent.as_ref().map(|parent| parent.id);
+        if parent_id != current.parent_id {
+            let path = parent.map(|parent| parent.path_below()).unwrap_or_default();
+            set.insert("parent_id", parent_id);
+            set.insert("path", path);
+            moved = true;
+        }
+    }
+
+    if set.is_empty() {
+        return Err(AppError::BadRequest("nothing to update".to_string()));
+    }
+    set.insert("updated_at", DateTime::now());
+
+    // matching on the old path keeps two concurrent moves from interleaving
+    let category = match categories(&app_state.db)
+        .find_one_and_update(
+            doc! {"_id": category_id, "path": &current.path},
+            doc! {"$set": set},
+        )
+        .return_document(ReturnDocument::After)
+        .await
+    {
+        Ok(category) => category.ok_or_else(|| {
+            AppError::Conflict("the category changed while updating it, please retry".to_string())
+        })?,
+        Err(e) if is_duplicate_key_error(&e) => {
+            return Err(slug_taken(input.slug.as_deref().unwrap_or_default()))
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    if moved {
+        move_subtree(&app_state.db, &category).await?;
+    }
+
+    if name.is_some() {
+        let products: Collection<Products> = app_state.db.collection("products");
+        products
+            .update_many(
+                doc! {"category_id": category_id},
+                doc! {"$set": {"category": &category.name}},
+            )
+            .await?;
+        reindex_products(&app_state, doc! {"category_id": category_id}).await?;
+    }
+
+    Ok(Json(category))
+}
+
+/// Only empty leaf categories can be deleted.
+#[debug_handler]
+pub async fn delete_category(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let category_id = parse_object_id(id)?;
+
+    let children = categories(&app_state.db)
+        .count_documents(doc! {"parent_id": category_id})
+        .await?;
+    if children > 0 {
+        return Err(AppError::Conflict(
+            "move or delete its subcategories first".to_string(),
+        ));
+    }
+
+    let products: Collection<Products> = app_state.db.collection("products");
+    let in_use = products
+        .count_documents(doc! {"category_id": category_id, "deleted_at": null})
+        .await?;
+    if in_use > 0 {
+        return Err(AppError::Conflict(format!(
+            "category is used by {} products",
+            in_use
+        )));
+    }
+
+    let result = categories(&app_state.db)
+        .delete_one(doc! {"_id": category_id})
+        .await?;
+
+    if result.deleted_count == 0 {
+        return Err(AppError::NotFound("Category not found".to_string()));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
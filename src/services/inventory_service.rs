@@ -0,0 +1,466 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Extension, Json,
+};
+use axum_macros::debug_handler;
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime, Document},
+    options::ReturnDocument,
+    Collection, Database,
+};
+
+use crate::{
+    config::app_state::AppState,
+    errors::app_error::AppError,
+    models::{
+        inventory_model::{
+            MovementQuery, MovementReason, ProductStock, ReservationStatus, ReservedItem,
+            StockAdjustment, StockLevel, StockMovement, StockReservation,
+        },
+        order_model::{Order, OrderStatus},
+        products_model::Products,
+        user_model::User,
+    },
+    services::order_service::transition_order,
+    utils::parse_id::parse_object_id,
+};
+
+/// How long checkout holds stock for an order that has not been paid.
+pub const RESERVATION_TTL_MINUTES: i64 = 30;
+/// How long stock stays held for an order whose card has been authorised
+/// but not captured yet, about as long as card networks keep the hold.
+pub const AUTHORIZED_HOLD_MINUTES: i64 = 7 * 24 * 60;
+const RESERVATION_SWEEP_SECONDS: u64 = 60;
+const DEFAULT_MOVEMENT_LIMIT: i64 = 50;
+
+fn products(db: &Database) -> Collection<Products> {
+    db.collection("products")
+}
+
+fn reservations(db: &Database) -> Collection<StockReservation> {
+    db.collection("stock_reservations")
+}
+
+fn movements(db: &Database) -> Collection<StockMovement> {
+    db.collection("stock_movements")
+}
+
+pub async fn record_movements(db: &Database, entries: Vec<StockMovement>) -> Result<(), AppError> {
+    if !entries.is_empty() {
+        movements(db).insert_many(entries).await?;
+    }
+    Ok(())
+}
+
+/// Filter for the stock counter of a product, or of one of its variants when
+/// `sku` is given, optionally only matching while it holds at least
+/// `minimum`, along with the field to `$inc`.
+fn stock_counter(
+    product_id: ObjectId,
+    sku: Option<&str>,
+    minimum: Option<i64>,
+) -> (Document, &'static str) {
+    match sku {
+        Some(sku) => {
+            let mut variant = doc! {"sku": sku};
+            if let Some(minimum) = minimum {
+                variant.insert("stock", doc! {"$gte": minimum});
+            }
+            (
+                doc! {"_id": product_id, "variants": {"$elemMatch": variant}},
+                "variants.$.stock",
+            )
+        }
+        None => {
+            let mut filter = doc! {"_id": product_id};
+            if let Some(minimum) = minimum {
+                filter.insert("stock", doc! {"$gte": minimum});
+            }
+            (filter, "stock")
+        }
+    }
+}
+
+/// Puts back stock taken by a reservation that could not be completed. This
+/// runs on an error path already, so failures are logged instead of returned.
+async fn restore_stock(db: &Database, taken: &[ReservedItem]) {
+    for item in taken {
+        let (filter, field) = stock_counter(item.product_id, item.sku.as_deref(), None);
+        if let Err(e) = products(db)
+            .update_one(filter, doc! {"$inc": {field: item.quantity as i64}})
+            .await
+        {
+            tracing::error!(
+                "failed to restore {} of product {}: {}",
+                item.quantity,
+                item.product_id,
+                e
+            );
+        }
+    }
+}
+
+/// Takes the stock for every item of an order. Each decrement only matches
+/// while enough stock is left, so concurrent checkouts cannot oversell; if
+/// any item falls short the ones already taken are put back.
+pub async fn reserve_stock(
+    db: &Database,
+    order_id: ObjectId,
+    user_id: ObjectId,
+    items: Vec<ReservedItem>,
+) -> Result<StockReservation, AppError> {
+    let mut taken: Vec<ReservedItem> = Vec::with_capacity(items.len());
+    let mut entries = Vec::with_capacity(items.len());
+
+    for item in &items {
+        let quantity = item.quantity as i64;
+
+        let (filter, field) = stock_counter(item.product_id, item.sku.as_deref(), Some(quantity));
+
+        let updated = products(db)
+            .find_one_and_update(filter, doc! {"$inc": {field: -quantity}})
+            .return_document(ReturnDocument::After)
+            .await;
+
+        match updated {
+            Ok(Some(product)) => {
+                taken.push(item.clone());
+
+                let mut entry = StockMovement::new(
+                    item.product_id,
+                    item.sku.clone(),
+                    -quantity,
+                    product.stock_of(item.sku.as_deref()).unwrap_or_default(),
+                    MovementReason::Reserved,
+                );
+                entry.order_id = Some(order_id);
+                entries.push(entry);
+            }
+            Ok(None) => {
+                restore_stock(db, &taken).await;
+                return Err(AppError::Conflict(match &item.sku {
+                    Some(sku) => format!("not enough stock for {}", sku),
+                    None => format!("not enough stock for product {}", item.product_id),
+                }));
+            }
+            Err(e) => {
+                restore_stock(db, &taken).await;
+                return Err(e.into());
+            }
+        }
+    }
+
+    let now = DateTime::now();
+    let reservation = StockReservation {
+        id: ObjectId::new(),
+        order_id,
+        user_id,
+        items,
+        status: ReservationStatus::Active,
+        created_at: now,
+        expires_at: DateTime::from_millis(
+            now.timestamp_millis() + RESERVATION_TTL_MINUTES * 60 * 1000,
+        ),
+        closed_at: None,
+    };
+
+    if let Err(e) = reservations(db).insert_one(&reservation).await {
+        restore_stock(db, &taken).await;
+        return Err(e.into());
+    }
+
+    record_movements(db, entries).await?;
+
+    Ok(reservation)
+}
+
+/// Returns the stock of an order's active reservation. Only the caller that
+/// flips the reservation out of `active` puts stock back, so releasing twice
+/// is harmless.
+pub async fn release_reservation(db: &Database, order_id: ObjectId) -> Result<(), AppError> {
+    let released = reservations(db)
+        .find_one_and_update(
+            doc! {"order_id": order_id, "status": ReservationStatus::Active.as_str()},
+            doc! {"$set": {
+                "status": ReservationStatus::Released.as_str(),
+                "closed_at": DateTime::now(),
+            }},
+        )
+        .await?;
+
+    let reservation = match released {
+        Some(reservation) => reservation,
+        None => return Ok(()),
+    };
+
+    let mut entries = Vec::with_capacity(reservation.items.len());
+    for item in reservation.items {
+        let quantity = item.quantity as i64;
+
+        let (filter, field) = stock_counter(item.product_id, item.sku.as_deref(), None);
+
+        let product = products(db)
+            .find_one_and_update(filter, doc! {"$inc": {field: quantity}})
+            .return_document(ReturnDocument::After)
+            .await?;
+
+        // the product or variant may have been deleted while the order was open
+        if let Some(product) = product {
+            let mut entry = StockMovement::new(
+                item.product_id,
+                item.sku.clone(),
+                quantity,
+                product.stock_of(item.sku.as_deref()).unwrap_or_default(),
+                MovementReason::Released,
+            );
+            entry.order_id = Some(order_id);
+            entries.push(entry);
+        }
+    }
+
+    record_movements(db, entries).await
+}
+
+/// Marks an order's reservation as used, the stock stays sold.
+pub async fn commit_reservation(db: &Database, order_id: ObjectId) -> Result<(), AppError> {
+    reservations(db)
+        .update_one(
+            doc! {"order_id": order_id, "status": ReservationStatus::Active.as_str()},
+            doc! {"$set": {
+                "status": ReservationStatus::Committed.as_str(),
+                "closed_at": DateTime::now(),
+            }},
+        )
+        .await?;
+    Ok(())
+}
+
+/// Keeps an order's stock held for another `minutes`, used once its payment
+/// has been authorised and only waits for an admin to capture it.
+pub async fn extend_reservation(
+    db: &Database,
+    order_id: ObjectId,
+    minutes: i64,
+) -> Result<(), AppError> {
+    let expires_at =
+        DateTime::from_millis(DateTime::now().timestamp_millis() + minutes * 60 * 1000);
+
+    reservations(db)
+        .update_one(
+            doc! {"order_id": order_id, "status": ReservationStatus::Active.as_str()},
+            doc! {"$max": {"expires_at": expires_at}},
+        )
+        .await?;
+    Ok(())
+}
+
+/// Cancels the orders whose reservation ran out, which releases their stock.
+/// An order that got paid in the meantime keeps its stock instead, one paid
+/// after it was cancelled here is refunded by the payment webhook. An order
+/// with an authorised payment waiting to be captured is not cancelled.
+pub async fn release_expired_reservations(db: &Database) -> Result<(), AppError> {
+    let mut cursor = reservations(db)
+        .find(doc! {
+            "status": ReservationStatus::Active.as_str(),
+            "expires_at": {"$lte": DateTime::now()},
+        })
+        .await?;
+
+    let mut expired = vec![];
+    while cursor.advance().await? {
+        expired.push(cursor.deserialize_current()?);
+    }
+
+    let orders: Collection<Order> = db.collection("orders");
+
+    for reservation in expired {
+        let authorized = orders
+            .find_one(doc! {
+                "_id": reservation.order_id,
+                "status": OrderStatus::Pending.as_str(),
+                "payment.status": "requires_capture",
+            })
+            .await?
+            .is_some();
+        if authorized {
+            extend_reservation(db, reservation.order_id, AUTHORIZED_HOLD_MINUTES).await?;
+            continue;
+        }
+
+        let result = transition_order(
+            db,
+            reservation.order_id,
+            OrderStatus::Cancelled,
+            None,
+            Some("stock reservation expired".to_string()),
+        )
+        .await;
+
+        match result {
+            Ok(_) => {}
+            Err(AppError::Conflict(_)) => commit_reservation(db, reservation.order_id).await?,
+            // the order is gone, nothing will ever pay for this stock
+            Err(AppError::NotFound(_)) => release_reservation(db, reservation.order_id).await?,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(())
+}
+
+pub fn spawn_reservation_sweeper(db: Database) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(RESERVATION_SWEEP_SECONDS));
+        loop {
+            interval.tick().await;
+            if let Err(e) = release_expired_reservations(&db).await {
+                tracing::error!("failed to release expired reservations: {:?}", e);
+            }
+        }
+    });
+}
+
+/// Quantities held by active reservations of a product, keyed by variant sku.
+async fn reserved_quantities(
+    db: &Database,
+    product_id: ObjectId,
+) -> Result<HashMap<Option<String>, i64>, AppError> {
+    let pipeline = vec![
+        doc! {"$match": {"status": ReservationStatus::Active.as_str(), "items.product_id": product_id}},
+        doc! {"$unwind": "$items"},
+        doc! {"$match": {"items.product_id": product_id}},
+        doc! {"$group": {"_id": "$items.sku", "reserved": {"$sum": "$items.quantity"}}},
+    ];
+
+    let mut cursor = reservations(db).aggregate(pipeline).await?;
+
+    let mut reserved = HashMap::new();
+    while cursor.advance().await? {
+        let result = cursor.deserialize_current()?;
+        let sku = result.get_str("_id").ok().map(|sku| sku.to_string());
+        let quantity = result
+            .get("reserved")
+            .and_then(|value| value.as_i64().or(value.as_i32().map(i64::from)))
+            .unwrap_or(0);
+        reserved.insert(sku, quantity);
+    }
+
+    Ok(reserved)
+}
+
+#[debug_handler]
+pub async fn get_stock(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let product_id = parse_object_id(id)?;
+
+    let product = products(&app_state.db)
+        .find_one(doc! {"_id": product_id})
+        .await?
+        .ok_or_else(|| AppError::NotFound("Product not found".to_string()))?;
+
+    let mut reserved = reserved_quantities(&app_state.db, product_id).await?;
+
+    let variants = product
+        .variants
+        .into_iter()
+        .map(|variant| StockLevel {
+            reserved: reserved.remove(&Some(variant.sku.clone())).unwrap_or(0),
+            sku: variant.sku,
+            stock: variant.stock,
+        })
+        .collect::<Vec<StockLevel>>();
+
+    Ok(Json(ProductStock {
+        product_id,
+        stock: product.stock,
+        reserved: reserved.remove(&None).unwrap_or(0),
+        variants,
+    }))
+}
+
+#[debug_handler]
+pub async fn adjust_stock(
+    State(app_state): State<Arc<AppState>>,
+    Extension(admin): Extension<User>,
+    Path(id): Path<String>,
+    Json(input): Json<StockAdjustment>,
+) -> Result<impl IntoResponse, AppError> {
+    let product_id = parse_object_id(id)?;
+
+    if input.delta == 0 {
+        return Err(AppError::BadRequest("delta must not be zero".to_string()));
+    }
+
+    // a negative adjustment only matches while it leaves the stock at zero or above
+    let minimum = (input.delta < 0).then_some(-input.delta);
+    let (filter, field) = stock_counter(product_id, input.sku.as_deref(), minimum);
+
+    let updated = products(&app_state.db)
+        .find_one_and_update(filter, doc! {"$inc": {field: input.delta}})
+        .return_document(ReturnDocument::After)
+        .await?;
+
+    let product = match updated {
+        Some(product) => product,
+        None => {
+            let product = products(&app_state.db)
+                .find_one(doc! {"_id": product_id})
+                .await?
+                .ok_or_else(|| AppError::NotFound("Product not found".to_string()))?;
+
+            if product.stock_of(input.sku.as_deref()).is_none() {
+                return Err(AppError::NotFound("Variant not found".to_string()));
+            }
+
+            return Err(AppError::Conflict("stock cannot go below zero".to_string()));
+        }
+    };
+
+    let stock_after = product.stock_of(input.sku.as_deref()).unwrap_or_default();
+    let mut entry = StockMovement::new(
+        product_id,
+        input.sku,
+        input.delta,
+        stock_after,
+        MovementReason::Adjustment,
+    );
+    entry.changed_by = admin.id;
+    entry.note = input.note;
+
+    record_movements(&app_state.db, vec![entry.clone()]).await?;
+
+    Ok(Json(entry))
+}
+
+#[debug_handler]
+pub async fn get_stock_movements(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(query): Query<MovementQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let product_id = parse_object_id(id)?;
+    let limit = query.limit.unwrap_or(DEFAULT_MOVEMENT_LIMIT).clamp(1, 500);
+
+    let mut filter = doc! {"product_id": product_id};
+    if let Some(sku) = query.sku {
+        filter.insert("sku", sku);
+    }
+
+    let mut cursor = movements(&app_state.db)
+        .find(filter)
+        .sort(doc! {"created_at": -1})
+        .limit(limit)
+        .await?;
+
+    let mut result = vec![];
+    while cursor.advance().await? {
+        result.push(cursor.deserialize_current()?);
+    }
+
+    Ok(Json(result))
+}
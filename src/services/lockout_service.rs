@@ -0,0 +1,77 @@
+use mongodb::{
+    bson::{doc, DateTime},
+    options::ReturnDocument,
+    Collection, Database,
+};
+
+use crate::{errors::app_error::AppError, models::auth_model::LoginFailures};
+
+/// Failures allowed before the account is locked at all.
+const FREE_ATTEMPTS: u32 = 5;
+/// The first lock lasts this long and doubles with every further failure.
+const BASE_LOCK_SECONDS: i64 = 30;
+const MAX_LOCK_SECONDS: i64 = 60 * 60;
+
+fn login_failures(db: &Database) -> Collection<LoginFailures> {
+    db.collection("login_failures")
+}
+
+fn seconds_until(time: DateTime) -> u64 {
+    let millis = time.timestamp_millis() - DateTime::now().timestamp_millis();
+    (millis.max(0) as u64).div_ceil(1000).max(1)
+}
+
+/// Fails with `TooManyRequests` while `email` is locked, whatever the
+/// password, so a lock can't be used to confirm a guess.
+pub async fn check_lockout(db: &Database, email: &str) -> Result<(), AppError> {
+    let locked = login_failures(db)
+        .find_one(doc! {"_id": email, "locked_until": {"$gt": DateTime::now()}})
+        .await?;
+
+    match locked.and_then(|failures| failures.locked_until) {
+        Some(until) => Err(AppError::TooManyRequests(seconds_until(until))),
+        None => Ok(()),
+    }
+}
+
+/// Counts a failed attempt and locks the account once it has used up its
+/// free attempts. Returns `TooManyRequests` if this failure locked it.
+pub async fn record_failure(db: &Database, email: &str) -> Result<(), AppError> {
+    let now = DateTime::now();
+
+    let failures = login_failures(db)
+        .find_one_and_update(
+            doc! {"_id": email},
+            doc! {"$inc": {"failures": 1}, "$set": {"last_failure_at": now}},
+        )
+        .upsert(true)
+        .return_document(ReturnDocument::After)
+        .await?
+        .map(|record| record.failures)
+        .unwrap_or(1);
+
+    if failures < FREE_ATTEMPTS {
+        return Ok(());
+    }
+
+    let exponent = (failures - FREE_ATTEMPTS).min(16);
+    let lock_seconds = (BASE_LOCK_SECONDS << exponent).min(MAX_LOCK_SECONDS);
+    let until = DateTime::from_millis(now.timestamp_millis() + lock_seconds * 1000);
+
+    login_failures(db)
+        .update_one(doc! {"_id": email}, doc! {"$set": {"locked_until": until}})
+        .await?;
+
+    tracing::warn!(
+        "{} locked for {}s after {} failed logins",
+        email,
+        lock_seconds,
+        failures
+    );
+    Err(AppError::TooManyRequests(lock_seconds as u64))
+}
+
+pub async fn clear_failures(db: &Database, email: &str) -> Result<(), AppError> {
+    login_failures(db).delete_one(doc! {"_id": email}).await?;
+    Ok(())
+}
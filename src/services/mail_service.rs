@@ -0,0 +1,116 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use axum_macros::debug_handler;
+use mongodb::bson::{doc, DateTime};
+
+use crate::{
+    config::app_state::AppState,
+    errors::app_error::AppError,
+    mail::{
+        outbox::outbox,
+        templates::{render, EmailTemplate, Locale, TemplateData},
+    },
+    models::outbox_model::{OutboxEmailView, OutboxQuery, OutboxStatus, PreviewQuery},
+    utils::{
+        pagination::{find_page, Keyset, Page, PageRequest},
+        parse_id::parse_object_id,
+    },
+};
+
+/// Queued, sent and dead emails, newest first.
+#[debug_handler]
+pub async fn get_outbox(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<OutboxQuery>,
+) -> Result<Json<Page<OutboxEmailView>>, AppError> {
+    let request = PageRequest::new(Keyset::NEWEST, query.cursor.as_deref(), query.limit)?;
+
+    let filter = match query.status {
+        Some(status) => doc! {"status": status.as_str()},
+        None => doc! {},
+    };
+    let page = find_page(&outbox(&app_state.db), filter, &request).await?;
+
+    Ok(Json(page.map(OutboxEmailView::from)))
+}
+
+/// Puts a dead email back in the queue with a fresh set of attempts.
+#[debug_handler]
+pub async fn retry_outbox_email(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let email_id = parse_object_id(id)?;
+
+    let result = outbox(&app_state.db)
+        .update_one(
+            doc! {"_id": email_id, "status": OutboxStatus::Dead.as_str()},
+            doc! {"$set": {
+                "status": OutboxStatus::Pending.as_str(),
+                "attempts": 0,
+                "next_attempt_at": DateTime::now(),
+            }},
+        )
+        .await?;
+
+    if result.matched_count == 0 {
+        return Err(AppError::NotFound("No dead email with this id".to_string()));
+    }
+
+    Ok(Json("email queued again"))
+}
+
+#[debug_handler]
+pub async fn get_captured_mail(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let messages = app_state.mailer.captured().ok_or_else(|| {
+        AppError::NotFound(format!(
+            "the {} mail transport does not capture emails",
+            app_state.mailer.name()
+        ))
+    })?;
+
+    Ok(Json(messages))
+}
+
+#[debug_handler]
+pub async fn list_templates() -> impl IntoResponse {
+    Json(serde_json::json!({
+        "templates": EmailTemplate::ALL.map(|template| template.name()),
+        "locales": Locale::ALL.map(|locale| locale.code()),
+    }))
+}
+
+/// Renders a template with sample data, as HTML or as its plain text part.
+#[debug_handler]
+pub async fn preview_template(
+    State(app_state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Query(query): Query<PreviewQuery>,
+) -> Result<Response, AppError> {
+    let template = EmailTemplate::from_name(&name)
+        .ok_or_else(|| AppError::NotFound(format!("No email template named `{}`", name)))?;
+    let locale = Locale::parse(query.locale.as_deref().unwrap_or_default());
+
+    let rendered = render(
+        &app_state.settings.mail.brand,
+        locale,
+        "Jane Doe",
+        &TemplateData::sample(template),
+    );
+
+    match query.format.as_deref() {
+        None | Some("html") => Ok(Html(rendered.html).into_response()),
+        Some("text") => Ok(rendered.text.into_response()),
+        Some(other) => Err(AppError::BadRequest(format!(
+            "unknown preview format `{}`, expected html or text",
+            other
+        ))),
+    }
+}
@@ -1,4 +1,16 @@
+pub mod api_key_service;
 pub mod auth_service;
+pub mod brand_service;
 pub mod cart_service;
+pub mod category_service;
+pub mod inventory_service;
+pub mod lockout_service;
+pub mod mail_service;
+pub mod order_service;
+pub mod payment_service;
 pub mod product_service;
+pub mod review_service;
+pub mod role_service;
+pub mod session_service;
+pub mod two_factor_service;
 pub mod user_service;
@@ -0,0 +1,347 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use axum_macros::debug_handler;
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime},
+    options::ReturnDocument,
+    Collection, Database,
+};
+
+use crate::{
+    config::app_state::AppState,
+    errors::app_error::AppError,
+    mail::templates::{send_template, Locale, OrderLine, TemplateData},
+    models::{
+        cart_model::Cart,
+        inventory_model::ReservedItem,
+        order_model::{Order, OrderItem, OrderStatus, StatusChange, UpdateOrderStatus},
+        user_model::User,
+    },
+    services::{
+        cart_service::{current_user_id, priced_cart},
+        inventory_service::{commit_reservation, release_reservation, reserve_stock},
+    },
+    utils::{
+        pagination::{find_page, Keyset, Page, PageQuery, PageRequest},
+        parse_id::parse_object_id,
+    },
+};
+
+/// Newest orders first.
+const BY_CREATED_AT: Keyset = Keyset::by("created_at", "created_at", false);
+
+fn orders(db: &Database) -> Collection<Order> {
+    db.collection("orders")
+}
+
+/// Moves an order to `next` if the state machine allows it from the state
+/// the order is currently in. The check and the write happen in a single
+/// update so two concurrent transitions cannot both succeed. Asking for the
+/// state the order is already in is a no-op. Paying for an order keeps its
+/// reserved stock, cancelling it gives the stock back.
+pub async fn transition_order(
+    db: &Database,
+    order_id: ObjectId,
+    next: OrderStatus,
+    changed_by: Option<ObjectId>,
+    note: Option<String>,
+) -> Result<Order, AppError> {
+    let collection = orders(db);
+    let now = DateTime::now();
+
+    let allowed_from = next
+        .allowed_from()
+        .iter()
+        .map(|status| status.as_str())
+        .collect::<Vec<&str>>();
+
+    let change = StatusChange {
+        status: next,
+        changed_at: now,
+        changed_by,
+        note,
+    };
+
+    let updated = collection
+        .find_one_and_update(
+            doc! {"_id": order_id, "status": {"$in": allowed_from}},
+            doc! {
+                "$set": {"status": next.as_str(), "updated_at": now},
+                "$push": {"status_history": mongodb::bson::to_bson(&change)?},
+            },
+        )
+        .return_document(ReturnDocument::After)
+        .await?;
+
+    let order = match updated {
+        Some(order) => order,
+        None => {
+            let order = collection
+                .find_one(doc! {"_id": order_id})
+                .await?
+                .ok_or_else(|| AppError::NotFound("Order not found".to_string()))?;
+
+            if order.status != next {
+                return Err(AppError::Conflict(format!(
+                    "order cannot move from {} to {}",
+                    order.status.as_str(),
+                    next.as_str()
+                )));
+            }
+            order
+        }
+    };
+
+    match order.status {
+        OrderStatus::Paid => commit_reservation(db, order.id).await?,
+        OrderStatus::Cancelled => release_reservation(db, order.id).await?,
+        _ => {}
+    }
+
+    Ok(order)
+}
+
+/// Emails the customer about their order. The order has already changed by
+/// then, so a failure is only logged.
+async fn notify_customer(app_state: &AppState, user: &User, data: TemplateData) {
+    if let Err(e) = send_template(
+        &app_state.db,
+        &app_state.settings.mail.brand,
+        Locale::parse(user.locale.as_deref().unwrap_or_default()),
+        &user.name,
+        &user.email,
+        data,
+    )
+    .await
+    {
+        tracing::error!("failed to queue order email for {}: {:?}", user.email, e);
+    }
+}
+
+pub async fn find_user_order(
+    db: &Database,
+    order_id: ObjectId,
+    user_id: ObjectId,
+) -> Result<Order, AppError> {
+    orders(db)
+        .find_one(doc! {"_id": order_id, "user_id": user_id})
+        .await?
+        .ok_or_else(|| AppError::NotFound("Order not found".to_string()))
+}
+
+#[debug_handler]
+pub async fn checkout(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, AppError> {
+    let user_id = current_user_id(&user)?;
+
+    let cart_collection: Collection<Cart> = app_state.db.collection("cart");
+    let cart = cart_collection
+        .find_one(doc! {"user_id": user_id})
+        .await?
+        .ok_or_else(|| AppError::BadRequest("your cart is empty".to_string()))?;
+
+    // prices are snapshotted here, later product edits don't change the order
+    let priced = priced_cart(&app_state.db, cart).await?;
+
+    if priced.items.is_empty() {
+        return Err(AppError::BadRequest("your cart is empty".to_string()));
+    }
+
+    let reserved_items = priced
+        .items
+        .iter()
+        .filter_map(|line| {
+            line.product._id.map(|product_id| ReservedItem {
+                product_id,
+                sku: line.sku.clone(),
+                quantity: line.quantity,
+            })
+        })
+        .collect::<Vec<ReservedItem>>();
+
+    let items = priced
+        .items
+        .into_iter()
+        .map(|line| OrderItem {
+            image_url: line.product.first_image(line.sku.as_deref()),
+            product_id: line.product_id,
+            sku: line.sku,
+            title: line.product.title,
+            unit_price: line.unit_price,
+            quantity: line.quantity,
+            line_total: line.line_total,
+        })
+        .collect::<Vec<OrderItem>>();
+
+    let now = DateTime::now();
+    let order = Order {
+        id: ObjectId::new(),
+        user_id,
+        items,
+        total_price: priced.total_price,
+        status: OrderStatus::Pending,
+        status_history: vec![StatusChange {
+            status: OrderStatus::Pending,
+            changed_at: now,
+            changed_by: Some(user_id),
+            note: None,
+        }],
+        payment: None,
+        created_at: now,
+        updated_at: now,
+    };
+
+    // stock is taken before the order exists so a failed reservation leaves nothing behind
+    reserve_stock(&app_state.db, order.id, user_id, reserved_items).await?;
+
+    if let Err(e) = orders(&app_state.db).insert_one(&order).await {
+        release_reservation(&app_state.db, order.id).await?;
+        return Err(e.into());
+    }
+
+    cart_collection
+        .update_one(
+            doc! {"user_id": user_id},
+            doc! {"$set": {"products": [], "total_price": 0.0}},
+        )
+        .await?;
+
+    let confirmation = TemplateData::OrderConfirmation {
+        order_id: order.id.to_hex(),
+        lines: order
+            .items
+            .iter()
+            .map(|item| OrderLine {
+                title: item.title.clone(),
+                quantity: item.quantity,
+                line_total: item.line_total,
+            })
+            .collect(),
+        total: order.total_price,
+    };
+    notify_customer(&app_state, &user, confirmation).await;
+
+    Ok((StatusCode::CREATED, Json(order)))
+}
+
+#[debug_handler]
+pub async fn get_my_orders(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<PageQuery>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Page<Order>>, AppError> {
+    let user_id = current_user_id(&user)?;
+
+    let request = PageRequest::new(BY_CREATED_AT, query.cursor.as_deref(), query.limit)?;
+
+    let page = find_page(&orders(&app_state.db), doc! {"user_id": user_id}, &request).await?;
+
+    Ok(Json(page))
+}
+
+#[debug_handler]
+pub async fn get_my_order(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let user_id = current_user_id(&user)?;
+    let order_id = parse_object_id(id)?;
+
+    Ok(Json(
+        find_user_order(&app_state.db, order_id, user_id).await?,
+    ))
+}
+
+#[debug_handler]
+pub async fn cancel_order(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let user_id = current_user_id(&user)?;
+    let order_id = parse_object_id(id)?;
+
+    // make sure the order belongs to the caller before touching it
+    find_user_order(&app_state.db, order_id, user_id).await?;
+
+    let order = transition_order(
+        &app_state.db,
+        order_id,
+        OrderStatus::Cancelled,
+        Some(user_id),
+        Some("cancelled by customer".to_string()),
+    )
+    .await?;
+
+    Ok(Json(order))
+}
+
+#[debug_handler]
+pub async fn get_all_orders(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<PageQuery>,
+) -> Result<Json<Page<Order>>, AppError> {
+    let request = PageRequest::new(BY_CREATED_AT, query.cursor.as_deref(), query.limit)?;
+
+    let page = find_page(&orders(&app_state.db), doc! {}, &request).await?;
+
+    Ok(Json(page))
+}
+
+/// Moves an order through fulfilment. Paid and refunded are left to the
+/// payment endpoints and webhook, which move the money as well.
+#[debug_handler]
+pub async fn update_order_status(
+    State(app_state): State<Arc<AppState>>,
+    Extension(admin): Extension<User>,
+    Path(id): Path<String>,
+    Json(input): Json<UpdateOrderStatus>,
+) -> Result<impl IntoResponse, AppError> {
+    let order_id = parse_object_id(id)?;
+
+    // these follow money moving at the provider, never an admin's say-so
+    match input.status {
+        OrderStatus::Paid => {
+            return Err(AppError::BadRequest(
+                "an order becomes paid when its payment is captured, use /api/payment/orders/{id}/capture"
+                    .to_string(),
+            ))
+        }
+        OrderStatus::Refunded => {
+            return Err(AppError::BadRequest(
+                "an order becomes refunded when its payment is refunded, use /api/payment/orders/{id}/refund"
+                    .to_string(),
+            ))
+        }
+        _ => {}
+    }
+
+    let previous = orders(&app_state.db)
+        .find_one(doc! {"_id": order_id})
+        .await?
+        .ok_or_else(|| AppError::NotFound("Order not found".to_string()))?;
+
+    let order =
+        transition_order(&app_state.db, order_id, input.status, admin.id, input.note).await?;
+
+    if order.status == OrderStatus::Shipped && previous.status != OrderStatus::Shipped {
+        let users: Collection<User> = app_state.db.collection("users");
+        if let Some(customer) = users.find_one(doc! {"_id": order.user_id}).await? {
+            let notice = TemplateData::ShippingNotice {
+                order_id: order.id.to_hex(),
+            };
+            notify_customer(&app_state, &customer, notice).await;
+        }
+    }
+
+    Ok(Json(order))
+}
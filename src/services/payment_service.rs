@@ -0,0 +1,424 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Extension, Json,
+};
+use axum_macros::debug_handler;
+use mongodb::{
+    bson::{self, doc, oid::ObjectId, DateTime, Document},
+    options::ReturnDocument,
+    Collection, Database,
+};
+
+use crate::{
+    config::app_state::AppState,
+    database::mongo::is_duplicate_key_error,
+    errors::app_error::{AppError, FieldError},
+    models::{
+        order_model::{Order, OrderStatus, PaymentInfo},
+        payment_model::{PaymentEvent, RefundInput},
+        user_model::User,
+    },
+    payments::provider::{WebhookEvent, WebhookEventKind},
+    services::{
+        cart_service::current_user_id,
+        inventory_service::{extend_reservation, AUTHORIZED_HOLD_MINUTES},
+        order_service::{find_user_order, transition_order},
+    },
+    utils::parse_id::parse_object_id,
+};
+
+const SIGNATURE_HEADER: &str = "Stripe-Signature";
+
+fn orders(db: &Database) -> Collection<Order> {
+    db.collection("orders")
+}
+
+async fn find_order(db: &Database, order_id: ObjectId) -> Result<Order, AppError> {
+    orders(db)
+        .find_one(doc! {"_id": order_id})
+        .await?
+        .ok_or_else(|| AppError::NotFound("Order not found".to_string()))
+}
+
+async fn set_payment_status(
+    db: &Database,
+    order_id: ObjectId,
+    status: &str,
+) -> Result<(), AppError> {
+    orders(db)
+        .update_one(
+            doc! {"_id": order_id, "payment": {"$ne": null}},
+            doc! {"$set": {"payment.status": status, "updated_at": DateTime::now()}},
+        )
+        .await?;
+    Ok(())
+}
+
+/// Stores what has been refunded so far and tells whether that covers the
+/// whole payment. `update` raises `payment.refunded_amount`.
+async fn record_refund(
+    db: &Database,
+    order_id: ObjectId,
+    update: Document,
+) -> Result<bool, AppError> {
+    let order = orders(db)
+        .find_one_and_update(doc! {"_id": order_id, "payment": {"$ne": null}}, update)
+        .return_document(ReturnDocument::After)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Order not found".to_string()))?;
+
+    let fully_refunded = order
+        .payment
+        .as_ref()
+        .is_some_and(|payment| payment.refunded_amount >= payment.amount);
+    let status = if fully_refunded {
+        "refunded"
+    } else {
+        "partially_refunded"
+    };
+    set_payment_status(db, order_id, status).await?;
+
+    Ok(fully_refunded)
+}
+
+/// Webhooks can arrive after an admin already moved the order on, so a
+/// transition the state machine refuses is logged and acknowledged rather
+/// than failed, otherwise the provider would keep redelivering it.
+async fn apply_transition(
+    db: &Database,
+    order_id: ObjectId,
+    next: OrderStatus,
+    note: String,
+) -> Result<(), AppError> {
+    match transition_order(db, order_id, next, None, Some(note)).await {
+        Ok(_) => Ok(()),
+        Err(AppError::Conflict(message)) => {
+            tracing::warn!("ignoring webhook for order {}: {}", order_id, message);
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// A payment that lands after the order was cancelled, typically by the
+/// reservation sweeper, is given back right away since the stock is gone.
+/// An error leaves the event unprocessed so the refund is tried again on
+/// redelivery.
+async fn refund_cancelled_order(
+    app_state: &AppState,
+    order: &Order,
+    event: &WebhookEvent,
+) -> Result<(), AppError> {
+    let Some(payment) = &order.payment else {
+        tracing::error!(
+            "payment event {} succeeded for cancelled order {} without payment info",
+            event.id,
+            order.id
+        );
+        return Ok(());
+    };
+
+    let remaining = payment.amount - payment.refunded_amount;
+    if remaining <= 0 {
+        return Ok(());
+    }
+
+    let refund = app_state
+        .payments
+        .refund(&payment.intent_id, Some(remaining))
+        .await?;
+    tracing::warn!(
+        "order {} was paid after being cancelled, refunded {} with {}",
+        order.id,
+        refund.amount,
+        refund.id
+    );
+
+    record_refund(
+        &app_state.db,
+        order.id,
+        doc! {
+            "$inc": {"payment.refunded_amount": refund.amount},
+            "$set": {"updated_at": DateTime::now()},
+        },
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn handle_event(app_state: &AppState, event: &WebhookEvent) -> Result<(), AppError> {
+    let db = &app_state.db;
+    let order_id = event
+        .order_id
+        .as_deref()
+        .and_then(|id| ObjectId::parse_str(id).ok());
+
+    let filter = match (order_id, &event.payment_intent_id) {
+        (Some(order_id), _) => doc! {"_id": order_id},
+        (None, Some(intent_id)) => doc! {"payment.intent_id": intent_id},
+        (None, None) => return Ok(()),
+    };
+
+    let order = match orders(db).find_one(filter).await? {
+        Some(order) => order,
+        None => {
+            tracing::warn!("payment event {} does not match any order", event.id);
+            return Ok(());
+        }
+    };
+
+    match &event.kind {
+        WebhookEventKind::PaymentAuthorized => {
+            set_payment_status(db, order.id, "requires_capture").await?;
+            // the stock must not be released while the capture is pending
+            extend_reservation(db, order.id, AUTHORIZED_HOLD_MINUTES).await
+        }
+        WebhookEventKind::PaymentSucceeded => {
+            set_payment_status(db, order.id, "succeeded").await?;
+            if order.status == OrderStatus::Cancelled {
+                return refund_cancelled_order(app_state, &order, event).await;
+            }
+            apply_transition(
+                db,
+                order.id,
+                OrderStatus::Paid,
+                format!("payment event {}", event.id),
+            )
+            .await
+        }
+        WebhookEventKind::PaymentFailed => set_payment_status(db, order.id, "failed").await,
+        WebhookEventKind::RefundSucceeded => {
+            let Some(amount_refunded) = event.amount_refunded else {
+                tracing::warn!("refund event {} has no amount_refunded", event.id);
+                return Ok(());
+            };
+
+            // the charge reports the running total, a refund made from the
+            // admin endpoint has already been counted
+            let fully_refunded = record_refund(
+                db,
+                order.id,
+                doc! {
+                    "$max": {"payment.refunded_amount": amount_refunded},
+                    "$set": {"updated_at": DateTime::now()},
+                },
+            )
+            .await?;
+
+            if !fully_refunded {
+                return Ok(());
+            }
+            apply_transition(
+                db,
+                order.id,
+                OrderStatus::Refunded,
+                format!("payment event {}", event.id),
+            )
+            .await
+        }
+        WebhookEventKind::Other(kind) => {
+            tracing::debug!("ignoring payment event {} of type {}", event.id, kind);
+            Ok(())
+        }
+    }
+}
+
+#[debug_handler]
+pub async fn create_payment_intent(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let user_id = current_user_id(&user)?;
+    let order_id = parse_object_id(id)?;
+
+    let order = find_user_order(&app_state.db, order_id, user_id).await?;
+
+    if order.status != OrderStatus::Pending {
+        return Err(AppError::Conflict(
+            "order is not awaiting payment".to_string(),
+        ));
+    }
+
+    // hand back the live intent so a second checkout can't authorise twice
+    if let Some(payment) = order
+        .payment
+        .as_ref()
+        .filter(|payment| !matches!(payment.status.as_str(), "failed" | "canceled"))
+    {
+        let intent = app_state.payments.retrieve(&payment.intent_id).await?;
+        return Ok(Json(intent));
+    }
+
+    let currency = &app_state.settings.payments.currency;
+    let intent = app_state.payments.create_intent(&order, currency).await?;
+
+    let payment = PaymentInfo {
+        provider: app_state.payments.name().to_string(),
+        intent_id: intent.id.clone(),
+        amount: intent.amount,
+        currency: intent.currency.clone(),
+        status: intent.status.clone(),
+        refunded_amount: 0,
+    };
+
+    orders(&app_state.db)
+        .update_one(
+            doc! {"_id": order.id},
+            doc! {"$set": {"payment": bson::to_bson(&payment)?, "updated_at": DateTime::now()}},
+        )
+        .await?;
+
+    Ok(Json(intent))
+}
+
+#[debug_handler]
+pub async fn capture_payment(
+    State(app_state): State<Arc<AppState>>,
+    Extension(admin): Extension<User>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let order_id = parse_object_id(id)?;
+    let order = find_order(&app_state.db, order_id).await?;
+
+    let payment = order
+        .payment
+        .ok_or_else(|| AppError::BadRequest("order has no payment".to_string()))?;
+
+    // check before charging so a cancelled order's card is never captured
+    if !OrderStatus::Paid.allowed_from().contains(&order.status) {
+        return Err(AppError::Conflict(format!(
+            "order cannot move from {} to {}",
+            order.status.as_str(),
+            OrderStatus::Paid.as_str()
+        )));
+    }
+
+    let intent = app_state.payments.capture(&payment.intent_id).await?;
+
+    set_payment_status(&app_state.db, order_id, &intent.status).await?;
+
+    if intent.status == "succeeded" {
+        transition_order(
+            &app_state.db,
+            order_id,
+            OrderStatus::Paid,
+            admin.id,
+            Some(format!("captured {}", intent.id)),
+        )
+        .await?;
+    }
+
+    Ok(Json(find_order(&app_state.db, order_id).await?))
+}
+
+#[debug_handler]
+pub async fn refund_payment(
+    State(app_state): State<Arc<AppState>>,
+    Extension(admin): Extension<User>,
+    Path(id): Path<String>,
+    Json(input): Json<RefundInput>,
+) -> Result<impl IntoResponse, AppError> {
+    let order_id = parse_object_id(id)?;
+    let order = find_order(&app_state.db, order_id).await?;
+
+    let payment = order
+        .payment
+        .ok_or_else(|| AppError::BadRequest("order has no payment".to_string()))?;
+
+    // check before moving money so a refund never happens without the order following
+    if !OrderStatus::Refunded.allowed_from().contains(&order.status) {
+        return Err(AppError::Conflict(format!(
+            "order cannot move from {} to {}",
+            order.status.as_str(),
+            OrderStatus::Refunded.as_str()
+        )));
+    }
+
+    let remaining = payment.amount - payment.refunded_amount;
+    let amount = input.amount.unwrap_or(remaining);
+    if remaining <= 0 {
+        return Err(AppError::Conflict(
+            "the payment has already been refunded in full".to_string(),
+        ));
+    }
+    if !(1..=remaining).contains(&amount) {
+        return Err(AppError::Validation(vec![FieldError::new(
+            "amount",
+            format!("must be between 1 and {}", remaining),
+        )]));
+    }
+
+    let refund = app_state
+        .payments
+        .refund(&payment.intent_id, Some(amount))
+        .await?;
+
+    let fully_refunded = record_refund(
+        &app_state.db,
+        order_id,
+        doc! {
+            "$inc": {"payment.refunded_amount": refund.amount},
+            "$set": {"updated_at": DateTime::now()},
+        },
+    )
+    .await?;
+
+    if fully_refunded {
+        transition_order(
+            &app_state.db,
+            order_id,
+            OrderStatus::Refunded,
+            admin.id,
+            Some(format!("refund {}", refund.id)),
+        )
+        .await?;
+    }
+
+    Ok(Json(find_order(&app_state.db, order_id).await?))
+}
+
+#[debug_handler]
+pub async fn payment_webhook(
+    State(app_state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    let signature = headers
+        .get(SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .ok_or(AppError::InvalidSignature)?;
+
+    let event = app_state.payments.verify_webhook(&body, signature)?;
+
+    let events: Collection<PaymentEvent> = app_state.db.collection("payment_events");
+    let record = PaymentEvent {
+        id: event.id.clone(),
+        provider: app_state.payments.name().to_string(),
+        kind: format!("{:?}", event.kind),
+        received_at: DateTime::now(),
+    };
+
+    // the unique _id makes a redelivered event a no-op
+    match events.insert_one(&record).await {
+        Ok(_) => {}
+        Err(e) if is_duplicate_key_error(&e) => {
+            return Ok((StatusCode::OK, "event already processed"));
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    if let Err(e) = handle_event(&app_state, &event).await {
+        // forget the event so the provider's retry gets processed
+        events.delete_one(doc! {"_id": &event.id}).await?;
+        return Err(e);
+    }
+
+    Ok((StatusCode::OK, "event processed"))
+}
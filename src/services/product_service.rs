@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc, time::Duration};
 
 use axum::{
     extract::{Multipart, Path, Query, State},
@@ -8,34 +8,496 @@ use axum::{
 };
 use axum_macros::debug_handler;
 use mongodb::{
-    bson::{doc, oid::ObjectId},
-    Collection,
+    bson::{self, doc, oid::ObjectId, DateTime, Document},
+    options::ReturnDocument,
+    Collection, Database,
 };
+use serde::Deserialize;
 
 use crate::{
     config::app_state::AppState,
-    models::products_model::{ProductFilter, ProductPaginate, Products},
-    utils::s3::upload_single,
+    database::mongo::is_duplicate_key_error,
+    errors::app_error::{AppError, FieldError},
+    models::{
+        inventory_model::{MovementReason, StockMovement},
+        products_model::{
+            DeleteImageQuery, DeleteProductQuery, FacetCount, ImageLink, ImageLinkQuery,
+            PriceBucket, ProductFacets, ProductFilter, ProductListing, ProductSort, Products,
+            UpdateProduct,
+        },
+    },
+    services::{
+        brand_service::find_brand, category_service::find_category,
+        inventory_service::record_movements,
+    },
+    storage::object_storage::{object_key, ObjectStorage},
+    utils::{
+        pagination::{find_page, Keyset, Page, PageQuery, PageRequest},
+        parse_id::parse_object_id,
+    },
 };
 
+const IMAGE_LINK_TTL_SECONDS: u64 = 15 * 60;
+const MAX_IMAGE_LINK_TTL_SECONDS: u64 = 7 * 24 * 60 * 60;
+/// Search matches the structured filters are applied to.
+const SEARCH_CANDIDATE_LIMIT: usize = 1000;
+const MAX_SEARCH_LENGTH: usize = 100;
+/// Boundaries of the price facet, the last bucket is open ended.
+const PRICE_BUCKETS: [f64; 7] = [0.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0];
+
+const BY_RELEVANCE: Keyset = Keyset::by("relevance", "search_rank", true);
+const BY_PRICE_ASC: Keyset = Keyset::by("price_asc", "effective_price", true);
+const BY_PRICE_DESC: Keyset = Keyset::by("price_desc", "effective_price", false);
+const BY_RATING: Keyset = Keyset::by("rating", "rating_average", false);
+
+/// Brings the search index up to date after a write. The product is saved
+/// already, so a failure is only logged and the index catches up with the
+/// rebuild on the next start.
+async fn sync_search(app_state: &AppState, product: &Products) {
+    if let Err(e) = app_state.search.index(product).await {
+        tracing::error!("failed to index product {:?}: {:?}", product._id, e);
+    }
+}
+
+/// Indexes the products matching `filter` again, e.g. after the name of
+/// their category changed.
+pub async fn reindex_products(app_state: &AppState, filter: Document) -> Result<(), AppError> {
+    let collection: Collection<Products> = app_state.db.collection("products");
+    let mut cursor = collection
+        .find(doc! {"$and": [filter, {"deleted_at": null}]})
+        .await?;
+
+    while cursor.advance().await? {
+        sync_search(app_state, &cursor.deserialize_current()?).await;
+    }
+    Ok(())
+}
+
+async fn unlist_from_search(app_state: &AppState, product_id: ObjectId) {
+    if let Err(e) = app_state.search.remove(product_id).await {
+        tracing::error!(
+            "failed to remove product {} from search: {:?}",
+            product_id,
+            e
+        );
+    }
+}
+
+/// Checks the option axes and that every variant picks exactly one allowed
+/// value per axis, with unique skus and option combinations.
+fn validate_product(product: &Products) -> Vec<FieldError> {
+    let mut errors = vec![];
+
+    if product.stock < 0 {
+        errors.push(FieldError::new("stock", "stock cannot be negative"));
+    }
+
+    if product.price < 0.0 || product.offer_price.is_some_and(|price| price < 0.0) {
+        errors.push(FieldError::new("price", "price cannot be negative"));
+    }
+
+    let mut option_names = HashSet::new();
+    for option in &product.options {
+        if option.name.trim().is_empty() || option.values.is_empty() {
+            errors.push(FieldError::new(
+                "options",
+                "every option needs a name and at least one value",
+            ));
+        }
+        if !option_names.insert(option.name.as_str()) {
+            errors.push(FieldError::new(
+                "options",
+                format!("option `{}` is listed twice", option.name),
+            ));
+        }
+    }
+
+    if !product.variants.is_empty() && product.options.is_empty() {
+        errors.push(FieldError::new(
+            "options",
+            "options are required when variants are given",
+        ));
+    }
+
+    let mut skus = HashSet::new();
+    let mut combinations = HashSet::new();
+    for variant in &product.variants {
+        if variant.sku.trim().is_empty() {
+            errors.push(FieldError::new("variants", "every variant needs a sku"));
+        } else if !skus.insert(variant.sku.as_str()) {
+            errors.push(FieldError::new(
+                "variants",
+                format!("sku `{}` is used twice", variant.sku),
+            ));
+        }
+
+        if variant.stock < 0 {
+            errors.push(FieldError::new(
+                "variants",
+                format!("stock of `{}` cannot be negative", variant.sku),
+            ));
+        }
+
+        if variant.price.is_some_and(|price| price < 0.0)
+            || variant.offer_price.is_some_and(|price| price < 0.0)
+        {
+            errors.push(FieldError::new(
+                "variants",
+                format!("price of `{}` cannot be negative", variant.sku),
+            ));
+        }
+
+        let valid_options = variant.options.len() == product.options.len()
+            && product.options.iter().all(|option| {
+                variant
+                    .options
+                    .get(&option.name)
+                    .is_some_and(|value| option.values.contains(value))
+            });
+
+        if !valid_options {
+            errors.push(FieldError::new(
+                "variants",
+                format!(
+                    "`{}` must pick one listed value for every option",
+                    variant.sku
+                ),
+            ));
+        } else if !combinations.insert(&variant.options) {
+            errors.push(FieldError::new(
+                "variants",
+                format!("`{}` repeats the options of another variant", variant.sku),
+            ));
+        }
+    }
+
+    errors
+}
+
+/// Copies name and path of the category `product` refers to onto it.
+async fn link_category(db: &Database, product: &mut Products) -> Result<(), AppError> {
+    let category = match product.category_id {
+        Some(id) => find_category(db, id).await?,
+        None => {
+            return Err(AppError::Validation(vec![FieldError::new(
+                "category_id",
+                "a category is required",
+            )]))
+        }
+    }
+    .ok_or_else(|| {
+        AppError::Validation(vec![FieldError::new(
+            "category_id",
+            "category does not exist",
+        )])
+    })?;
+
+    product.category_path = category.path_below();
+    product.category = category.name;
+    Ok(())
+}
+
+async fn link_brand(db: &Database, product: &mut Products) -> Result<(), AppError> {
+    let brand = match product.brand_id {
+        Some(id) => find_brand(db, id).await?,
+        None => {
+            return Err(AppError::Validation(vec![FieldError::new(
+                "brand_id",
+                "a brand is required",
+            )]))
+        }
+    }
+    .ok_or_else(|| {
+        AppError::Validation(vec![FieldError::new("brand_id", "brand does not exist")])
+    })?;
+
+    product.brand = brand.name;
+    Ok(())
+}
+
 pub async fn create_products(
     State(app_state): State<Arc<AppState>>,
     Json(mut data): Json<Products>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+) -> Result<impl IntoResponse, AppError> {
     let collection: Collection<Products> = app_state.db.collection("products");
 
+    let errors = validate_product(&data);
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+
+    link_category(&app_state.db, &mut data).await?;
+    link_brand(&app_state.db, &mut data).await?;
+
     let product_id = ObjectId::new();
 
     data._id = Some(product_id);
+    data.deleted_at = None;
+    data.rating_average = 0.0;
+    data.rating_count = 0;
+
+    let mut entries = vec![];
+    if data.stock > 0 {
+        entries.push(StockMovement::new(
+            product_id,
+            None,
+            data.stock,
+            data.stock,
+            MovementReason::Initial,
+        ));
+    }
+    for variant in data.variants.iter().filter(|variant| variant.stock > 0) {
+        entries.push(StockMovement::new(
+            product_id,
+            Some(variant.sku.clone()),
+            variant.stock,
+            variant.stock,
+            MovementReason::Initial,
+        ));
+    }
+
+    let result = match collection.insert_one(&data).await {
+        Ok(result) => result,
+        Err(e) if is_duplicate_key_error(&e) => {
+            return Err(AppError::Conflict(
+                "a variant sku is already used by another product".to_string(),
+            ));
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    record_movements(&app_state.db, entries).await?;
+    sync_search(&app_state, &data).await;
+
+    Ok(Json(result))
+}
+
+#[debug_handler]
+pub async fn update_product(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(input): Json<UpdateProduct>,
+) -> Result<impl IntoResponse, AppError> {
+    let product_id = parse_object_id(id)?;
+    let collection: Collection<Products> = app_state.db.collection("products");
+
+    let mut product = collection
+        .find_one(doc! {"_id": product_id, "deleted_at": null})
+        .await?
+        .ok_or_else(|| AppError::NotFound("Product not found".to_string()))?;
+
+    let mut filter = doc! {"_id": product_id, "deleted_at": null};
+    let mut set = Document::new();
+
+    if let Some(title) = input.title {
+        set.insert("title", &title);
+        product.title = title;
+    }
+    if let Some(description) = input.description {
+        set.insert("description", &description);
+        product.description = description;
+    }
+    if let Some(price) = input.price {
+        set.insert("price", price as f64);
+        product.price = price;
+    }
+    if let Some(offer_price) = input.offer_price {
+        set.insert("offer_price", offer_price.map(f64::from));
+        product.offer_price = offer_price;
+    }
+    if let Some(category_id) = input.category_id {
+        product.category_id = Some(parse_object_id(category_id)?);
+        link_category(&app_state.db, &mut product).await?;
+        set.insert("category_id", product.category_id);
+        set.insert("category", &product.category);
+        set.insert("category_path", &product.category_path);
+    }
+    if let Some(brand_id) = input.brand_id {
+        product.brand_id = Some(parse_object_id(brand_id)?);
+        link_brand(&app_state.db, &mut product).await?;
+        set.insert("brand_id", product.brand_id);
+        set.insert("brand", &product.brand);
+    }
+    if let Some(options) = input.options {
+        set.insert("options", bson::to_bson(&options)?);
+        product.options = options;
+    }
+    if let Some(mut variants) = input.variants {
+        // stock only moves through the inventory endpoints, kept variants
+        // hold on to theirs and new ones start empty
+        for variant in &mut variants {
+            variant.stock = product
+                .variant(&variant.sku)
+                .map(|existing| existing.stock)
+                .unwrap_or(0);
+        }
+
+        // only write if no reservation touched the variants in the meantime
+        filter.insert("variants", bson::to_bson(&product.variants)?);
+        set.insert("variants", bson::to_bson(&variants)?);
+        product.variants = variants;
+    }
+
+    if set.is_empty() {
+        return Err(AppError::BadRequest("nothing to update".to_string()));
+    }
+
+    let errors = validate_product(&product);
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+
+    let updated = collection
+        .find_one_and_update(filter, doc! {"$set": set})
+        .return_document(ReturnDocument::After)
+        .await;
+
+    match updated {
+        Ok(Some(product)) => {
+            sync_search(&app_state, &product).await;
+            Ok(Json(product))
+        }
+        Ok(None) => Err(AppError::Conflict(
+            "the product changed while updating it, please retry".to_string(),
+        )),
+        Err(e) if is_duplicate_key_error(&e) => Err(AppError::Conflict(
+            "a variant sku is already used by another product".to_string(),
+        )),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Removes an image from storage. Urls that point elsewhere, e.g. images
+/// added by hand, are only dropped from the product.
+async fn delete_image(storage: &dyn ObjectStorage, url: &str) -> Result<(), AppError> {
+    match storage.key_from_url(url) {
+        Some(key) => storage.delete(&key).await,
+        None => Ok(()),
+    }
+}
+
+/// Soft deletes a product unless `?hard=true` is given, which removes the
+/// document and its images from the bucket for good.
+#[debug_handler]
+pub async fn delete_product(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(query): Query<DeleteProductQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let product_id = parse_object_id(id)?;
+    let collection: Collection<Products> = app_state.db.collection("products");
+
+    if !query.hard {
+        let result = collection
+            .update_one(
+                doc! {"_id": product_id, "deleted_at": null},
+                doc! {"$set": {"deleted_at": DateTime::now()}},
+            )
+            .await?;
+
+        if result.matched_count == 0 {
+            return Err(AppError::NotFound("Product not found".to_string()));
+        }
+        unlist_from_search(&app_state, product_id).await;
 
-    let result = collection.insert_one(data).await.map_err(|_| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "failed to create product".to_string(),
+        return Ok(StatusCode::NO_CONTENT);
+    }
+
+    let product = collection
+        .find_one_and_delete(doc! {"_id": product_id})
+        .await?
+        .ok_or_else(|| AppError::NotFound("Product not found".to_string()))?;
+    unlist_from_search(&app_state, product_id).await;
+
+    let images = product
+        .image_url
+        .into_iter()
+        .chain(product.variants.into_iter().filter_map(|v| v.image_url))
+        .flatten();
+
+    // the product is gone already, a leftover object is only wasted space
+    for url in images {
+        if let Err(e) = delete_image(app_state.storage.as_ref(), &url).await {
+            tracing::error!("failed to delete image {}: {:?}", url, e);
+        }
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[debug_handler]
+pub async fn delete_product_image(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(query): Query<DeleteImageQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let product_id = parse_object_id(id)?;
+    let collection: Collection<Products> = app_state.db.collection("products");
+
+    let product_images = collection
+        .update_one(
+            doc! {"_id": product_id, "deleted_at": null, "image_url": &query.url},
+            doc! {"$pull": {"image_url": &query.url}},
         )
-    })?;
+        .await?;
+
+    let variant_images = collection
+        .update_one(
+            doc! {"_id": product_id, "deleted_at": null, "variants.image_url": &query.url},
+            doc! {"$pull": {"variants.$[variant].image_url": &query.url}},
+        )
+        .array_filters(vec![doc! {"variant.image_url": &query.url}])
+        .await?;
+
+    if product_images.modified_count + variant_images.modified_count == 0 {
+        collection
+            .find_one(doc! {"_id": product_id, "deleted_at": null})
+            .await?
+            .ok_or_else(|| AppError::NotFound("Product not found".to_string()))?;
+
+        return Err(AppError::NotFound("Image not found".to_string()));
+    }
+
+    delete_image(app_state.storage.as_ref(), &query.url).await?;
 
-    return Ok(Json(result));
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// A temporary link to one of the product's images, for buckets that are not
+/// publicly readable.
+#[debug_handler]
+pub async fn get_product_image_link(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(query): Query<ImageLinkQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let product_id = parse_object_id(id)?;
+    let collection: Collection<Products> = app_state.db.collection("products");
+
+    collection
+        .find_one(doc! {
+            "_id": product_id,
+            "$or": [{"image_url": &query.url}, {"variants.image_url": &query.url}],
+        })
+        .await?
+        .ok_or_else(|| AppError::NotFound("Image not found".to_string()))?;
+
+    let key = app_state
+        .storage
+        .key_from_url(&query.url)
+        .ok_or_else(|| AppError::BadRequest("image is not kept in our storage".to_string()))?;
+
+    let expires_in = query
+        .expires_in
+        .unwrap_or(IMAGE_LINK_TTL_SECONDS)
+        .clamp(1, MAX_IMAGE_LINK_TTL_SECONDS);
+
+    let url = app_state
+        .storage
+        .presign(&key, Duration::from_secs(expires_in))
+        .await?;
+
+    Ok(Json(ImageLink { url, expires_in }))
 }
 
 #[debug_handler]
@@ -43,52 +505,47 @@ pub async fn upload_product_image(
     State(app_state): State<Arc<AppState>>,
     Path(id): Path<String>,
     mut multipart: Multipart,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+) -> Result<impl IntoResponse, AppError> {
+    let product_id = parse_object_id(id.clone())?;
+
     while let Some(field) = multipart.next_field().await.map_err(|e| {
         tracing::error!("Mutlipart error {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Failed to read multipart fields".to_string(),
-        )
+        AppError::BadRequest("Failed to read multipart fields".to_string())
     })? {
         let name = field.name().unwrap_or("unknown").to_string();
         let file_name = field.file_name().unwrap_or("unnamed").to_string();
-        let content_type = field.content_type().unwrap_or("unknown").to_string();
-        let data = field.bytes().await.unwrap();
+        let content_type = field
+            .content_type()
+            .unwrap_or("application/octet-stream")
+            .to_string();
+        let data = field
+            .bytes()
+            .await
+            .map_err(|_| AppError::BadRequest("Failed to read multipart fields".to_string()))?;
 
         if name == "images" {
-            let image_name = file_name.clone();
-            let image_type = content_type.clone();
             let image_bytes = data.to_vec();
 
             let collection: Collection<Products> = app_state.db.collection("products");
 
-            let upload_result = upload_single(image_bytes, &image_type, &image_name)
-                .await
-                .map_err(|_| {
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        "Failed to upload image".to_string(),
-                    )
-                })?;
+            let key = object_key("products", &file_name);
+            app_state
+                .storage
+                .put(&key, image_bytes, &content_type)
+                .await?;
+            let upload_result = app_state.storage.public_url(&key);
 
             tracing::debug!("UPLOADED_URL: {upload_result}");
 
             let update = doc! {"$push": doc! {"image_url": upload_result }};
-            let filter = doc! {"_id": &id};
+            let filter = doc! {"_id": product_id, "deleted_at": null};
 
-            let product = collection
+            collection
                 .find_one_and_update(filter, update)
-                .await
-                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-            match product {
-                Some(_) => {
-                    tracing::info!("PRODUCT UPDATED IMAGE UPLOADED");
-                }
-                None => return Err((StatusCode::NOT_FOUND, "Product not found".to_string())),
-            };
+                .await?
+                .ok_or_else(|| AppError::NotFound("Product not found".to_string()))?;
 
+            tracing::info!("PRODUCT UPDATED IMAGE UPLOADED");
             tracing::info!("PRODUCT_ID: {id}");
         }
     }
@@ -98,99 +555,270 @@ pub async fn upload_product_image(
 #[debug_handler]
 pub async fn get_all_products(
     State(app_state): State<Arc<AppState>>,
-    Query(query): Query<ProductPaginate>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    Query(query): Query<PageQuery>,
+) -> Result<Json<Page<Products>>, AppError> {
     let collection: Collection<Products> = app_state.db.collection("products");
 
-    let mut products = vec![];
-    let page = query.page.max(1);
-    let limit_per_page = 5;
+    let request = PageRequest::new(Keyset::NEWEST, query.cursor.as_deref(), query.limit)?;
 
-    tracing::debug!("QUERY:? {:?}", query);
+    let page = find_page(&collection, doc! {"deleted_at": null}, &request).await?;
 
-    let pipeline = vec![
-        doc! {"$skip": ((page - 1) * limit_per_page)},
-        doc! {"$limit": limit_per_page},
-    ];
+    Ok(Json(page))
+}
 
-    let mut cursor = collection.aggregate(pipeline).await.map_err(|_| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Failed to fetch products here 1".to_string(),
-        )
-    })?;
+/// Filters a listing can be narrowed by and that have a facet of their own.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Facet {
+    Brand,
+    Category,
+    Price,
+}
 
-    while cursor.advance().await.map_err(|_| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Failed to fetch products here 2".to_string(),
-        )
-    })? {
-        products.push(cursor.deserialize_current().map_err(|_| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "something went wrong".to_string(),
-            )
-        })?);
+#[derive(Deserialize)]
+struct Count {
+    count: u64,
+}
+
+#[derive(Deserialize)]
+struct RawPriceBucket {
+    #[serde(rename = "_id")]
+    min: f64,
+    count: u64,
+}
+
+#[derive(Deserialize)]
+struct FacetedProducts {
+    /// Kept as documents, the cursor is built from the computed sort fields.
+    items: Vec<Document>,
+    total: Vec<Count>,
+    brands: Vec<FacetCount>,
+    categories: Vec<FacetCount>,
+    prices: Vec<RawPriceBucket>,
+}
+
+/// Every faceted filter except the one of `skip`.
+fn narrowed(filters: &[(Facet, Document)], skip: Option<Facet>) -> Document {
+    let conditions = filters
+        .iter()
+        .filter(|(facet, _)| Some(*facet) != skip)
+        .map(|(_, condition)| condition.clone())
+        .collect::<Vec<Document>>();
+
+    if conditions.is_empty() {
+        doc! {}
+    } else {
+        doc! {"$and": conditions}
     }
+}
 
-    tracing::info!("{:?}", products);
-    return Ok(Json(products));
+fn count_by(filters: &[(Facet, Document)], facet: Facet, field: &str) -> Vec<Document> {
+    vec![
+        doc! {"$match": narrowed(filters, Some(facet))},
+        doc! {"$group": {"_id": format!("${}", field), "count": {"$sum": 1}}},
+        doc! {"$sort": {"count": -1, "_id": 1}},
+        doc! {"$project": {"_id": 0, "value": "$_id", "count": 1}},
+    ]
 }
 
+fn validate_filter(query: &ProductFilter) -> Vec<FieldError> {
+    let mut errors = vec![];
+
+    if query
+        .q
+        .as_ref()
+        .is_some_and(|q| q.trim().chars().count() > MAX_SEARCH_LENGTH)
+    {
+        errors.push(FieldError::new(
+            "q",
+            format!(
+                "search cannot be longer than {} characters",
+                MAX_SEARCH_LENGTH
+            ),
+        ));
+    }
+
+    if query.min_price.is_some_and(|price| price < 0.0)
+        || query.max_price.is_some_and(|price| price < 0.0)
+    {
+        errors.push(FieldError::new("price", "price cannot be negative"));
+    }
+
+    if let (Some(min), Some(max)) = (query.min_price, query.max_price) {
+        if min > max {
+            errors.push(FieldError::new(
+                "price",
+                "min_price cannot be above max_price",
+            ));
+        }
+    }
+
+    if query
+        .min_rating
+        .is_some_and(|rating| !(1.0..=5.0).contains(&rating))
+    {
+        errors.push(FieldError::new(
+            "min_rating",
+            "min_rating must be between 1 and 5",
+        ));
+    }
+
+    errors
+}
+
+/// Public catalogue listing with full-text search, structured filters,
+/// sorting and facet counts, all computed in a single aggregation.
 #[debug_handler]
 pub async fn filter_products(
     State(app_state): State<Arc<AppState>>,
     Query(query): Query<ProductFilter>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+) -> Result<Json<ProductListing>, AppError> {
     let collection: Collection<Products> = app_state.db.collection("products");
 
-    let search_query = vec![
-        query.title.unwrap_or_else(|| "".to_string()),
-        query.brand.unwrap_or_else(|| "".to_string()),
-        query.category.unwrap_or_else(|| "".to_string()),
-    ]
-    .into_iter()
-    .filter(|s| !s.is_empty())
-    .collect::<Vec<String>>();
+    let errors = validate_filter(&query);
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
 
-    tracing::info!("SEARCH_QUERY IS: {:?}", &search_query);
+    let mut conditions = vec![doc! {"deleted_at": null}];
 
-    let pipeline = vec![
-        doc! {
-            "$search": {
-            "index": "default",
-            "text": {
-            "query": &search_query,
-            "path": ["title", "brand", "category"]
-        }
+    let search_query = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+
+    let ranked_ids = match search_query {
+        Some(text) => {
+            tracing::debug!("SEARCH_QUERY IS: {:?}", text);
+            let ids = app_state
+                .search
+                .search(text, SEARCH_CANDIDATE_LIMIT)
+                .await?;
+            conditions.push(doc! {"_id": {"$in": &ids}});
+            Some(ids)
         }
-        },
-        doc! {"$limit": 5},
-    ];
+        None => None,
+    };
 
-    let mut cursor = collection.aggregate(pipeline).await.map_err(|e| {
-        tracing::debug!("ERROR: {}", e.to_string());
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Error while fetching products".to_string(),
-        )
-    })?;
+    if let Some(has_offer) = query.has_offer {
+        conditions.push(match has_offer {
+            true => doc! {"offer_price": {"$ne": null}},
+            false => doc! {"offer_price": null},
+        });
+    }
 
-    let mut products = Vec::new();
+    if let Some(min_rating) = query.min_rating {
+        conditions.push(doc! {"rating_average": {"$gte": min_rating}});
+    }
 
-    while cursor.advance().await.map_err(|_| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Something went wrong".to_string(),
-        )
-    })? {
-        products.push(
-            cursor
-                .deserialize_current()
-                .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Failed".to_string()))?,
-        )
+    if let Some(in_stock) = query.in_stock {
+        let available = doc! {"$or": [{"stock": {"$gt": 0}}, {"variants.stock": {"$gt": 0}}]};
+        conditions.push(match in_stock {
+            true => available,
+            false => doc! {"$nor": [available]},
+        });
+    }
+
+    let mut filters = vec![];
+    if let Some(brand) = query.brand.filter(|b| !b.is_empty()) {
+        filters.push((Facet::Brand, doc! {"brand": brand}));
+    }
+    if let Some(brand_id) = query.brand_id {
+        filters.push((Facet::Brand, doc! {"brand_id": parse_object_id(brand_id)?}));
+    }
+    if let Some(category) = query.category.filter(|c| !c.is_empty()) {
+        filters.push((Facet::Category, doc! {"category": category}));
+    }
+    if let Some(category_id) = query.category_id {
+        let category_id = parse_object_id(category_id)?;
+        filters.push((
+            Facet::Category,
+            match query.include_descendants.unwrap_or(true) {
+                true => doc! {"category_path": category_id},
+                false => doc! {"category_id": category_id},
+            },
+        ));
+    }
+    let mut price = Document::new();
+    if let Some(min) = query.min_price {
+        price.insert("$gte", min);
+    }
+    if let Some(max) = query.max_price {
+        price.insert("$lte", max);
     }
+    if !price.is_empty() {
+        filters.push((Facet::Price, doc! {"effective_price": price}));
+    }
+
+    let mut computed = doc! {"effective_price": {"$ifNull": ["$offer_price", "$price"]}};
+    if let Some(ids) = &ranked_ids {
+        computed.insert("search_rank", doc! {"$indexOfArray": [ids, "$_id"]});
+    }
+
+    let keyset = match (query.sort, &ranked_ids) {
+        (ProductSort::Relevance, Some(_)) => BY_RELEVANCE,
+        (ProductSort::Relevance, None) | (ProductSort::Newest, _) => Keyset::NEWEST,
+        (ProductSort::PriceAsc, _) => BY_PRICE_ASC,
+        (ProductSort::PriceDesc, _) => BY_PRICE_DESC,
+        (ProductSort::Rating, _) => BY_RATING,
+    };
+    let request = PageRequest::new(keyset, query.cursor.as_deref(), query.limit)?;
+
+    let pipeline = vec![
+        doc! {"$match": {"$and": conditions}},
+        doc! {"$addFields": computed},
+        doc! {"$facet": {
+            "items": [
+                {"$match": narrowed(&filters, None)},
+                {"$match": request.filter()},
+                {"$sort": request.sort()},
+                {"$limit": request.fetch_limit()},
+            ],
+            "total": [
+                {"$match": narrowed(&filters, None)},
+                {"$count": "count"},
+            ],
+            "brands": count_by(&filters, Facet::Brand, "brand"),
+            "categories": count_by(&filters, Facet::Category, "category"),
+            "prices": [
+                {"$match": narrowed(&filters, Some(Facet::Price))},
+                {"$bucket": {
+                    "groupBy": "$effective_price",
+                    "boundaries": PRICE_BUCKETS.to_vec(),
+                    "default": PRICE_BUCKETS[PRICE_BUCKETS.len() - 1],
+                    "output": {"count": {"$sum": 1}},
+                }},
+            ],
+        }},
+    ];
+
+    let mut cursor = collection
+        .aggregate(pipeline)
+        .with_type::<FacetedProducts>()
+        .await?;
+
+    let faceted = match cursor.advance().await? {
+        true => cursor.deserialize_current()?,
+        false => return Err(AppError::Internal("$facet returned nothing".to_string())),
+    };
 
-    Ok(Json(products))
+    let prices = faceted
+        .prices
+        .into_iter()
+        .map(|bucket| PriceBucket {
+            min: bucket.min,
+            max: PRICE_BUCKETS
+                .iter()
+                .copied()
+                .find(|boundary| *boundary > bucket.min),
+            count: bucket.count,
+        })
+        .collect();
+
+    let total = faceted.total.first().map(|total| total.count).unwrap_or(0);
+
+    Ok(Json(ProductListing {
+        page: request.page(faceted.items, total)?,
+        facets: ProductFacets {
+            brands: faceted.brands,
+            categories: faceted.categories,
+            prices,
+        },
+    }))
 }
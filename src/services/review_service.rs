@@ -0,0 +1,399 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use axum_macros::debug_handler;
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime, Document},
+    options::ReturnDocument,
+    Collection, Database,
+};
+use serde::Deserialize;
+
+use crate::{
+    config::app_state::AppState,
+    database::mongo::is_duplicate_key_error,
+    errors::app_error::{AppError, FieldError},
+    models::{
+        order_model::{Order, OrderStatus},
+        products_model::Products,
+        review_model::{
+            CreateReview, ModerateReview, ModerationQuery, Review, ReviewQuery, ReviewSort,
+            ReviewStatus, ReviewVote, UpdateReview,
+        },
+        user_model::User,
+    },
+    services::cart_service::current_user_id,
+    utils::{
+        pagination::{find_page, Keyset, Page, PageRequest},
+        parse_id::parse_object_id,
+    },
+};
+
+const MAX_REVIEW_LENGTH: usize = 5000;
+const MAX_NOTE_LENGTH: usize = 500;
+
+const BY_HELPFUL: Keyset = Keyset::by("helpful", "helpful_count", false);
+
+fn reviews(db: &Database) -> Collection<Review> {
+    db.collection("reviews")
+}
+
+fn votes(db: &Database) -> Collection<ReviewVote> {
+    db.collection("review_votes")
+}
+
+fn validate_rating(rating: u8) -> Option<FieldError> {
+    (!(1..=5).contains(&rating)).then(|| FieldError::new("rating", "rating must be 1 to 5"))
+}
+
+fn validate_text(text: &str) -> Option<FieldError> {
+    if text.is_empty() {
+        return Some(FieldError::new("text", "text cannot be empty"));
+    }
+    if text.chars().count() > MAX_REVIEW_LENGTH {
+        return Some(FieldError::new(
+            "text",
+            format!(
+                "text cannot be longer than {} characters",
+                MAX_REVIEW_LENGTH
+            ),
+        ));
+    }
+    None
+}
+
+#[derive(Deserialize)]
+struct RatingSummary {
+    average: f64,
+    count: i64,
+}
+
+/// Recomputes the denormalised rating of a product from its approved
+/// reviews. Recomputing instead of adjusting keeps it right even when two
+/// moderators act on the same product at once.
+async fn refresh_rating(db: &Database, product_id: ObjectId) -> Result<(), AppError> {
+    let pipeline = vec![
+        doc! {"$match": {"product_id": product_id, "status": ReviewStatus::Approved.as_str()}},
+        doc! {"$group": {"_id": null, "average": {"$avg": "$rating"}, "count": {"$sum": 1}}},
+    ];
+
+    let mut cursor = reviews(db)
+        .aggregate(pipeline)
+        .with_type::<RatingSummary>()
+        .await?;
+
+    let summary = match cursor.advance().await? {
+        true => cursor.deserialize_current()?,
+        false => RatingSummary {
+            average: 0.0,
+            count: 0,
+        },
+    };
+
+    let products: Collection<Products> = db.collection("products");
+    products
+        .update_one(
+            doc! {"_id": product_id},
+            doc! {"$set": {"rating_average": summary.average, "rating_count": summary.count}},
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Approved reviews of a product, newest or most helpful first.
+#[debug_handler]
+pub async fn list_product_reviews(
+    State(app_state): State<Arc<AppState>>,
+    Path(product_id): Path<String>,
+    Query(query): Query<ReviewQuery>,
+) -> Result<Json<Page<Review>>, AppError> {
+    let product_id = parse_object_id(product_id)?;
+
+    let keyset = match query.sort {
+        ReviewSort::Newest => Keyset::NEWEST,
+        ReviewSort::Helpful => BY_HELPFUL,
+    };
+    let request = PageRequest::new(keyset, query.cursor.as_deref(), query.limit)?;
+
+    let filter = doc! {"product_id": product_id, "status": ReviewStatus::Approved.as_str()};
+    let page = find_page(&reviews(&app_state.db), filter, &request).await?;
+
+    Ok(Json(page))
+}
+
+/// Only customers with a delivered order containing the product may review
+/// it, once.
+#[debug_handler]
+pub async fn create_review(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(product_id): Path<String>,
+    Json(input): Json<CreateReview>,
+) -> Result<impl IntoResponse, AppError> {
+    let product_id = parse_object_id(product_id)?;
+    let user_id = current_user_id(&user)?;
+    let text = input.text.trim().to_string();
+
+    let errors = validate_rating(input.rating)
+        .into_iter()
+        .chain(validate_text(&text))
+        .collect::<Vec<FieldError>>();
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+
+    let products: Collection<Products> = app_state.db.collection("products");
+    products
+        .find_one(doc! {"_id": product_id, "deleted_at": null})
+        .await?
+        .ok_or_else(|| AppError::NotFound("Product not found".to_string()))?;
+
+    let orders: Collection<Order> = app_state.db.collection("orders");
+    let delivered = orders
+        .find_one(doc! {
+            "user_id": user_id,
+            "status": OrderStatus::Delivered.as_str(),
+            "items.product_id": product_id.to_hex(),
+        })
+        .await?;
+
+    if delivered.is_none() {
+        return Err(AppError::Forbidden(
+            "only customers who received this product can review it".to_string(),
+        ));
+    }
+
+    let now = DateTime::now();
+    let review = Review {
+        id: ObjectId::new(),
+        product_id,
+        user_id,
+        author_name: user.name,
+        rating: input.rating,
+        text,
+        status: ReviewStatus::Pending,
+        moderation_note: None,
+        moderated_by: None,
+        helpful_count: 0,
+        created_at: now,
+        updated_at: now,
+    };
+
+    match reviews(&app_state.db).insert_one(&review).await {
+        Ok(_) => Ok((StatusCode::CREATED, Json(review))),
+        Err(e) if is_duplicate_key_error(&e) => Err(AppError::Conflict(
+            "you have already reviewed this product".to_string(),
+        )),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[debug_handler]
+pub async fn update_my_review(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(id): Path<String>,
+    Json(input): Json<UpdateReview>,
+) -> Result<impl IntoResponse, AppError> {
+    let review_id = parse_object_id(id)?;
+    let user_id = current_user_id(&user)?;
+
+    let mut set = Document::new();
+    let mut errors = vec![];
+
+    if let Some(rating) = input.rating {
+        errors.extend(validate_rating(rating));
+        set.insert("rating", rating as i32);
+    }
+    if let Some(text) = input.text {
+        let text = text.trim().to_string();
+        errors.extend(validate_text(&text));
+        set.insert("text", text);
+    }
+
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+    if set.is_empty() {
+        return Err(AppError::BadRequest("nothing to update".to_string()));
+    }
+
+    set.insert("status", ReviewStatus::Pending.as_str());
+    set.insert("moderation_note", None::<String>);
+    set.insert("moderated_by", None::<ObjectId>);
+    set.insert("updated_at", DateTime::now());
+
+    let review = reviews(&app_state.db)
+        .find_one_and_update(
+            doc! {"_id": review_id, "user_id": user_id},
+            doc! {"$set": set},
+        )
+        .return_document(ReturnDocument::After)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Review not found".to_string()))?;
+
+    // an approved review stops counting until it is approved again
+    refresh_rating(&app_state.db, review.product_id).await?;
+
+    Ok(Json(review))
+}
+
+#[debug_handler]
+pub async fn delete_my_review(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let review_id = parse_object_id(id)?;
+    let user_id = current_user_id(&user)?;
+
+    let review = reviews(&app_state.db)
+        .find_one_and_delete(doc! {"_id": review_id, "user_id": user_id})
+        .await?
+        .ok_or_else(|| AppError::NotFound("Review not found".to_string()))?;
+
+    votes(&app_state.db)
+        .delete_many(doc! {"review_id": review_id})
+        .await?;
+    refresh_rating(&app_state.db, review.product_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[debug_handler]
+pub async fn vote_helpful(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let review_id = parse_object_id(id)?;
+    let user_id = current_user_id(&user)?;
+
+    let review = reviews(&app_state.db)
+        .find_one(doc! {"_id": review_id, "status": ReviewStatus::Approved.as_str()})
+        .await?
+        .ok_or_else(|| AppError::NotFound("Review not found".to_string()))?;
+
+    if review.user_id == user_id {
+        return Err(AppError::BadRequest(
+            "you can't vote for your own review".to_string(),
+        ));
+    }
+
+    let vote = ReviewVote {
+        id: ObjectId::new(),
+        review_id,
+        user_id,
+        created_at: DateTime::now(),
+    };
+
+    match votes(&app_state.db).insert_one(&vote).await {
+        Ok(_) => {}
+        Err(e) if is_duplicate_key_error(&e) => {
+            return Err(AppError::Conflict(
+                "you already found this review helpful".to_string(),
+            ))
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    reviews(&app_state.db)
+        .update_one(doc! {"_id": review_id}, doc! {"$inc": {"helpful_count": 1}})
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[debug_handler]
+pub async fn remove_helpful_vote(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let review_id = parse_object_id(id)?;
+    let user_id = current_user_id(&user)?;
+
+    let result = votes(&app_state.db)
+        .delete_one(doc! {"review_id": review_id, "user_id": user_id})
+        .await?;
+
+    if result.deleted_count == 0 {
+        return Err(AppError::NotFound("Vote not found".to_string()));
+    }
+
+    reviews(&app_state.db)
+        .update_one(
+            doc! {"_id": review_id},
+            doc! {"$inc": {"helpful_count": -1}},
+        )
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The moderation queue, pending reviews unless another status is asked for.
+#[debug_handler]
+pub async fn list_reviews_for_moderation(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<ModerationQuery>,
+) -> Result<Json<Page<Review>>, AppError> {
+    let request = PageRequest::new(Keyset::NEWEST, query.cursor.as_deref(), query.limit)?;
+
+    let status = query.status.unwrap_or(ReviewStatus::Pending);
+    let page = find_page(
+        &reviews(&app_state.db),
+        doc! {"status": status.as_str()},
+        &request,
+    )
+    .await?;
+
+    Ok(Json(page))
+}
+
+#[debug_handler]
+pub async fn moderate_review(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(id): Path<String>,
+    Json(input): Json<ModerateReview>,
+) -> Result<impl IntoResponse, AppError> {
+    let review_id = parse_object_id(id)?;
+
+    let note = input
+        .note
+        .map(|note| note.trim().to_string())
+        .filter(|note| !note.is_empty());
+    if note
+        .as_ref()
+        .is_some_and(|note| note.chars().count() > MAX_NOTE_LENGTH)
+    {
+        return Err(AppError::Validation(vec![FieldError::new(
+            "note",
+            format!("note cannot be longer than {} characters", MAX_NOTE_LENGTH),
+        )]));
+    }
+
+    let review = reviews(&app_state.db)
+        .find_one_and_update(
+            doc! {"_id": review_id},
+            doc! {"$set": {
+                "status": input.status.as_str(),
+                "moderation_note": note,
+                "moderated_by": current_user_id(&user)?,
+                "updated_at": DateTime::now(),
+            }},
+        )
+        .return_document(ReturnDocument::After)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Review not found".to_string()))?;
+
+    refresh_rating(&app_state.db, review.product_id).await?;
+
+    Ok(Json(review))
+}
@@ -0,0 +1,302 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use axum_macros::debug_handler;
+use mongodb::{
+    bson::{self, doc, DateTime, Document},
+    options::ReturnDocument,
+    Collection, Database,
+};
+
+use crate::{
+    config::app_state::AppState,
+    database::mongo::is_duplicate_key_error,
+    errors::app_error::{AppError, FieldError},
+    models::{
+        role_model::{
+            AssignRole, CreateRole, Permission, Role, UpdateRole, ADMIN_ROLE, DEFAULT_ROLE,
+        },
+        user_model::User,
+    },
+    services::cart_service::current_user_id,
+    utils::parse_id::parse_object_id,
+};
+
+/// How long a role's permissions are trusted before they are read again, so
+/// changes made by another instance are picked up.
+const ROLE_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// What a role grants, as cached by `RoleCache`.
+#[derive(Debug, Default)]
+pub struct RoleAccess {
+    pub permissions: HashSet<Permission>,
+    pub require_two_factor: bool,
+}
+
+fn roles(db: &Database) -> Collection<Role> {
+    db.collection("roles")
+}
+
+/// Permissions per role name, kept in memory so checking a permission does
+/// not cost a database round trip on every request.
+#[derive(Default)]
+pub struct RoleCache {
+    entries: RwLock<HashMap<String, (Instant, Arc<RoleAccess>)>>,
+}
+
+impl RoleCache {
+    /// What `name` grants, an unknown role grants nothing.
+    pub async fn access(&self, db: &Database, name: &str) -> Result<Arc<RoleAccess>, AppError> {
+        if let Some((loaded_at, access)) = self.entries.read().unwrap().get(name) {
+            if loaded_at.elapsed() < ROLE_CACHE_TTL {
+                return Ok(access.clone());
+            }
+        }
+
+        let access = Arc::new(
+            roles(db)
+                .find_one(doc! {"_id": name})
+                .await?
+                .map(|role| RoleAccess {
+                    permissions: role.permissions.into_iter().collect(),
+                    require_two_factor: role.require_two_factor,
+                })
+                .unwrap_or_default(),
+        );
+
+        self.entries
+            .write()
+            .unwrap()
+            .insert(name.to_string(), (Instant::now(), access.clone()));
+
+        Ok(access)
+    }
+
+    pub fn invalidate(&self, name: &str) {
+        self.entries.write().unwrap().remove(name);
+    }
+}
+
+/// Creates the built-in roles matching the original user/admin split. The
+/// admin role is topped up with permissions added since it was created.
+pub async fn seed_roles(db: &Database) {
+    let now = DateTime::now();
+    let all = Permission::ALL
+        .iter()
+        .map(|permission| permission.as_str())
+        .collect::<Vec<&str>>();
+
+    roles(db)
+        .update_one(
+            doc! {"_id": ADMIN_ROLE},
+            doc! {
+                "$setOnInsert": {
+                    "description": "Full access to the shop",
+                    // switched on by an admin once they have enrolled, not
+                    // at seed time where it would lock everybody out
+                    "require_two_factor": false,
+                    "built_in": true,
+                    "created_at": now,
+                    "updated_at": now,
+                },
+                "$addToSet": {"permissions": {"$each": all}},
+            },
+        )
+        .upsert(true)
+        .await
+        .expect("failed to seed admin role");
+
+    roles(db)
+        .update_one(
+            doc! {"_id": DEFAULT_ROLE},
+            doc! {"$setOnInsert": {
+                "description": "Customer account",
+                "permissions": [],
+                "require_two_factor": false,
+                "built_in": true,
+                "created_at": now,
+                "updated_at": now,
+            }},
+        )
+        .upsert(true)
+        .await
+        .expect("failed to seed user role");
+}
+
+fn validate_role_name(name: &str) -> Result<(), AppError> {
+    let valid = !name.is_empty()
+        && name.len() <= 32
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-');
+
+    if !valid {
+        return Err(AppError::Validation(vec![FieldError::new(
+            "name",
+            "must be 1 to 32 lowercase letters, digits, `_` or `-`",
+        )]));
+    }
+    Ok(())
+}
+
+#[debug_handler]
+pub async fn list_permissions() -> impl IntoResponse {
+    Json(Permission::ALL)
+}
+
+#[debug_handler]
+pub async fn list_roles(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut cursor = roles(&app_state.db)
+        .find(doc! {})
+        .sort(doc! {"_id": 1})
+        .await?;
+
+    let mut result = vec![];
+    while cursor.advance().await? {
+        result.push(cursor.deserialize_current()?);
+    }
+
+    Ok(Json(result))
+}
+
+#[debug_handler]
+pub async fn create_role(
+    State(app_state): State<Arc<AppState>>,
+    Json(input): Json<CreateRole>,
+) -> Result<impl IntoResponse, AppError> {
+    validate_role_name(&input.name)?;
+
+    let now = DateTime::now();
+    let role = Role {
+        name: input.name,
+        description: input.description,
+        permissions: input.permissions,
+        require_two_factor: input.require_two_factor,
+        built_in: false,
+        created_at: now,
+        updated_at: now,
+    };
+
+    match roles(&app_state.db).insert_one(&role).await {
+        Ok(_) => Ok((StatusCode::CREATED, Json(role))),
+        Err(e) if is_duplicate_key_error(&e) => Err(AppError::Conflict(format!(
+            "role `{}` already exists",
+            role.name
+        ))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[debug_handler]
+pub async fn update_role(
+    State(app_state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Json(input): Json<UpdateRole>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut set = Document::new();
+
+    if let Some(description) = input.description {
+        set.insert("description", description);
+    }
+    if let Some(permissions) = input.permissions {
+        // taking permissions away from admin could lock everybody out
+        if name == ADMIN_ROLE && !Permission::ALL.iter().all(|p| permissions.contains(p)) {
+            return Err(AppError::BadRequest(
+                "the admin role always has every permission".to_string(),
+            ));
+        }
+        set.insert("permissions", bson::to_bson(&permissions)?);
+    }
+    if let Some(require_two_factor) = input.require_two_factor {
+        set.insert("require_two_factor", require_two_factor);
+    }
+
+    if set.is_empty() {
+        return Err(AppError::BadRequest("nothing to update".to_string()));
+    }
+    set.insert("updated_at", DateTime::now());
+
+    let role = roles(&app_state.db)
+        .find_one_and_update(doc! {"_id": &name}, doc! {"$set": set})
+        .return_document(ReturnDocument::After)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Role not found".to_string()))?;
+
+    app_state.roles.invalidate(&name);
+
+    Ok(Json(role))
+}
+
+#[debug_handler]
+pub async fn delete_role(
+    State(app_state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let role = roles(&app_state.db)
+        .find_one(doc! {"_id": &name})
+        .await?
+        .ok_or_else(|| AppError::NotFound("Role not found".to_string()))?;
+
+    if role.built_in {
+        return Err(AppError::Conflict(
+            "built-in roles cannot be deleted".to_string(),
+        ));
+    }
+
+    let users: Collection<User> = app_state.db.collection("users");
+    let assigned = users.count_documents(doc! {"role": &name}).await?;
+    if assigned > 0 {
+        return Err(AppError::Conflict(format!(
+            "role `{}` is still assigned to {} users",
+            name, assigned
+        )));
+    }
+
+    roles(&app_state.db).delete_one(doc! {"_id": &name}).await?;
+    app_state.roles.invalidate(&name);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[debug_handler]
+pub async fn assign_role(
+    State(app_state): State<Arc<AppState>>,
+    Extension(admin): Extension<User>,
+    Path(id): Path<String>,
+    Json(input): Json<AssignRole>,
+) -> Result<impl IntoResponse, AppError> {
+    let user_id = parse_object_id(id)?;
+
+    if current_user_id(&admin)? == user_id {
+        return Err(AppError::BadRequest(
+            "you cannot change your own role".to_string(),
+        ));
+    }
+
+    roles(&app_state.db)
+        .find_one(doc! {"_id": &input.role})
+        .await?
+        .ok_or_else(|| AppError::NotFound("Role not found".to_string()))?;
+
+    let users: Collection<User> = app_state.db.collection("users");
+    let result = users
+        .update_one(doc! {"_id": user_id}, doc! {"$set": {"role": &input.role}})
+        .await?;
+
+    if result.matched_count == 0 {
+        return Err(AppError::NotFound("User not found".to_string()));
+    }
+
+    Ok(Json(format!("role `{}` assigned", input.role)))
+}
@@ -0,0 +1,124 @@
+use chrono::{Duration, Utc};
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime},
+    options::ReturnDocument,
+    Collection, Database,
+};
+
+use crate::{
+    errors::app_error::AppError,
+    models::session_model::Session,
+    utils::token::{generate_token, hash_token},
+};
+
+pub const REFRESH_TOKEN_TTL_DAYS: i64 = 30;
+
+fn sessions(db: &Database) -> Collection<Session> {
+    db.collection("sessions")
+}
+
+/// Starts a new token family for `user_id` and returns it together with the
+/// plaintext refresh token. Only the hash is persisted.
+pub async fn create_session(
+    db: &Database,
+    user_id: ObjectId,
+) -> Result<(Session, String), mongodb::error::Error> {
+    let refresh_token = generate_token();
+    let expires_at = Utc::now() + Duration::days(REFRESH_TOKEN_TTL_DAYS);
+
+    let session = Session {
+        id: ObjectId::new(),
+        user_id,
+        refresh_token_hash: hash_token(&refresh_token),
+        rotated_token_hashes: vec![],
+        created_at: DateTime::now(),
+        expires_at: DateTime::from_millis(expires_at.timestamp_millis()),
+        revoked_at: None,
+    };
+
+    sessions(db).insert_one(&session).await?;
+
+    Ok((session, refresh_token))
+}
+
+/// Exchanges a refresh token for a new one in the same family.
+///
+/// Presenting a token that has already been rotated away means it leaked, so
+/// the whole family is revoked and the caller has to log in again.
+pub async fn rotate_session(
+    db: &Database,
+    refresh_token: &str,
+) -> Result<(Session, String), AppError> {
+    let collection = sessions(db);
+    let token_hash = hash_token(refresh_token);
+    let new_refresh_token = generate_token();
+
+    let filter = doc! {
+        "refresh_token_hash": &token_hash,
+        "revoked_at": null,
+        "expires_at": {"$gt": DateTime::now()},
+    };
+    let update = doc! {
+        "$set": {"refresh_token_hash": hash_token(&new_refresh_token)},
+        "$push": {"rotated_token_hashes": &token_hash},
+    };
+
+    let rotated = collection
+        .find_one_and_update(filter, update)
+        .return_document(ReturnDocument::After)
+        .await?;
+
+    if let Some(session) = rotated {
+        return Ok((session, new_refresh_token));
+    }
+
+    let reused = collection
+        .find_one(doc! {"rotated_token_hashes": &token_hash})
+        .await?;
+
+    match reused {
+        Some(session) => {
+            tracing::warn!(
+                "refresh token reuse detected for session {}, revoking family",
+                session.id
+            );
+            revoke_session(db, session.id).await?;
+            Err(AppError::RefreshTokenReused)
+        }
+        None => Err(AppError::SessionExpired),
+    }
+}
+
+pub async fn revoke_session(db: &Database, session_id: ObjectId) -> mongodb::error::Result<()> {
+    sessions(db)
+        .update_one(
+            doc! {"_id": session_id, "revoked_at": null},
+            doc! {"$set": {"revoked_at": DateTime::now()}},
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn revoke_user_sessions(db: &Database, user_id: ObjectId) -> mongodb::error::Result<()> {
+    sessions(db)
+        .update_many(
+            doc! {"user_id": user_id, "revoked_at": null},
+            doc! {"$set": {"revoked_at": DateTime::now()}},
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn is_session_active(
+    db: &Database,
+    session_id: ObjectId,
+) -> mongodb::error::Result<bool> {
+    let session = sessions(db)
+        .find_one(doc! {
+            "_id": session_id,
+            "revoked_at": null,
+            "expires_at": {"$gt": DateTime::now()},
+        })
+        .await?;
+    Ok(session.is_some())
+}
@@ -0,0 +1,278 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Extension, Json};
+use axum_macros::debug_handler;
+use chrono::{Duration, Utc};
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime},
+    options::ReturnDocument,
+    Collection, Database,
+};
+
+use crate::{
+    config::app_state::AppState,
+    errors::app_error::AppError,
+    models::{
+        auth_model::{
+            DisableTwoFactor, LoginChallenge, RecoveryCodes, TwoFactorCode, TwoFactorSetup,
+        },
+        user_model::User,
+    },
+    services::cart_service::current_user_id,
+    utils::{
+        bcrypt::verify_password,
+        token::{generate_token, hash_token},
+        totp::{
+            generate_recovery_codes, generate_secret, normalize_recovery_code, otpauth_uri,
+            verify_code,
+        },
+    },
+};
+
+pub const CHALLENGE_TTL_MINUTES: i64 = 5;
+const MAX_CHALLENGE_ATTEMPTS: u32 = 5;
+
+fn challenges(db: &Database) -> Collection<LoginChallenge> {
+    db.collection("login_challenges")
+}
+
+fn hash_recovery_codes(codes: &[String]) -> Vec<String> {
+    codes
+        .iter()
+        .map(|code| hash_token(&normalize_recovery_code(code)))
+        .collect()
+}
+
+/// Starts the second login step for `user_id` and returns the challenge
+/// token the client has to send back together with a code.
+pub async fn create_challenge(db: &Database, user_id: ObjectId) -> Result<String, AppError> {
+    let token = generate_token();
+    let now = Utc::now();
+
+    let challenge = LoginChallenge {
+        id: ObjectId::new(),
+        user_id,
+        token_hash: hash_token(&token),
+        attempts: 0,
+        created_at: DateTime::from_millis(now.timestamp_millis()),
+        expires_at: DateTime::from_millis(
+            (now + Duration::minutes(CHALLENGE_TTL_MINUTES)).timestamp_millis(),
+        ),
+    };
+
+    challenges(db).insert_one(&challenge).await?;
+
+    Ok(token)
+}
+
+/// Looks up a live challenge and counts the attempt, so a challenge can only
+/// be guessed at a few times before the user has to log in again.
+pub async fn redeem_challenge(db: &Database, token: &str) -> Result<LoginChallenge, AppError> {
+    challenges(db)
+        .find_one_and_update(
+            doc! {
+                "token_hash": hash_token(token),
+                "expires_at": {"$gt": DateTime::now()},
+                "attempts": {"$lt": MAX_CHALLENGE_ATTEMPTS},
+            },
+            doc! {"$inc": {"attempts": 1}},
+        )
+        .return_document(ReturnDocument::After)
+        .await?
+        .ok_or_else(|| {
+            AppError::Unauthorized(
+                "your login challenge has expired please login again".to_string(),
+            )
+        })
+}
+
+pub async fn finish_challenge(db: &Database, challenge_id: ObjectId) -> Result<(), AppError> {
+    challenges(db)
+        .delete_one(doc! {"_id": challenge_id})
+        .await?;
+    Ok(())
+}
+
+/// Accepts a TOTP code or an unused recovery code for `user`. Either is
+/// consumed atomically so the same code never works twice.
+pub async fn verify_second_factor(db: &Database, user: &User, code: &str) -> Result<(), AppError> {
+    let two_factor = user
+        .two_factor
+        .as_ref()
+        .filter(|tf| tf.confirmed_at.is_some())
+        .ok_or(AppError::InvalidTwoFactorCode)?;
+
+    let user_id = current_user_id(user)?;
+    let users: Collection<User> = db.collection("users");
+
+    if let Some(step) = verify_code(&two_factor.secret, code, Utc::now().timestamp()) {
+        let result = users
+            .update_one(
+                doc! {
+                    "_id": user_id,
+                    "$or": [
+                        {"two_factor.last_used_step": null},
+                        {"two_factor.last_used_step": {"$lt": step}},
+                    ],
+                },
+                doc! {"$set": {"two_factor.last_used_step": step}},
+            )
+            .await?;
+
+        return match result.modified_count {
+            1 => Ok(()),
+            _ => Err(AppError::InvalidTwoFactorCode),
+        };
+    }
+
+    let hash = hash_token(&normalize_recovery_code(code));
+    let result = users
+        .update_one(
+            doc! {"_id": user_id, "two_factor.recovery_code_hashes": &hash},
+            doc! {"$pull": {"two_factor.recovery_code_hashes": &hash}},
+        )
+        .await?;
+
+    if result.modified_count == 0 {
+        return Err(AppError::InvalidTwoFactorCode);
+    }
+
+    tracing::info!("recovery code used by {}", user.email);
+    Ok(())
+}
+
+#[debug_handler]
+pub async fn setup_two_factor(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, AppError> {
+    if user.two_factor_enabled() {
+        return Err(AppError::Conflict(
+            "two-factor authentication is already enabled".to_string(),
+        ));
+    }
+
+    let user_id = current_user_id(&user)?;
+    let secret = generate_secret();
+
+    let users: Collection<User> = app_state.db.collection("users");
+    users
+        .update_one(
+            doc! {"_id": user_id},
+            doc! {"$set": {"two_factor": {
+                "secret": &secret,
+                "confirmed_at": null,
+                "recovery_code_hashes": [],
+                "last_used_step": null,
+            }}},
+        )
+        .await?;
+
+    Ok(Json(TwoFactorSetup {
+        otpauth_uri: otpauth_uri(&app_state.settings.mail.brand, &user.email, &secret),
+        secret,
+    }))
+}
+
+#[debug_handler]
+pub async fn confirm_two_factor(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Json(input): Json<TwoFactorCode>,
+) -> Result<impl IntoResponse, AppError> {
+    if user.two_factor_enabled() {
+        return Err(AppError::Conflict(
+            "two-factor authentication is already enabled".to_string(),
+        ));
+    }
+
+    let secret = user
+        .two_factor
+        .as_ref()
+        .map(|tf| tf.secret.clone())
+        .ok_or_else(|| {
+            AppError::BadRequest("start enrolment with /api/auth/2fa/setup first".to_string())
+        })?;
+
+    let step = verify_code(&secret, &input.code, Utc::now().timestamp())
+        .ok_or(AppError::InvalidTwoFactorCode)?;
+
+    let user_id = current_user_id(&user)?;
+    let recovery_codes = generate_recovery_codes();
+
+    // matching on the secret makes sure a setup started meanwhile is not confirmed
+    let users: Collection<User> = app_state.db.collection("users");
+    let result = users
+        .update_one(
+            doc! {"_id": user_id, "two_factor.secret": &secret, "two_factor.confirmed_at": null},
+            doc! {"$set": {
+                "two_factor.confirmed_at": DateTime::now(),
+                "two_factor.recovery_code_hashes": hash_recovery_codes(&recovery_codes),
+                "two_factor.last_used_step": step,
+            }},
+        )
+        .await?;
+
+    if result.modified_count == 0 {
+        return Err(AppError::Conflict(
+            "enrolment changed meanwhile, start again".to_string(),
+        ));
+    }
+
+    Ok(Json(RecoveryCodes { recovery_codes }))
+}
+
+#[debug_handler]
+pub async fn disable_two_factor(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Json(input): Json<DisableTwoFactor>,
+) -> Result<impl IntoResponse, AppError> {
+    if !user.two_factor_enabled() {
+        return Err(AppError::BadRequest(
+            "two-factor authentication is not enabled".to_string(),
+        ));
+    }
+
+    if !verify_password(input.password, &user.password)? {
+        return Err(AppError::InvalidCredentials);
+    }
+    verify_second_factor(&app_state.db, &user, &input.code).await?;
+
+    let users: Collection<User> = app_state.db.collection("users");
+    users
+        .update_one(
+            doc! {"_id": current_user_id(&user)?},
+            doc! {"$unset": {"two_factor": ""}},
+        )
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[debug_handler]
+pub async fn regenerate_recovery_codes(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Json(input): Json<TwoFactorCode>,
+) -> Result<impl IntoResponse, AppError> {
+    if !user.two_factor_enabled() {
+        return Err(AppError::BadRequest(
+            "two-factor authentication is not enabled".to_string(),
+        ));
+    }
+
+    verify_second_factor(&app_state.db, &user, &input.code).await?;
+
+    let recovery_codes = generate_recovery_codes();
+
+    let users: Collection<User> = app_state.db.collection("users");
+    users
+        .update_one(
+            doc! {"_id": current_user_id(&user)?},
+            doc! {"$set": {"two_factor.recovery_code_hashes": hash_recovery_codes(&recovery_codes)}},
+        )
+        .await?;
+
+    Ok(Json(RecoveryCodes { recovery_codes }))
+}
@@ -1,105 +1,220 @@
 use std::sync::Arc;
 
 use crate::{
-    models::user_model::{TempUser, VerifyOtpInput},
+    database::mongo::is_duplicate_key_error,
+    mail::templates::{send_template, Locale, TemplateData},
+    middlewares::auth_middleware::Credential,
+    models::{
+        role_model::{Permission, DEFAULT_ROLE},
+        user_model::{
+            DeleteAccount, PublicUser, RegisterInput, TempUser, UpdateProfile, UserQuery,
+            VerifyOtpInput,
+        },
+    },
+    services::{
+        auth_service::clear_auth_cookies, cart_service::current_user_id,
+        session_service::revoke_user_sessions,
+    },
+    storage::object_storage::object_key,
     utils::{
-        bcrypt::hash_password, generate_otp::create_otp, s3::upload_single, send_email::send_mail,
+        bcrypt::{hash_password, verify_password},
+        generate_otp::create_otp,
+        pagination::{find_page, Keyset, Page, PageRequest},
     },
 };
 use axum::{
-    extract::{Multipart, Path, State},
-    http::StatusCode,
+    extract::{Multipart, Path, Query, State},
+    http::{HeaderMap, StatusCode},
     response::IntoResponse,
-    Json,
+    Extension, Json,
 };
 use axum_cookie::{cookie::Cookie, prelude::SameSite, CookieManager};
 use axum_macros::debug_handler;
-use chrono::{Duration, Utc};
 use mongodb::{
-    bson::{doc, oid::ObjectId},
-    Collection,
+    bson::{doc, oid::ObjectId, DateTime, Document},
+    options::ReturnDocument,
+    Collection, Database,
 };
 use uuid::Uuid;
 
 use crate::{
     config::app_state::AppState,
+    errors::app_error::{AppError, FieldError},
     models::user_model::{UpdateUser, User},
     utils::parse_id::parse_object_id,
 };
 
+const OTP_TTL_MINUTES: i64 = 5;
+const MAX_OTP_ATTEMPTS: u32 = 5;
+const OTP_RESEND_COOLDOWN_SECONDS: i64 = 60;
+const MAX_OTP_RESENDS: u32 = 5;
+const MAX_NAME_LENGTH: usize = 80;
+
+fn temp_users(db: &Database) -> Collection<TempUser> {
+    db.collection("temp-user")
+}
+
+fn from_now(seconds: i64) -> DateTime {
+    DateTime::from_millis(DateTime::now().timestamp_millis() + seconds * 1000)
+}
+
+/// Fails with `TooManyRequests` until the cooldown since the last code sent
+/// for this registration has passed.
+fn ensure_resend_cooldown(temp_user: &TempUser) -> Result<(), AppError> {
+    let ready_in = temp_user.last_sent_at.timestamp_millis() + OTP_RESEND_COOLDOWN_SECONDS * 1000
+        - DateTime::now().timestamp_millis();
+
+    if ready_in > 0 {
+        return Err(AppError::TooManyRequests((ready_in as u64).div_ceil(1000)));
+    }
+    Ok(())
+}
+
+async fn send_otp(app_state: &AppState, temp_user: &TempUser, otp: &str) -> Result<(), AppError> {
+    send_template(
+        &app_state.db,
+        &app_state.settings.mail.brand,
+        Locale::parse(temp_user.locale.as_deref().unwrap_or_default()),
+        &temp_user.name,
+        &temp_user.email,
+        TemplateData::Otp {
+            otp: otp.to_string(),
+            valid_minutes: OTP_TTL_MINUTES,
+        },
+    )
+    .await
+}
+
 #[debug_handler]
 pub async fn register(
     State(app_state): State<Arc<AppState>>,
     cookie: CookieManager,
-    Json(input): Json<TempUser>,
-) -> Result<Json<String>, (StatusCode, String)> {
+    headers: HeaderMap,
+    Json(input): Json<RegisterInput>,
+) -> Result<Json<String>, AppError> {
+    let email = input.email.trim().to_lowercase();
     let user_collection: Collection<User> = app_state.db.collection("users");
-    let filter = doc! {"email": &input.email.to_lowercase()};
-    let temp_user_collection: Collection<TempUser> = app_state.db.collection("temp-user");
+    let filter = doc! {"email": &email};
 
-    let is_user_exists = user_collection
-        .find_one(filter)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()));
+    let is_user_exists = user_collection.find_one(filter).await?;
 
-    match is_user_exists {
-        Ok(Some(_)) => {
-            return Err((
-                StatusCode::CONFLICT,
-                "user with this email already exists".to_string(),
-            ));
-        }
-        Ok(None) => {
-            let id = Uuid::new_v4().to_string();
-
-            let otp = create_otp();
-
-            match send_mail(&input.name, &input.email, &otp).await {
-                Ok(_) => true,
-                Err(_) => {
-                    return Err((
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        "Failed to send email".to_string(),
-                    ))
-                }
-            };
-
-            let hashed = match hash_password(input.password) {
-                Ok(hashed) => hashed,
-                Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
-            };
-
-            let temp_user = TempUser {
-                _id: id.clone(),
-                email: input.email.to_lowercase(),
-                password: hashed,
-                otp: Some(otp.to_string()),
-                name: input.name,
-                expires_at: Utc::now() + Duration::minutes(5),
-            };
+    if is_user_exists.is_some() {
+        return Err(AppError::Conflict(
+            "user with this email already exists".to_string(),
+        ));
+    }
 
-            temp_user_collection
-                .insert_one(temp_user)
-                .await
-                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    // registering again replaces the pending registration, but not faster
+    // than a code could be resent, so this can't be used to flood a mailbox
+    let pending = temp_users(&app_state.db)
+        .find_one(doc! {"email": &email, "expires_at": {"$gt": DateTime::now()}})
+        .await?;
+    if let Some(pending) = &pending {
+        ensure_resend_cooldown(pending)?;
+    }
+    temp_users(&app_state.db)
+        .delete_many(doc! {"email": &email})
+        .await?;
 
-            let mut session_token = Cookie::new("session_token", id);
-            session_token.set_http_only(true);
-            session_token.set_same_site(SameSite::Strict);
+    let id = Uuid::new_v4().to_string();
 
-            cookie.set(session_token);
+    let otp = create_otp().to_string();
 
-            return Ok(Json(
-                "A 6 digit otp has been sent to your gmail".to_string(),
-            ));
-        }
-        Err(_) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "server error".to_string(),
+    let hashed = hash_password(input.password)?;
+
+    let locale = match &input.locale {
+        Some(tag) => Locale::parse(tag),
+        None => Locale::from_headers(&headers),
+    };
+
+    let temp_user = TempUser {
+        id: id.clone(),
+        email,
+        password: hashed,
+        otp_hash: hash_password(otp.clone())?,
+        name: input.name,
+        locale: Some(locale.code().to_string()),
+        attempts: 0,
+        resends: 0,
+        last_sent_at: DateTime::now(),
+        expires_at: from_now(OTP_TTL_MINUTES * 60),
+    };
+
+    match temp_users(&app_state.db).insert_one(&temp_user).await {
+        Ok(_) => {}
+        Err(e) if is_duplicate_key_error(&e) => {
+            return Err(AppError::Conflict(
+                "a registration for this email is already in progress".to_string(),
             ))
         }
+        Err(e) => return Err(e.into()),
+    }
+
+    send_otp(&app_state, &temp_user, &otp).await?;
+
+    let mut session_token = Cookie::new("session_token", id);
+    session_token.set_http_only(true);
+    session_token.set_same_site(SameSite::Strict);
+
+    cookie.set(session_token);
+
+    Ok(Json(
+        "A 6 digit otp has been sent to your gmail".to_string(),
+    ))
+}
+
+#[debug_handler]
+pub async fn resend_otp(
+    State(app_state): State<Arc<AppState>>,
+    cookie: CookieManager,
+) -> Result<Json<String>, AppError> {
+    let secret_token = cookie
+        .get("session_token")
+        .ok_or(AppError::RegistrationExpired)?;
+
+    let pending = temp_users(&app_state.db)
+        .find_one(doc! {"_id": secret_token.value(), "expires_at": {"$gt": DateTime::now()}})
+        .await?
+        .ok_or(AppError::RegistrationExpired)?;
+
+    if pending.resends >= MAX_OTP_RESENDS {
+        temp_users(&app_state.db)
+            .delete_one(doc! {"_id": &pending.id})
+            .await?;
+        cookie.remove("session_token");
+        return Err(AppError::BadRequest(
+            "too many codes requested please register again".to_string(),
+        ));
+    }
+    ensure_resend_cooldown(&pending)?;
+
+    let otp = create_otp().to_string();
+
+    // matching on last_sent_at lets only one of two concurrent resends through
+    let result = temp_users(&app_state.db)
+        .update_one(
+            doc! {"_id": &pending.id, "last_sent_at": pending.last_sent_at},
+            doc! {
+                "$set": {
+                    "otp_hash": hash_password(otp.clone())?,
+                    "attempts": 0,
+                    "last_sent_at": DateTime::now(),
+                    "expires_at": from_now(OTP_TTL_MINUTES * 60),
+                },
+                "$inc": {"resends": 1},
+            },
+        )
+        .await?;
+
+    if result.modified_count == 0 {
+        return Err(AppError::TooManyRequests(
+            OTP_RESEND_COOLDOWN_SECONDS as u64,
+        ));
     }
+
+    send_otp(&app_state, &pending, &otp).await?;
+
+    Ok(Json("A new 6 digit otp has been sent".to_string()))
 }
 
 #[debug_handler]
@@ -107,189 +222,385 @@ pub async fn verify(
     State(app_state): State<Arc<AppState>>,
     cookie: CookieManager,
     Json(input): Json<VerifyOtpInput>,
-) -> impl IntoResponse {
-    let secret_token = match cookie.get("session_token") {
-        Some(cookie) => cookie,
-        None => return Err((StatusCode::BAD_REQUEST, "your session have been expired")),
-    };
+) -> Result<impl IntoResponse, AppError> {
+    let secret_token = cookie
+        .get("session_token")
+        .ok_or(AppError::RegistrationExpired)?;
 
-    let temp_user_collection: Collection<TempUser> = app_state.db.collection("temp-user");
+    let temp_user_collection = temp_users(&app_state.db);
 
-    let temp_user = temp_user_collection
+    let usr = temp_user_collection
         .find_one(doc! {"_id": secret_token.value()})
-        .await
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Server Error"));
+        .await?
+        .ok_or(AppError::RegistrationExpired)?;
+
+    // the TTL index only runs once a minute, so expiry is checked here as well
+    if usr.expires_at <= DateTime::now() {
+        temp_user_collection
+            .delete_one(doc! {"_id": &usr.id})
+            .await?;
+        cookie.remove("session_token");
+        return Err(AppError::RegistrationExpired);
+    }
 
-    match temp_user {
-        Ok(Some(usr)) => {
-            let stored_otp = match &usr.otp {
-                Some(otp) => otp,
-                None => return Err((StatusCode::BAD_REQUEST, "your session is expired")),
-            };
+    if !verify_password(input.otp, &usr.otp_hash)? {
+        let attempts = temp_user_collection
+            .find_one_and_update(
+                doc! {"_id": secret_token.value()},
+                doc! {"$inc": {"attempts": 1}},
+            )
+            .return_document(ReturnDocument::After)
+            .await?
+            .map(|temp_user| temp_user.attempts)
+            .unwrap_or(MAX_OTP_ATTEMPTS);
+
+        // a six digit code is only safe if it can't be guessed at for long
+        if attempts >= MAX_OTP_ATTEMPTS {
+            temp_user_collection
+                .delete_one(doc! {"_id": secret_token.value()})
+                .await?;
+            cookie.remove("session_token");
+            return Err(AppError::BadRequest(
+                "too many wrong codes please register again".to_string(),
+            ));
+        }
+        return Err(AppError::InvalidOtp);
+    }
 
-            if stored_otp.to_string() != input.otp.to_string() {
-                return Err((StatusCode::BAD_REQUEST, "Invalid OTP"));
-            }
+    let user_id = Some(ObjectId::new());
 
-            let user_id = Some(ObjectId::new());
-
-            let user = User {
-                id: user_id,
-                email: usr.email.to_lowercase(),
-                password: usr.password,
-                name: usr.name,
-                role: Some("user".to_string()),
-            };
-
-            let user_collection: Collection<User> = app_state.db.collection("users");
-
-            let result = user_collection
-                .insert_one(user)
-                .await
-                .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Server Error"));
-
-            match result {
-                Ok(_) => {
-                    let result = temp_user_collection
-                        .delete_one(doc! {"_id": secret_token.value()})
-                        .await
-                        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "server error"));
-
-                    match result {
-                        Ok(_) => {
-                            cookie.remove("session_token");
-                            return Ok((StatusCode::CREATED, "User created successfully"));
-                        }
-                        Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, "server error")),
-                    };
-                }
-                Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, "Server Error")),
-            };
-        }
-        Ok(None) => return Err((StatusCode::BAD_REQUEST, "your session is expired")),
-        Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, "Server error")),
+    let user = User {
+        id: user_id,
+        email: usr.email.to_lowercase(),
+        password: usr.password,
+        name: usr.name,
+        role: Some(DEFAULT_ROLE.to_string()),
+        locale: usr.locale,
+        two_factor: None,
     };
+
+    let user_collection: Collection<User> = app_state.db.collection("users");
+
+    user_collection.insert_one(&user).await?;
+
+    // the account exists already, a missing welcome email is not worth failing for
+    if let Err(e) = send_template(
+        &app_state.db,
+        &app_state.settings.mail.brand,
+        Locale::parse(user.locale.as_deref().unwrap_or_default()),
+        &user.name,
+        &user.email,
+        TemplateData::Welcome,
+    )
+    .await
+    {
+        tracing::error!("failed to queue welcome email for {}: {:?}", user.email, e);
+    }
+
+    temp_user_collection
+        .delete_one(doc! {"_id": secret_token.value()})
+        .await?;
+
+    cookie.remove("session_token");
+    Ok((StatusCode::CREATED, "User created successfully"))
 }
 
+fn profile_name(name: &str) -> Result<String, AppError> {
+    let name = name.trim();
+    if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+        return Err(AppError::Validation(vec![FieldError::new(
+            "name",
+            format!("must be 1 to {} characters", MAX_NAME_LENGTH),
+        )]));
+    }
+    Ok(name.to_string())
+}
+
+/// Escapes user input so it is matched literally inside a `$regex`.
+fn escape_regex(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for c in input.chars() {
+        if "\\^$.|?*+()[]{}".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[debug_handler]
+pub async fn get_me(Extension(user): Extension<User>) -> Json<PublicUser> {
+    Json(user.into())
+}
+
+#[debug_handler]
+pub async fn update_me(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Json(input): Json<UpdateProfile>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut set = Document::new();
+
+    if let Some(name) = input.name {
+        set.insert("name", profile_name(&name)?);
+    }
+    if let Some(tag) = input.locale {
+        set.insert("locale", Locale::parse(&tag).code());
+    }
+
+    if set.is_empty() {
+        return Err(AppError::BadRequest("nothing to update".to_string()));
+    }
+
+    let collection: Collection<User> = app_state.db.collection("users");
+
+    let user = collection
+        .find_one_and_update(doc! {"_id": user.id}, doc! {"$set": set})
+        .return_document(ReturnDocument::After)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    Ok(Json(PublicUser::from(user)))
+}
+
+#[debug_handler]
+pub async fn delete_me(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    cookie: CookieManager,
+    Json(input): Json<DeleteAccount>,
+) -> Result<impl IntoResponse, AppError> {
+    if !verify_password(input.password, &user.password)? {
+        return Err(AppError::InvalidCredentials);
+    }
+
+    let user_id = current_user_id(&user)?;
+
+    let collection: Collection<User> = app_state.db.collection("users");
+    collection.delete_one(doc! {"_id": user_id}).await?;
+
+    revoke_user_sessions(&app_state.db, user_id).await?;
+    clear_auth_cookies(&cookie);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[debug_handler]
 pub async fn get_all_users(
     State(app_state): State<Arc<AppState>>,
-) -> Result<Json<Vec<User>>, (StatusCode, String)> {
+    Query(query): Query<UserQuery>,
+) -> Result<Json<Page<PublicUser>>, AppError> {
     let collection: Collection<User> = app_state.db.collection("users");
 
-    let mut users: Vec<User> = vec![];
+    let request = PageRequest::new(Keyset::NEWEST, query.cursor.as_deref(), query.limit)?;
 
-    let mut cursor = collection
-        .find(doc! {})
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let mut conditions = vec![];
 
-    while cursor
-        .advance()
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    if let Some(role) = query.role.filter(|r| !r.is_empty()) {
+        if role == DEFAULT_ROLE {
+            // accounts created before roles existed have no role at all
+            conditions.push(doc! {"$or": [{"role": &role}, {"role": null}]});
+        } else {
+            conditions.push(doc! {"role": &role});
+        }
+    }
+
+    if let Some(search) = query
+        .search
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
     {
-        users.push(
-            cursor
-                .deserialize_current()
-                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?,
-        );
+        let pattern = escape_regex(search);
+        conditions.push(doc! {"$or": [
+            {"name": {"$regex": &pattern, "$options": "i"}},
+            {"email": {"$regex": &pattern, "$options": "i"}},
+        ]});
     }
-    Ok(Json(users))
+
+    let filter = if conditions.is_empty() {
+        doc! {}
+    } else {
+        doc! {"$and": conditions}
+    };
+
+    let page = find_page(&collection, filter, &request).await?;
+
+    Ok(Json(page.map(PublicUser::from)))
 }
 
+#[debug_handler]
 pub async fn get_user_by_id(
     State(app_state): State<Arc<AppState>>,
     Path(id): Path<String>,
-) -> Result<Json<User>, (StatusCode, String)> {
-    let obj_id = match parse_object_id(id) {
-        Ok(id) => id,
-        Err(e) => return Err((e.0, e.1.to_string())),
-    };
+) -> Result<Json<PublicUser>, AppError> {
+    let obj_id = parse_object_id(id)?;
 
     let filter = doc! {"_id": obj_id};
 
     let collection: Collection<User> = app_state.db.collection("users");
 
-    let user = collection
-        .find_one(filter)
-        .await
-        .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+    let user = collection.find_one(filter).await?;
 
     match user {
-        Some(value) => Ok(Json(value)),
-        None => Err((StatusCode::NOT_FOUND, "User not found".to_string())),
+        Some(value) => Ok(Json(value.into())),
+        None => Err(AppError::NotFound("User not found".to_string())),
     }
 }
 
+/// An admin may only manage accounts whose role grants nothing more than
+/// their own, unless they may manage roles anyway.
+async fn ensure_outranks(
+    app_state: &AppState,
+    admin: &User,
+    credential: &Credential,
+    target: &User,
+) -> Result<(), AppError> {
+    let admin_role = admin.role.as_deref().unwrap_or(DEFAULT_ROLE);
+    let admin_access = app_state.roles.access(&app_state.db, admin_role).await?;
+
+    // acting with an API key is limited by that key's scopes too
+    let granted = |permission: &Permission| {
+        admin_access.permissions.contains(permission)
+            && match credential {
+                Credential::ApiKey { scopes, .. } => scopes.contains(permission),
+                Credential::Session(_) => true,
+            }
+    };
+
+    let target_role = target.role.as_deref().unwrap_or(DEFAULT_ROLE);
+    let target_access = app_state.roles.access(&app_state.db, target_role).await?;
+
+    if !target_access.permissions.iter().all(&granted) && !granted(&Permission::RoleManage) {
+        return Err(AppError::Forbidden(format!(
+            "{} can do more than you, managing them needs {}",
+            target.email,
+            Permission::RoleManage.as_str()
+        )));
+    }
+
+    Ok(())
+}
+
+#[debug_handler]
 pub async fn update_user(
     State(app_state): State<Arc<AppState>>,
+    Extension(admin): Extension<User>,
+    Extension(credential): Extension<Credential>,
     Path(id): Path<String>,
     Json(input): Json<UpdateUser>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<impl IntoResponse, AppError> {
     let collection: Collection<User> = app_state.db.collection("users");
-    let object_id = match parse_object_id(id) {
-        Ok(oid) => oid,
-        Err(_) => return Err(StatusCode::BAD_REQUEST),
-    };
+    let object_id = parse_object_id(id)?;
 
-    let filter = doc! {"_id": object_id};
-    let update = doc! {"$set": doc! {"name": &input.name, "age": input.age as i32}};
+    let target = collection
+        .find_one(doc! {"_id": object_id})
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+    ensure_outranks(&app_state, &admin, &credential, &target).await?;
 
-    let result = collection
-        .update_one(filter, update)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut set = Document::new();
+
+    if let Some(name) = input.name {
+        set.insert("name", profile_name(&name)?);
+    }
+    if let Some(email) = input.email {
+        let email = email.trim().to_lowercase();
+        let taken = collection
+            .find_one(doc! {"email": &email, "_id": {"$ne": object_id}})
+            .await?;
+        if taken.is_some() {
+            return Err(AppError::Conflict(
+                "user with this email already exists".to_string(),
+            ));
+        }
+        set.insert("email", email);
+    }
+    if let Some(tag) = input.locale {
+        set.insert("locale", Locale::parse(&tag).code());
+    }
 
-    if result.matched_count == 0 {
-        return Err(StatusCode::NOT_FOUND);
+    if set.is_empty() {
+        return Err(AppError::BadRequest("nothing to update".to_string()));
     }
 
-    Ok(StatusCode::NO_CONTENT)
+    let user = collection
+        .find_one_and_update(doc! {"_id": object_id}, doc! {"$set": set})
+        .return_document(ReturnDocument::After)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    Ok(Json(PublicUser::from(user)))
 }
 
+#[debug_handler]
 pub async fn delete_user(
     State(app_state): State<Arc<AppState>>,
+    Extension(admin): Extension<User>,
+    Extension(credential): Extension<Credential>,
     Path(id): Path<String>,
-) -> Result<Json<String>, (StatusCode, String)> {
-    let object_id = match parse_object_id(id) {
-        Ok(id) => id,
-        Err(e) => return Err((e.0, e.1.to_string())),
-    };
+) -> Result<Json<String>, AppError> {
+    let object_id = parse_object_id(id)?;
+
+    if current_user_id(&admin)? == object_id {
+        return Err(AppError::BadRequest(
+            "use DELETE /api/user/me to delete your own account".to_string(),
+        ));
+    }
 
     let collection: Collection<User> = app_state.db.collection("users");
 
+    let target = collection
+        .find_one(doc! {"_id": object_id})
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+    ensure_outranks(&app_state, &admin, &credential, &target).await?;
+
     let filter = doc! {"_id": object_id};
 
     collection
         .find_one_and_delete(filter)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    revoke_user_sessions(&app_state.db, object_id).await?;
 
     Ok(Json(String::from("user deleted success")))
 }
 
 #[debug_handler]
-pub async fn test_multipart(mut multipart: Multipart) -> impl IntoResponse {
-    while let Some(field) = multipart.next_field().await.unwrap() {
+pub async fn test_multipart(
+    State(app_state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, AppError> {
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|_| AppError::BadRequest("Failed to read multipart fields".to_string()))?
+    {
         let name = field.name().unwrap_or("unknown").to_string();
         let file_name = field.file_name().unwrap_or("unnamed").to_string();
-        let content_type = field.content_type().unwrap_or("unknown").to_string();
-        let data = field.bytes().await.unwrap();
+        let content_type = field
+            .content_type()
+            .unwrap_or("application/octet-stream")
+            .to_string();
+        let data = field
+            .bytes()
+            .await
+            .map_err(|_| AppError::BadRequest("Failed to read multipart fields".to_string()))?;
 
         if name == "avatar" {
-            let avatar_type = content_type.clone();
-            let avatar_name = file_name.clone();
-
-            let result = match upload_single(data.to_vec(), &avatar_type, &avatar_name).await {
-                Ok(url) => url,
-                Err(_) => {
-                    return Err((StatusCode::INTERNAL_SERVER_ERROR, "falied to upload avatar"))
-                }
-            };
+            let key = object_key("avatars", &file_name);
+            app_state
+                .storage
+                .put(&key, data.to_vec(), &content_type)
+                .await?;
 
-            return Ok(Json(result));
+            return Ok(Json(app_state.storage.public_url(&key)));
         }
     }
 
-    return Err((StatusCode::INTERNAL_SERVER_ERROR, "Server Error"));
+    Err(AppError::BadRequest(
+        "multipart field `avatar` is missing".to_string(),
+    ))
 }
@@ -0,0 +1,74 @@
+use std::{
+    io::ErrorKind,
+    path::{Component, Path, PathBuf},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+
+use crate::{config::settings::LocalStorageSettings, errors::app_error::AppError};
+
+use super::object_storage::ObjectStorage;
+
+/// Keeps objects as files below `root`, served back by the static route the
+/// app mounts at `LOCAL_STORAGE_ROUTE`. Meant for development and CI.
+pub struct LocalStorage {
+    root: PathBuf,
+    public_base: String,
+}
+
+impl LocalStorage {
+    pub fn new(settings: &LocalStorageSettings, public_base: String) -> Self {
+        LocalStorage {
+            root: PathBuf::from(&settings.root),
+            public_base,
+        }
+    }
+
+    fn path_of(&self, key: &str) -> Result<PathBuf, AppError> {
+        let relative = Path::new(key);
+
+        // keys are relative and may not climb out of the root
+        if relative
+            .components()
+            .any(|component| !matches!(component, Component::Normal(_)))
+        {
+            return Err(AppError::BadRequest(format!(
+                "invalid object key `{}`",
+                key
+            )));
+        }
+
+        Ok(self.root.join(relative))
+    }
+}
+
+#[async_trait]
+impl ObjectStorage for LocalStorage {
+    async fn put(&self, key: &str, bytes: Vec<u8>, _content_type: &str) -> Result<(), AppError> {
+        let path = self.path_of(key)?;
+
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(path, bytes).await?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), AppError> {
+        match tokio::fs::remove_file(self.path_of(key)?).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Local files are served publicly, there is nothing to sign.
+    async fn presign(&self, key: &str, _expires_in: Duration) -> Result<String, AppError> {
+        Ok(self.public_url(key))
+    }
+
+    fn public_url(&self, key: &str) -> String {
+        format!("{}/{}", self.public_base, key)
+    }
+}
@@ -0,0 +1,28 @@
+pub mod local;
+pub mod object_storage;
+pub mod s3;
+
+use std::sync::Arc;
+
+use crate::config::settings::{StorageBackendKind, StorageSettings};
+
+use self::{local::LocalStorage, object_storage::ObjectStorage, s3::S3Storage};
+
+/// Path the local backend's files are served under.
+pub const LOCAL_STORAGE_ROUTE: &str = "/uploads";
+
+pub async fn build_storage(settings: &StorageSettings, app_url: &str) -> Arc<dyn ObjectStorage> {
+    match settings.backend {
+        StorageBackendKind::S3 => Arc::new(S3Storage::new(&settings.s3).await),
+        StorageBackendKind::Local => {
+            tracing::warn!(
+                "storing uploads on the local disk in `{}`",
+                settings.local.root
+            );
+            Arc::new(LocalStorage::new(
+                &settings.local,
+                format!("{}{}", app_url, LOCAL_STORAGE_ROUTE),
+            ))
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use mongodb::bson::Uuid;
+
+use crate::errors::app_error::AppError;
+
+/// Somewhere uploaded files live. Implementations are held in `AppState` so
+/// handlers never depend on a concrete backend.
+#[async_trait]
+pub trait ObjectStorage: Send + Sync {
+    async fn put(&self, key: &str, bytes: Vec<u8>, content_type: &str) -> Result<(), AppError>;
+
+    /// Deleting a key that does not exist is not an error.
+    async fn delete(&self, key: &str) -> Result<(), AppError>;
+
+    /// A url that grants temporary read access to the object.
+    async fn presign(&self, key: &str, expires_in: Duration) -> Result<String, AppError>;
+
+    fn public_url(&self, key: &str) -> String;
+
+    /// The key behind a url returned by `public_url`, `None` for urls that do
+    /// not point into this storage.
+    fn key_from_url(&self, url: &str) -> Option<String> {
+        let prefix = self.public_url("");
+        url.strip_prefix(&prefix)
+            .filter(|key| !key.is_empty())
+            .map(|key| key.to_string())
+    }
+}
+
+/// A fresh key under `prefix` for an uploaded file. Only a safe subset of the
+/// client supplied name is kept so it can never escape the prefix.
+pub fn object_key(prefix: &str, file_name: &str) -> String {
+    let name = file_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+
+    format!(
+        "{}/{}.{}",
+        prefix,
+        Uuid::new(),
+        name.trim_start_matches('.')
+    )
+}
@@ -0,0 +1,104 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use aws_config::{BehaviorVersion, Region};
+use aws_sdk_s3::{presigning::PresigningConfig, primitives::ByteStream, Client};
+
+use crate::{config::settings::S3Settings, errors::app_error::AppError};
+
+use super::object_storage::ObjectStorage;
+
+pub struct S3Storage {
+    client: Client,
+    bucket_name: String,
+    public_base: String,
+}
+
+impl S3Storage {
+    /// Builds the client once, it is reused for every request.
+    pub async fn new(settings: &S3Settings) -> Self {
+        let config = aws_config::defaults(BehaviorVersion::latest())
+            .region(Region::new(settings.region.clone()))
+            .load()
+            .await;
+
+        let mut builder =
+            aws_sdk_s3::config::Builder::from(&config).force_path_style(settings.force_path_style);
+        if let Some(endpoint) = &settings.endpoint {
+            builder = builder.endpoint_url(endpoint);
+        }
+
+        S3Storage {
+            client: Client::from_conf(builder.build()),
+            bucket_name: settings.bucket_name.clone(),
+            public_base: public_base(settings),
+        }
+    }
+}
+
+/// Where objects of the bucket can be read from, without a trailing slash.
+fn public_base(settings: &S3Settings) -> String {
+    if let Some(public_url) = &settings.public_url {
+        return public_url.clone();
+    }
+
+    match &settings.endpoint {
+        Some(endpoint) if settings.force_path_style => {
+            format!("{}/{}", endpoint, settings.bucket_name)
+        }
+        Some(endpoint) => match endpoint.split_once("://") {
+            Some((scheme, host)) => format!("{}://{}.{}", scheme, settings.bucket_name, host),
+            None => format!("{}/{}", endpoint, settings.bucket_name),
+        },
+        None => format!(
+            "https://{}.s3.{}.amazonaws.com",
+            settings.bucket_name, settings.region
+        ),
+    }
+}
+
+#[async_trait]
+impl ObjectStorage for S3Storage {
+    async fn put(&self, key: &str, bytes: Vec<u8>, content_type: &str) -> Result<(), AppError> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .content_type(content_type)
+            .body(ByteStream::from(bytes))
+            .send()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), AppError> {
+        self.client
+            .delete_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .send()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn presign(&self, key: &str, expires_in: Duration) -> Result<String, AppError> {
+        let config = PresigningConfig::expires_in(expires_in)
+            .map_err(|e| AppError::Storage(e.to_string()))?;
+
+        let request = self
+            .client
+            .get_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .presigned(config)
+            .await?;
+
+        Ok(request.uri().to_string())
+    }
+
+    fn public_url(&self, key: &str) -> String {
+        format!("{}/{}", self.public_base, key)
+    }
+}
@@ -1,64 +1,164 @@
-use std::env;
+use std::{collections::HashMap, fs};
 
 use chrono::{Duration, Utc};
 use jsonwebtoken::{
-    decode, encode, errors::ErrorKind, DecodingKey, EncodingKey, Header, TokenData, Validation,
+    decode, decode_header, encode,
+    jwk::{AlgorithmParameters, EllipticCurve, JwkSet},
+    Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation,
 };
 use mongodb::bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    config::settings::{JwtAlgorithmKind, JwtSettings},
+    errors::app_error::AppError,
+};
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct MyClaims {
     pub user_id: ObjectId,
+    pub session_id: ObjectId,
+    iss: String,
     aud: String,
+    iat: u64,
     exp: u64,
 }
 
-pub fn create_token(user_id: ObjectId) -> String {
-    let exp = Utc::now() + Duration::hours(1);
+pub const ACCESS_TOKEN_TTL_MINUTES: i64 = 15;
+
+/// Key material for access tokens, read once at startup from the files named
+/// in `JwtSettings`.
+pub struct JwtKeys {
+    algorithm: Algorithm,
+    issuer: String,
+    audience: String,
+    signing_kid: String,
+    signing_key: EncodingKey,
+    verifying_keys: HashMap<String, DecodingKey>,
+    jwks: JwkSet,
+}
+
+impl JwtKeys {
+    pub fn load(settings: &JwtSettings) -> Result<Self, String> {
+        let algorithm = match settings.algorithm {
+            JwtAlgorithmKind::Rs256 => Algorithm::RS256,
+            JwtAlgorithmKind::EdDsa => Algorithm::EdDSA,
+        };
+
+        let pem = fs::read(&settings.private_key_file)
+            .map_err(|e| format!("failed to read {}: {}", settings.private_key_file, e))?;
+        let signing_key = match settings.algorithm {
+            JwtAlgorithmKind::Rs256 => EncodingKey::from_rsa_pem(&pem),
+            JwtAlgorithmKind::EdDsa => EncodingKey::from_ed_pem(&pem),
+        }
+        .map_err(|e| {
+            format!(
+                "{} is not a valid private key: {}",
+                settings.private_key_file, e
+            )
+        })?;
+
+        let content = fs::read_to_string(&settings.public_keys_file)
+            .map_err(|e| format!("failed to read {}: {}", settings.public_keys_file, e))?;
+        let jwks: JwkSet = serde_json::from_str(&content).map_err(|e| {
+            format!(
+                "{} is not a JWKS document: {}",
+                settings.public_keys_file, e
+            )
+        })?;
+
+        let mut verifying_keys = HashMap::new();
+        for jwk in &jwks.keys {
+            let kid =
+                jwk.common.key_id.clone().ok_or_else(|| {
+                    format!("every key in {} needs a kid", settings.public_keys_file)
+                })?;
+
+            // this document is published, so a symmetric key must never end up in it
+            let matches_algorithm = match (&jwk.algorithm, settings.algorithm) {
+                (AlgorithmParameters::RSA(_), JwtAlgorithmKind::Rs256) => true,
+                (AlgorithmParameters::OctetKeyPair(params), JwtAlgorithmKind::EdDsa) => {
+                    params.curve == EllipticCurve::Ed25519
+                }
+                _ => false,
+            };
+            if !matches_algorithm {
+                return Err(format!(
+                    "key `{}` in {} does not match JWT_ALGORITHM",
+                    kid, settings.public_keys_file
+                ));
+            }
+
+            let key = DecodingKey::from_jwk(jwk)
+                .map_err(|e| format!("key `{}` is not a valid public key: {}", kid, e))?;
+            if verifying_keys.insert(kid.clone(), key).is_some() {
+                return Err(format!("kid `{}` is listed twice", kid));
+            }
+        }
+
+        if !verifying_keys.contains_key(&settings.signing_kid) {
+            return Err(format!(
+                "JWT_SIGNING_KID `{}` has no public key in {}",
+                settings.signing_kid, settings.public_keys_file
+            ));
+        }
+
+        Ok(JwtKeys {
+            algorithm,
+            issuer: settings.issuer.clone(),
+            audience: settings.audience.clone(),
+            signing_kid: settings.signing_kid.clone(),
+            signing_key,
+            verifying_keys,
+            jwks,
+        })
+    }
+
+    /// Public keys other services verify our tokens with.
+    pub fn jwks(&self) -> &JwkSet {
+        &self.jwks
+    }
+}
+
+pub fn create_token(
+    keys: &JwtKeys,
+    user_id: ObjectId,
+    session_id: ObjectId,
+) -> Result<String, AppError> {
+    let now = Utc::now();
+    let exp = now + Duration::minutes(ACCESS_TOKEN_TTL_MINUTES);
     let my_claims = MyClaims {
         user_id,
-        aud: "example.com".to_string(),
+        session_id,
+        iss: keys.issuer.clone(),
+        aud: keys.audience.clone(),
+        iat: now.timestamp() as u64,
         exp: exp.timestamp() as u64,
     };
 
-    let secret = env::var("JWT_SECRET").expect("secret key not found");
-
-    let token = encode(
-        &Header::default(),
-        &my_claims,
-        &EncodingKey::from_secret(secret.as_ref()),
-    );
+    let mut header = Header::new(keys.algorithm);
+    header.kid = Some(keys.signing_kid.clone());
 
-    let access_token = match token {
-        Ok(token) => token,
-        Err(e) => e.to_string(),
-    };
+    let token = encode(&header, &my_claims, &keys.signing_key)?;
 
-    access_token
+    Ok(token)
 }
 
-pub fn decode_token(token: &str) -> Result<TokenData<MyClaims>, DecodeTokenError> {
-    let mut validation = Validation::default();
-    validation.set_audience(&["example.com"]);
+pub fn decode_token(keys: &JwtKeys, token: &str) -> Result<TokenData<MyClaims>, AppError> {
+    let header = decode_header(token)?;
 
-    let secret = env::var("JWT_SECRET").expect("secret key not found");
-    let key = &DecodingKey::from_secret(secret.as_ref());
+    // tokens signed with a key that has been rotated out are rejected here
+    let key = header
+        .kid
+        .as_ref()
+        .and_then(|kid| keys.verifying_keys.get(kid))
+        .ok_or(AppError::InvalidToken)?;
 
-    let token_data = match decode::<MyClaims>(&token, &key, &validation) {
-        Ok(token) => token,
-        Err(err) => match *err.kind() {
-            ErrorKind::InvalidToken => return Err(DecodeTokenError::InvalidToken),
-            ErrorKind::InvalidIssuer => return Err(DecodeTokenError::InvalidIssuer),
-            _ => return Err(DecodeTokenError::OtherError),
-        },
-    };
+    let mut validation = Validation::new(keys.algorithm);
+    validation.set_audience(&[&keys.audience]);
+    validation.set_issuer(&[&keys.issuer]);
 
-    Ok(token_data)
-}
+    let token_data = decode::<MyClaims>(token, key, &validation)?;
 
-pub enum DecodeTokenError {
-    InvalidToken,
-    InvalidIssuer,
-    OtherError,
+    Ok(token_data)
 }
@@ -1,6 +1,8 @@
 pub mod bcrypt;
 pub mod generate_otp;
 pub mod jwt;
+pub mod pagination;
 pub mod parse_id;
-pub mod s3;
-pub mod send_email;
+pub mod slug;
+pub mod token;
+pub mod totp;
@@ -0,0 +1,397 @@
+use data_encoding::BASE64URL_NOPAD;
+use mongodb::{
+    bson::{doc, from_document, oid::ObjectId, Bson, Document},
+    Collection,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::errors::app_error::AppError;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 20;
+pub const MAX_PAGE_SIZE: i64 = 100;
+
+/// Query of listings that take nothing but a page.
+#[derive(Debug, Deserialize)]
+pub struct PageQuery {
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// One page of a listing. `next_cursor` is passed back as `cursor` to get
+/// the following page and is `null` on the last one.
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub total: u64,
+}
+
+impl<T> Page<T> {
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            next_cursor: self.next_cursor,
+            total: self.total,
+        }
+    }
+}
+
+/// Order of a listing, by `field` and then newest `_id` first so that
+/// every item has a unique position a cursor can point at.
+#[derive(Debug, Clone, Copy)]
+pub struct Keyset {
+    name: &'static str,
+    field: Option<&'static str>,
+    ascending: bool,
+}
+
+impl Keyset {
+    pub const NEWEST: Keyset = Keyset {
+        name: "newest",
+        field: None,
+        ascending: false,
+    };
+
+    /// `name` is stored in the cursor, so a cursor can't be reused with
+    /// another order.
+    pub const fn by(name: &'static str, field: &'static str, ascending: bool) -> Self {
+        Keyset {
+            name,
+            field: Some(field),
+            ascending,
+        }
+    }
+
+    fn sort(&self) -> Document {
+        let mut sort = Document::new();
+        if let Some(field) = self.field {
+            sort.insert(field, if self.ascending { 1 } else { -1 });
+        }
+        sort.insert("_id", -1);
+        sort
+    }
+}
+
+/// The last item of the previous page.
+struct Position {
+    key: Bson,
+    id: ObjectId,
+}
+
+fn invalid_cursor() -> AppError {
+    AppError::BadRequest("invalid cursor".to_string())
+}
+
+fn encode_cursor(keyset: &Keyset, item: &Document) -> Option<String> {
+    let mut position = doc! {"s": keyset.name, "id": item.get_object_id("_id").ok()?};
+    // a missing field sorts like null, so that is where the next page starts
+    if let Some(field) = keyset.field {
+        position.insert("k", item.get(field).cloned().unwrap_or(Bson::Null));
+    }
+
+    let mut bytes = vec![];
+    position.to_writer(&mut bytes).ok()?;
+    Some(BASE64URL_NOPAD.encode(&bytes))
+}
+
+fn decode_cursor(keyset: &Keyset, cursor: &str) -> Result<Position, AppError> {
+    let bytes = BASE64URL_NOPAD
+        .decode(cursor.as_bytes())
+        .map_err(|_| invalid_cursor())?;
+    let position = Document::from_reader(&bytes[..]).map_err(|_| invalid_cursor())?;
+
+    if position.get_str("s").ok() != Some(keyset.name) {
+        return Err(AppError::BadRequest(
+            "cursor belongs to another sort order".to_string(),
+        ));
+    }
+    let id = position.get_object_id("id").map_err(|_| invalid_cursor())?;
+
+    // cursors come from the client, only plain values may end up in a filter
+    let key = match (keyset.field, position.get("k")) {
+        (None, _) => Bson::Null,
+        (
+            Some(_),
+            Some(
+                key @ (Bson::Null
+                | Bson::Double(_)
+                | Bson::Int32(_)
+                | Bson::Int64(_)
+                | Bson::String(_)
+                | Bson::DateTime(_)
+                | Bson::ObjectId(_)),
+            ),
+        ) => key.clone(),
+        (Some(_), _) => return Err(invalid_cursor()),
+    };
+
+    Ok(Position { key, id })
+}
+
+/// A requested page: its size and where the previous one ended.
+pub struct PageRequest {
+    limit: i64,
+    keyset: Keyset,
+    after: Option<Position>,
+}
+
+impl PageRequest {
+    pub fn new(keyset: Keyset, cursor: Option<&str>, limit: Option<i64>) -> Result<Self, AppError> {
+        let after = match cursor.map(str::trim).filter(|c| !c.is_empty()) {
+            Some(cursor) => Some(decode_cursor(&keyset, cursor)?),
+            None => None,
+        };
+
+        Ok(PageRequest {
+            limit: limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE),
+            keyset,
+            after,
+        })
+    }
+
+    /// Matches the items after the cursor, everything on the first page.
+    pub fn filter(&self) -> Document {
+        let Some(position) = &self.after else {
+            return doc! {};
+        };
+        let older = doc! {"_id": {"$lt": position.id}};
+
+        match self.keyset.field {
+            None => older,
+            Some(field) => {
+                let mut tied = older;
+                tied.insert(field, position.key.clone());
+
+                // nulls and missing fields come first ascending and last
+                // descending, and no comparison operator matches them
+                let beyond = match (self.keyset.ascending, &position.key) {
+                    (true, Bson::Null) => vec![doc! {field: {"$ne": null}}],
+                    (true, key) => vec![doc! {field: {"$gt": key.clone()}}],
+                    (false, Bson::Null) => vec![],
+                    (false, key) => vec![doc! {field: {"$lt": key.clone()}}, doc! {field: null}],
+                };
+
+                let mut branches = beyond;
+                branches.push(tied);
+                doc! {"$or": branches}
+            }
+        }
+    }
+
+    pub fn sort(&self) -> Document {
+        self.keyset.sort()
+    }
+
+    /// One more than the page size, the extra item only tells whether there
+    /// is a next page.
+    pub fn fetch_limit(&self) -> i64 {
+        self.limit + 1
+    }
+
+    /// Builds the page from documents fetched with `filter`, `sort` and
+    /// `fetch_limit`. They have to include the sort field.
+    pub fn page<T: DeserializeOwned>(
+        &self,
+        mut documents: Vec<Document>,
+        total: u64,
+    ) -> Result<Page<T>, AppError> {
+        let has_more = documents.len() as i64 > self.limit;
+        documents.truncate(self.limit as usize);
+
+        let next_cursor = match (has_more, documents.last()) {
+            (true, Some(last)) => encode_cursor(&self.keyset, last),
+            _ => None,
+        };
+
+        let items = documents
+            .into_iter()
+            .map(from_document)
+            .collect::<Result<Vec<T>, _>>()
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+
+        Ok(Page {
+            items,
+            next_cursor,
+            total,
+        })
+    }
+}
+
+/// Runs `filter` on `collection` and returns the requested page of it.
+pub async fn find_page<T: DeserializeOwned + Send + Sync>(
+    collection: &Collection<T>,
+    filter: Document,
+    request: &PageRequest,
+) -> Result<Page<T>, AppError> {
+    let collection = collection.clone_with_type::<Document>();
+    let total = collection.count_documents(filter.clone()).await?;
+
+    let mut cursor = collection
+        .find(doc! {"$and": [filter, request.filter()]})
+        .sort(request.sort())
+        .limit(request.fetch_limit())
+        .await?;
+
+    let mut documents = vec![];
+    while cursor.advance().await? {
+        documents.push(cursor.deserialize_current()?);
+    }
+
+    request.page(documents, total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BY_PRICE: Keyset = Keyset::by("price", "price", true);
+    const BY_RATING: Keyset = Keyset::by("rating", "rating", false);
+
+    fn raw_cursor(position: Document) -> String {
+        let mut bytes = vec![];
+        position.to_writer(&mut bytes).unwrap();
+        BASE64URL_NOPAD.encode(&bytes)
+    }
+
+    /// A page of `size` items out of `documents`, returning its cursor.
+    fn next_cursor(keyset: Keyset, documents: Vec<Document>, size: i64) -> Option<String> {
+        let request = PageRequest::new(keyset, None, Some(size)).unwrap();
+        request.page::<Document>(documents, 0).unwrap().next_cursor
+    }
+
+    #[test]
+    fn cursor_round_trip() {
+        let id = ObjectId::new();
+        let keys = [
+            Bson::Double(19.99),
+            Bson::Int32(3),
+            Bson::Int64(4),
+            Bson::String("b".to_string()),
+            Bson::ObjectId(ObjectId::new()),
+            Bson::Null,
+        ];
+
+        for key in keys {
+            let cursor = encode_cursor(&BY_PRICE, &doc! {"_id": id, "price": key.clone()}).unwrap();
+            let position = decode_cursor(&BY_PRICE, &cursor).unwrap();
+            assert_eq!(position.id, id);
+            assert_eq!(position.key, key);
+        }
+
+        let cursor = encode_cursor(&Keyset::NEWEST, &doc! {"_id": id}).unwrap();
+        assert_eq!(decode_cursor(&Keyset::NEWEST, &cursor).unwrap().id, id);
+    }
+
+    #[test]
+    fn missing_sort_field_still_has_a_next_page() {
+        let documents = vec![
+            doc! {"_id": ObjectId::new(), "rating": 4.5},
+            doc! {"_id": ObjectId::new()},
+            doc! {"_id": ObjectId::new()},
+        ];
+
+        let cursor = next_cursor(BY_RATING, documents, 2).expect("a next cursor");
+        assert_eq!(decode_cursor(&BY_RATING, &cursor).unwrap().key, Bson::Null);
+    }
+
+    #[test]
+    fn last_page_has_no_cursor() {
+        let documents = vec![doc! {"_id": ObjectId::new(), "rating": 4.5}];
+        assert_eq!(next_cursor(BY_RATING, documents, 2), None);
+    }
+
+    #[test]
+    fn rejects_tampered_cursors() {
+        let id = ObjectId::new();
+        let valid = encode_cursor(&BY_PRICE, &doc! {"_id": id, "price": 10}).unwrap();
+
+        let mut flipped = BASE64URL_NOPAD.decode(valid.as_bytes()).unwrap();
+        flipped[0] ^= 0xff;
+
+        let cases = [
+            ("not base64", "***".to_string()),
+            ("not bson", BASE64URL_NOPAD.encode(b"hello")),
+            ("corrupted", BASE64URL_NOPAD.encode(&flipped)),
+            (
+                "operator as key",
+                raw_cursor(doc! {"s": "price", "id": id, "k": {"$gt": ""}}),
+            ),
+            (
+                "array as key",
+                raw_cursor(doc! {"s": "price", "id": id, "k": [1, 2]}),
+            ),
+            (
+                "regex as key",
+                raw_cursor(doc! {"s": "price", "id": id, "k": Bson::RegularExpression(
+                    mongodb::bson::Regex { pattern: ".*".to_string(), options: String::new() }
+                )}),
+            ),
+            ("no key", raw_cursor(doc! {"s": "price", "id": id})),
+            (
+                "id not an ObjectId",
+                raw_cursor(doc! {"s": "price", "id": "x", "k": 1}),
+            ),
+            (
+                "other sort order",
+                encode_cursor(&BY_RATING, &doc! {"_id": id, "rating": 1}).unwrap(),
+            ),
+        ];
+
+        for (name, cursor) in cases {
+            assert!(
+                decode_cursor(&BY_PRICE, &cursor).is_err(),
+                "{} was accepted",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn filters_after_the_cursor() {
+        let id = ObjectId::new();
+        let request = |keyset: Keyset, key: Bson| {
+            let cursor = raw_cursor(doc! {"s": keyset.name, "id": id, "k": key});
+            PageRequest::new(keyset, Some(&cursor), None)
+                .unwrap()
+                .filter()
+        };
+
+        assert_eq!(
+            request(BY_PRICE, Bson::Int32(10)),
+            doc! {"$or": [{"price": {"$gt": 10}}, {"_id": {"$lt": id}, "price": 10}]}
+        );
+        assert_eq!(
+            request(BY_PRICE, Bson::Null),
+            doc! {"$or": [{"price": {"$ne": null}}, {"_id": {"$lt": id}, "price": null}]}
+        );
+        assert_eq!(
+            request(BY_RATING, Bson::Double(4.0)),
+            doc! {"$or": [
+                {"rating": {"$lt": 4.0}},
+                {"rating": null},
+                {"_id": {"$lt": id}, "rating": 4.0},
+            ]}
+        );
+        assert_eq!(
+            request(BY_RATING, Bson::Null),
+            doc! {"$or": [{"_id": {"$lt": id}, "rating": null}]}
+        );
+
+        let newest = raw_cursor(doc! {"s": "newest", "id": id});
+        assert_eq!(
+            PageRequest::new(Keyset::NEWEST, Some(&newest), None)
+                .unwrap()
+                .filter(),
+            doc! {"_id": {"$lt": id}}
+        );
+    }
+
+    #[test]
+    fn clamps_the_page_size() {
+        let size = |limit| PageRequest::new(Keyset::NEWEST, None, limit).unwrap().limit;
+
+        assert_eq!(size(None), DEFAULT_PAGE_SIZE);
+        assert_eq!(size(Some(0)), 1);
+        assert_eq!(size(Some(MAX_PAGE_SIZE + 1)), MAX_PAGE_SIZE);
+    }
+}
@@ -1,10 +1,7 @@
-use axum::http::StatusCode;
 use mongodb::bson::oid::ObjectId;
 
-pub fn parse_object_id(id: String) -> Result<ObjectId, (StatusCode, String)> {
-    let object_id = match ObjectId::parse_str(&id) {
-        Ok(oid) => Ok(oid),
-        Err(_) => Err((StatusCode::BAD_REQUEST, "Invalid Id".to_string())),
-    };
-    return object_id;
+use crate::errors::app_error::AppError;
+
+pub fn parse_object_id(id: String) -> Result<ObjectId, AppError> {
+    ObjectId::parse_str(&id).map_err(|_| AppError::InvalidId)
 }
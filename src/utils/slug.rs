@@ -0,0 +1,31 @@
+use crate::errors::app_error::FieldError;
+
+const MAX_SLUG_LENGTH: usize = 80;
+
+/// Lowercase ascii words joined by dashes, e.g. "Men's Shoes" becomes
+/// `men-s-shoes`.
+pub fn slugify(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>()
+        .join("-")
+}
+
+pub fn validate_slug(slug: &str) -> Option<FieldError> {
+    if slug.is_empty() || slug.len() > MAX_SLUG_LENGTH {
+        return Some(FieldError::new(
+            "slug",
+            format!("slug must be 1 to {} characters", MAX_SLUG_LENGTH),
+        ));
+    }
+
+    if slug != slugify(slug) {
+        return Some(FieldError::new(
+            "slug",
+            "slug may only contain lowercase letters, digits and single dashes",
+        ));
+    }
+
+    None
+}